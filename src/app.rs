@@ -1,15 +1,39 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::{Receiver, Sender};
-use iced::keyboard;
-use iced::widget::{canvas, center, column, container, text};
-use iced::{Element, Length, Subscription, Task, Theme};
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, StreamExt};
+use iced::widget::{canvas, center, column, container, image, progress_bar, row, stack, text, Row};
+use iced::{keyboard, window};
+use iced::{theme, Element, Length, Point, Size, Subscription, Task, Theme};
 
-use crate::audio::decoder;
+use crate::audio::analysis::{self, ChannelLayout, Key, NoteReading};
+use crate::audio::decoder::{self, AudioInfo, DecodePolicy, DecodeReport};
 use crate::audio::engine;
+use crate::audio::export::{self, ExportChannels};
+use crate::audio::filter::{MAX_CUTOFF_HZ, MIN_CUTOFF_HZ};
 use crate::audio::types::*;
-use crate::ui::controls::{self, ControlMessage};
-use crate::ui::waveform::{WaveformMessage, WaveformView};
+use crate::download;
+use crate::media_keys::{MediaKey, MediaKeys};
+use crate::midi::{self, MidiAction, MidiConfig, MidiListener};
+use crate::presets::{self, DspPreset};
+use crate::recent::{self, RecentFile};
+use crate::resume::{ResumePoint, ResumeState};
+use crate::session::{Annotations, Session};
+use crate::settings::{Settings, ThemeChoice, WindowGeometry};
+use crate::ui::context_menu::{self, ContextMenu};
+use crate::ui::controls::{
+    self, shuttle_speed, volume_gain, ControlMessage, ControlState, EqBand, MAX_TEMPO, MIN_TEMPO,
+};
+use crate::ui::format::{format_time, parse_time};
+use crate::ui::meter::LevelMeter;
+use crate::ui::minimap;
+use crate::ui::playlist::{self, PlaylistMessage};
+use crate::ui::tuner;
+use crate::ui::waveform::{self, WaveformMessage, WaveformView};
 use crate::waveform_cache::WaveformPeaks;
 
 pub struct App {
@@ -22,59 +46,681 @@ pub struct App {
     position: f64,
     duration: f64,
     tempo: f32,
+    /// Shuttle control position while it is held, 0 otherwise.
+    shuttle: f32,
+    /// Playback was started by the shuttle, and pauses again when it's let go.
+    shuttle_started: bool,
+    /// Pitch shift in semitones.
+    pitch: f32,
+    /// Fine pitch adjustment in cents, added to `pitch`.
+    pitch_cents: f32,
+    /// Volume slider position; the output gain is its square.
+    volume: f32,
+    muted: bool,
+    channel_mode: ChannelMode,
+    /// Fraction of centre-panned content removed from stereo files.
+    vocal_reduction: f32,
+    /// Stereo width; 1.0 leaves the image as recorded.
+    stereo_width: f32,
+    /// EQ band gains in dB.
+    eq_low: f32,
+    eq_mid: f32,
+    eq_high: f32,
+    /// Filter cutoffs in Hz; fully open by default.
+    hp_hz: f32,
+    lp_hz: f32,
+    /// Saved EQ, filter and stereo settings, sorted by name.
+    presets: Vec<DspPreset>,
+    /// Name typed for saving the current settings as a preset.
+    preset_name_input: String,
+    /// Beat times of the loaded file as detected, in seconds.
+    detected_beats: Vec<f64>,
+    /// Seconds the detected beats are moved by to put the first on a downbeat.
+    downbeat_offset: f64,
+    /// The beat grid in use: the detected beats moved by `downbeat_offset`.
+    beats: Vec<f64>,
+    metronome_enabled: bool,
+    /// Flash a light on each beat, whether or not the metronome is heard.
+    beat_flash: bool,
+    metronome_gain: f32,
+    beats_per_bar: u32,
+    /// Bars of clicks before a loop starts or restarts.
+    count_in_bars: u32,
     loop_region: Option<(f64, f64)>,
+    /// Loops stored with Shift+1..9 and recalled with 1..9, in seconds.
+    loops: Vec<Option<(f64, f64)>>,
+    loop_mode: LoopMode,
+    /// Loop boundaries are moved to nearby zero crossings by the engine.
+    loop_snap: bool,
+    /// Selected loops are rounded to the nearest detected beats.
+    beat_snap: bool,
+    /// Crossfade at the loop seam, in seconds.
+    loop_crossfade: f32,
+    /// Step for the loop nudge keys, in seconds.
+    loop_nudge: f64,
+    /// Step for Shift+arrow seeking, in seconds.
+    fine_step: f64,
+    go_to_input: String,
+    /// A minor input mistake being reported, and when its message goes away.
+    transient_error: Option<(String, Instant)>,
+    /// Loop passes before `after_loops` applies; `None` loops forever.
+    loop_repeats: Option<u32>,
+    loop_repeats_input: String,
+    after_loops: AfterLoops,
+    /// Tempo of the recording itself in BPM, entered by the user.
+    original_bpm: Option<f32>,
+    original_bpm_input: String,
+    target_bpm_input: String,
+    /// Speed up (or slow down) by a step each time the loop comes round.
+    tempo_ramp_enabled: bool,
+    tempo_ramp_start_input: String,
+    tempo_ramp_target_input: String,
+    tempo_ramp_step_input: String,
+    stop_behavior: StopBehavior,
+    show_stop_button: bool,
+    /// Apply the file's ReplayGain tags on top of the volume.
+    replay_gain: bool,
     filename: Option<String>,
+    /// Path of the loaded file, saved into sessions.
+    file_path: Option<PathBuf>,
+    /// Recently opened files, most recent first.
+    recent_files: Vec<RecentFile>,
+    /// Files queued to play one after another.
+    playlist: Vec<PathBuf>,
+    /// Entry of `playlist` that is loaded, if the loaded file is in it.
+    playlist_index: Option<usize>,
+    /// Tempo and pitch carry over to the next entry instead of resetting.
+    playlist_keep_settings: bool,
+    /// Start playing once the file being loaded is ready.
+    play_on_load: bool,
+    /// Queue entry being decoded ahead of time, so it starts without a wait.
+    preload_path: Option<PathBuf>,
+    /// The decoded `preload_path`, once ready.
+    preloaded: Option<DecodedFile>,
+    /// Where each file was left off, restored when it is reopened.
+    resume: ResumeState,
+    /// When the current file's resume point was last recorded.
+    last_resume_save: Instant,
+    /// Session whose settings are applied once its audio file finishes loading.
+    pending_session: Option<Session>,
+    channel_layout: Option<ChannelLayout>,
+    /// Detected key of the file, once analysis has finished.
+    key: Option<Key>,
+    /// Codec and format of the loaded file, as stored.
+    audio_info: Option<AudioInfo>,
+    /// Embedded cover art, ready to draw.
+    cover: Option<image::Handle>,
+    /// Nearest note to the pitch at the playhead, if there is a clear one.
+    tuner_reading: Option<NoteReading>,
+    /// When the tuner was last updated.
+    last_tuner_update: Instant,
+    /// Position the tuner last read at, so a paused playhead is read once.
+    tuner_position: Option<f64>,
+    tuner_enabled: bool,
+    /// When `position` was last set by the engine or a seek; the playhead is
+    /// drawn ahead of it by the time since, so it moves smoothly.
+    position_updated: Instant,
     error: Option<String>,
+    warning: Option<String>,
+    /// Output underruns since launch.
+    underruns: u64,
+    /// Output peak and RMS levels, from the engine's level reports.
+    level_meter: LevelMeter,
+
+    // Output device selection
+    output_devices: Vec<String>,
+    output_device: Option<String>,
+
+    /// Theme and waveform colours, saved to the config directory.
+    settings: Settings,
+    /// Light or dark preference reported by the system.
+    system_theme: theme::Mode,
+    /// Current window size and position, saved on close.
+    window: WindowGeometry,
+
+    // MIDI foot controller
+    midi_config: MidiConfig,
+    midi_ports: Vec<String>,
+    midi: Option<MidiListener>,
+    /// Global media key hotkeys, while enabled.
+    media_keys: Option<MediaKeys>,
+    /// Resume playback once the engine restarts after losing its device.
+    resume_on_engine_ready: bool,
 
     // Waveform
     waveform_view: WaveformView,
     audio_data: Option<Arc<AudioData>>,
+    /// Decoded fraction of the file being loaded, while a load is running.
+    decode_progress: Option<f32>,
+    /// Files estimated to need more than this are confirmed before decoding.
+    memory_budget_bytes: u64,
+    /// How undecodable packets are handled on the next load.
+    decode_policy: DecodePolicy,
+    /// Channel layout of exported files.
+    export_channels: ExportChannels,
+    /// When the engine's lock-free counters were last drained into the log.
+    last_counter_drain: Instant,
+    /// The latest load, so results from superseded ones can be dropped.
+    load: LoadTracker,
+    /// URL typed into the open-URL field.
+    url_input: String,
+    /// Downloaded fraction of a URL being fetched, while a download is running.
+    download_progress: Option<f32>,
+    /// Incremented per download so superseded ones can be ignored.
+    download_id: u64,
+    /// Set to stop the download that is currently running.
+    download_cancel: Option<Arc<AtomicBool>>,
+
+    /// Cue markers as (time in seconds, label), sorted by time.
+    markers: Vec<(f64, String)>,
+    marker_label_input: String,
 
     // Drag state for loop selection
     drag_start: Option<f64>,
+    /// A loop edge or the whole loop is being dragged.
+    loop_editing: bool,
+    /// Open right-click menu over the waveform.
+    context_menu: Option<ContextMenu>,
+    /// The time readout counts down to the end instead of up.
+    show_remaining: bool,
+
+    /// Scroll a zoomed waveform along with the playhead.
+    follow_playhead: bool,
+    /// The waveform was scrolled away from the playhead by hand; following
+    /// resumes once the playhead is back in view.
+    follow_suspended: bool,
+
+    // Fixed-length loop selection
+    loop_length_locked: bool,
+    locked_loop_length: f64,
+    loop_length_input: String,
 }
 
+/// How often the audio engine's counters are written to the log.
+const COUNTER_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Crossfade at the loop seam until the user changes it, in seconds.
+const DEFAULT_LOOP_CROSSFADE: f32 = 0.01;
+
+/// Step for the loop nudge keys until the user changes it, in seconds.
+const DEFAULT_LOOP_NUDGE: f64 = 0.01;
+
+/// How long an input mistake is reported before the message clears.
+const TRANSIENT_ERROR_DURATION: Duration = Duration::from_secs(4);
+
+/// Step for Shift+arrow seeking until the user changes it, in seconds.
+const DEFAULT_FINE_STEP: f64 = 0.1;
+
+/// How often the current file's position is recorded for resuming later.
+const RESUME_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the tuner re-estimates the pitch at the playhead while playing.
+const TUNER_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Furthest the drawn playhead runs ahead of the last reported position, so
+/// it can't drift far when position updates stop (paused, counting in).
+const MAX_PLAYHEAD_INTERPOLATION: Duration = Duration::from_millis(100);
+
+/// Where a followed playhead lands after paging, as a fraction of the
+/// visible width from the left edge.
+const FOLLOW_MARGIN: f64 = 0.05;
+
+/// Seek step for Alt+arrow, in seconds.
+const ALT_SEEK_STEP: f64 = 1.0;
+
+/// Tempo change per MIDI tempo up/down press, as a speed ratio.
+const MIDI_TEMPO_STEP: f32 = 0.05;
+
+/// Number of stored loop slots, one per digit key.
+const LOOP_SLOTS: usize = 9;
+
+/// How close (in seconds) the playhead must be to a marker to count as on it
+/// when jumping to the previous or next marker.
+const MARKER_JUMP_TOLERANCE: f64 = 0.05;
+
+/// Loop length used when "lock loop length" is first enabled, in seconds.
+const DEFAULT_LOCKED_LOOP_LENGTH: f64 = 4.0;
+
 #[derive(Debug, Clone)]
 pub enum Message {
     EngineReady(Result<(Sender<AudioCommand>, Receiver<AudioEvent>), String>),
-    FileLoaded(Result<(AudioData, String), String>),
+    DevicesListed(Vec<String>),
+    SystemThemeChanged(theme::Mode),
+    WindowResized(Size),
+    WindowMoved(Point),
+    /// The window is closing; release global hotkeys before exiting.
+    CloseRequested,
+    MidiPortsListed(Vec<String>),
+    /// Fraction of the file decoded so far, tagged with its load id.
+    DecodeProgress(u64, f32),
+    /// Fraction of a URL downloaded so far, tagged with its download id.
+    DownloadProgress(u64, f32),
+    Downloaded(u64, Result<PathBuf, String>),
+    FileLoaded(u64, Result<DecodedFile, String>),
+    /// A queue entry decoded ahead of time.
+    Preloaded(PathBuf, Result<DecodedFile, String>),
+    PeaksComputed(u64, WaveformPeaks),
+    /// Beat times in seconds for the given load.
+    BeatsDetected(u64, Vec<f64>),
+    /// Key of the given load, if a clear one was found.
+    KeyDetected(u64, Option<Key>),
     Control(ControlMessage),
     Waveform(WaveformMessage),
     Tick,
     KeyEvent(keyboard::Event),
-    FileDialogResult(Option<std::path::PathBuf>),
+    FileDialogResult(Option<PathBuf>),
+    QueuePicked(Option<Vec<PathBuf>>),
+    Playlist(PlaylistMessage),
+    FileEstimated(PathBuf, Result<Option<u64>, String>),
+    LargeFileConfirmed(PathBuf, bool),
+    SessionSavePicked(Option<PathBuf>),
+    SessionLoaded(Option<Result<Session, String>>),
+    AnnotationsSavePicked(Option<PathBuf>),
+    AnnotationsLoaded(Option<Result<Annotations, String>>),
+    ExportPicked(Option<PathBuf>),
+    ExportFinished(Result<PathBuf, String>),
 }
 
+/// A decoded file with its details, ready to load.
+type DecodedFile = (AudioData, AudioInfo, DecodeReport, PathBuf);
+
 fn boot() -> (App, Task<Message>) {
-    let app = App {
+    let settings = Settings::load();
+    let window = initial_window(&settings);
+    let mut app = App {
         cmd_tx: None,
         event_rx: None,
         status: PlaybackStatus::Stopped,
         position: 0.0,
         duration: 0.0,
         tempo: 1.0,
+        shuttle: 0.0,
+        shuttle_started: false,
+        pitch: 0.0,
+        pitch_cents: 0.0,
+        volume: 1.0,
+        muted: false,
+        channel_mode: ChannelMode::default(),
+        vocal_reduction: 0.0,
+        stereo_width: 1.0,
+        eq_low: 0.0,
+        eq_mid: 0.0,
+        eq_high: 0.0,
+        hp_hz: MIN_CUTOFF_HZ,
+        lp_hz: MAX_CUTOFF_HZ,
+        presets: presets::load(),
+        preset_name_input: String::new(),
+        detected_beats: Vec::new(),
+        downbeat_offset: 0.0,
+        beats: Vec::new(),
+        metronome_enabled: false,
+        beat_flash: false,
+        metronome_gain: 0.5,
+        beats_per_bar: 4,
+        count_in_bars: 0,
         loop_region: None,
+        loops: vec![None; LOOP_SLOTS],
+        loop_mode: LoopMode::default(),
+        loop_snap: true,
+        beat_snap: false,
+        loop_crossfade: DEFAULT_LOOP_CROSSFADE,
+        loop_nudge: DEFAULT_LOOP_NUDGE,
+        fine_step: DEFAULT_FINE_STEP,
+        go_to_input: String::new(),
+        transient_error: None,
+        loop_repeats: None,
+        loop_repeats_input: String::new(),
+        after_loops: AfterLoops::default(),
+        original_bpm: None,
+        original_bpm_input: String::new(),
+        target_bpm_input: String::new(),
+        tempo_ramp_enabled: false,
+        tempo_ramp_start_input: "70".to_string(),
+        tempo_ramp_target_input: "100".to_string(),
+        tempo_ramp_step_input: "5".to_string(),
+        stop_behavior: StopBehavior::default(),
+        show_stop_button: true,
+        replay_gain: true,
         filename: None,
+        file_path: None,
+        recent_files: recent::load(),
+        playlist: Vec::new(),
+        playlist_index: None,
+        playlist_keep_settings: false,
+        play_on_load: false,
+        preload_path: None,
+        preloaded: None,
+        resume: ResumeState::load(),
+        last_resume_save: Instant::now(),
+        pending_session: None,
+        channel_layout: None,
+        key: None,
+        audio_info: None,
+        cover: None,
+        tuner_reading: None,
+        last_tuner_update: Instant::now(),
+        tuner_position: None,
+        tuner_enabled: true,
+        position_updated: Instant::now(),
         error: None,
+        warning: None,
+        underruns: 0,
+        level_meter: LevelMeter::default(),
+        output_devices: Vec::new(),
+        output_device: None,
+        settings,
+        system_theme: theme::Mode::None,
+        window,
+        midi_config: MidiConfig::load(),
+        midi_ports: Vec::new(),
+        midi: None,
+        media_keys: None,
+        resume_on_engine_ready: false,
         waveform_view: WaveformView::new(),
         audio_data: None,
+        decode_progress: None,
+        memory_budget_bytes: memory_budget_bytes(),
+        decode_policy: DecodePolicy::default(),
+        export_channels: ExportChannels::default(),
+        last_counter_drain: Instant::now(),
+        load: LoadTracker::default(),
+        url_input: String::new(),
+        download_progress: None,
+        download_id: 0,
+        download_cancel: None,
+        markers: Vec::new(),
+        marker_label_input: String::new(),
         drag_start: None,
+        loop_editing: false,
+        context_menu: None,
+        show_remaining: false,
+        follow_playhead: true,
+        follow_suspended: false,
+        loop_length_locked: false,
+        locked_loop_length: DEFAULT_LOCKED_LOOP_LENGTH,
+        loop_length_input: DEFAULT_LOCKED_LOOP_LENGTH.to_string(),
     };
 
-    let task = Task::perform(
+    apply_theme(&mut app);
+    app.waveform_view.set_style(app.settings.waveform_style);
+    let mut tasks = vec![
+        spawn_engine_task(None),
+        list_devices_task(),
+        list_midi_ports_task(),
+        iced::system::theme().map(Message::SystemThemeChanged),
+    ];
+    // `transcribe song.mp3` opens the file straight away; problems with it
+    // are reported like any other failed open
+    if let Some(arg) = std::env::args_os().nth(1) {
+        tracing::info!(location = %arg.to_string_lossy(), "opening file from the command line");
+        match arg.to_str().filter(|s| download::is_url(s)) {
+            Some(url) => tasks.push(start_download(&mut app, url.to_string())),
+            None => tasks.push(open_file(PathBuf::from(arg))),
+        }
+    }
+    let task = Task::batch(tasks);
+
+    (app, task)
+}
+
+fn list_devices_task() -> Task<Message> {
+    Task::perform(
         async {
-            tokio::task::spawn_blocking(engine::spawn_engine)
+            tokio::task::spawn_blocking(engine::output_device_names)
                 .await
                 .unwrap()
         },
-        Message::EngineReady,
-    );
+        Message::DevicesListed,
+    )
+}
 
-    (app, task)
+fn list_midi_ports_task() -> Task<Message> {
+    Task::perform(
+        async {
+            tokio::task::spawn_blocking(midi::input_port_names)
+                .await
+                .unwrap()
+        },
+        Message::MidiPortsListed,
+    )
+}
+
+/// Restart the engine on the default output after the current device was
+/// disconnected, carrying on playback if it was playing.
+fn recover_from_device_loss(app: &mut App) -> Task<Message> {
+    tracing::warn!(device = ?app.output_device, "output device lost, switching to default");
+    // The stream is already closed, so there is nothing to shut down
+    app.cmd_tx = None;
+    app.event_rx = None;
+    app.resume_on_engine_ready = app.status == PlaybackStatus::Playing;
+    app.output_device = None;
+    app.error = Some("Output device disconnected; switched to the default output.".to_string());
+    Task::batch([spawn_engine_task(None), list_devices_task()])
+}
+
+/// Estimate the memory `path` needs, then decode it (after confirmation if
+/// it's over budget).
+fn open_file(path: PathBuf) -> Task<Message> {
+    Task::perform(
+        async move {
+            let probe_path = path.clone();
+            let estimate =
+                tokio::task::spawn_blocking(move || decoder::estimate_file_memory(&probe_path))
+                    .await
+                    .unwrap();
+            (path, estimate)
+        },
+        |(path, estimate)| Message::FileEstimated(path, estimate),
+    )
+}
+
+/// Download `url` in the background, reporting progress as it goes, then
+/// open it like a local file. Any download still running is cancelled.
+fn start_download(app: &mut App, url: String) -> Task<Message> {
+    if let Some(cancel) = app.download_cancel.take() {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    let cancel = Arc::new(AtomicBool::new(false));
+    app.download_cancel = Some(cancel.clone());
+    app.download_id += 1;
+    let download_id = app.download_id;
+    app.download_progress = Some(0.0);
+    Task::stream(iced::stream::channel(
+        16,
+        async move |mut output: mpsc::Sender<Message>| {
+            let (progress_tx, mut progress_rx) = mpsc::unbounded();
+            let fetch = tokio::task::spawn_blocking(move || {
+                download::download(&url, &cancel, |p| {
+                    let _ = progress_tx.unbounded_send(p);
+                })
+            });
+            while let Some(p) = progress_rx.next().await {
+                let _ = output.send(Message::DownloadProgress(download_id, p)).await;
+            }
+            let result = fetch.await.unwrap();
+            let _ = output.send(Message::Downloaded(download_id, result)).await;
+        },
+    ))
+}
+
+/// Numbers each load and holds the flag that cancels the decode running for
+/// it. Only results tagged with the latest number are applied.
+#[derive(Default)]
+struct LoadTracker {
+    id: u64,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl LoadTracker {
+    /// Supersede the current load, cancelling its decode if one is still
+    /// running, and return the new load's id and cancel flag.
+    fn start(&mut self) -> (u64, Arc<AtomicBool>) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.cancel = Some(cancel.clone());
+        self.id += 1;
+        (self.id, cancel)
+    }
+
+    /// Whether results tagged `id` belong to the latest load.
+    fn is_current(&self, id: u64) -> bool {
+        id == self.id
+    }
+
+    /// The latest load's decode is over, so there is nothing left to cancel.
+    fn finish(&mut self) {
+        self.cancel = None;
+    }
+}
+
+/// Decode `path` in the background, reporting progress as it goes. Any load
+/// still running is cancelled, and its results will be ignored.
+fn start_decode(app: &mut App, path: PathBuf) -> Task<Message> {
+    let (load_id, cancel) = app.load.start();
+    app.decode_progress = Some(0.0);
+    let policy = app.decode_policy;
+    Task::stream(iced::stream::channel(
+        16,
+        async move |mut output: mpsc::Sender<Message>| {
+            let (progress_tx, mut progress_rx) = mpsc::unbounded();
+            let decode = tokio::task::spawn_blocking(move || {
+                decoder::decode_file(&path, policy, &cancel, |p| {
+                    let _ = progress_tx.unbounded_send(p);
+                })
+                .map(|(data, info, report)| (data, info, report, path))
+            });
+            // Ends once the decode finishes and drops its progress sender
+            while let Some(p) = progress_rx.next().await {
+                let _ = output.send(Message::DecodeProgress(load_id, p)).await;
+            }
+            let result = decode.await.unwrap();
+            let _ = output.send(Message::FileLoaded(load_id, result)).await;
+        },
+    ))
+}
+
+/// Index of the queue entry after the current one, or the first entry when
+/// the loaded file isn't queued.
+fn next_in_queue(app: &App) -> Option<usize> {
+    let next = app.playlist_index.map_or(0, |i| i + 1);
+    (next < app.playlist.len()).then_some(next)
+}
+
+/// Open queue entry `index`, and start playing it once loaded if `play`.
+/// A preloaded entry is loaded straight away.
+fn play_queue_entry(app: &mut App, index: usize, play: bool) -> Task<Message> {
+    let Some(path) = app.playlist.get(index).cloned() else {
+        return Task::none();
+    };
+    app.playlist_index = Some(index);
+    app.play_on_load = play;
+    app.pending_session = None;
+    let mut tasks = Vec::new();
+    if !app.playlist_keep_settings {
+        tasks.push(update(
+            app,
+            Message::Control(ControlMessage::ResetTempoPitch),
+        ));
+    }
+    if app.preload_path.as_ref() == Some(&path) {
+        if let Some(decoded) = app.preloaded.take() {
+            app.preload_path = None;
+            // Stands in for a decode, superseding any that is running
+            app.decode_progress = None;
+            let (load_id, _) = app.load.start();
+            tasks.push(update(app, Message::FileLoaded(load_id, Ok(decoded))));
+            return Task::batch(tasks);
+        }
+    }
+    tasks.push(open_file(path));
+    Task::batch(tasks)
+}
+
+/// Swap two queue entries, keeping track of the current one.
+fn swap_queue_entries(app: &mut App, a: usize, b: usize) {
+    app.playlist.swap(a, b);
+    app.playlist_index = app.playlist_index.map(|current| {
+        if current == a {
+            b
+        } else if current == b {
+            a
+        } else {
+            current
+        }
+    });
+}
+
+/// Decode the queue entry after the current one in the background, unless
+/// that is already under way. Entries over the memory budget are left to
+/// load (and be confirmed) when they come up.
+fn preload_next(app: &mut App) -> Task<Message> {
+    let Some(path) = next_in_queue(app).map(|i| app.playlist[i].clone()) else {
+        app.preload_path = None;
+        app.preloaded = None;
+        return Task::none();
+    };
+    if app.preload_path.as_ref() == Some(&path) {
+        return Task::none();
+    }
+    app.preload_path = Some(path.clone());
+    app.preloaded = None;
+    let policy = app.decode_policy;
+    let budget = app.memory_budget_bytes;
+    Task::perform(
+        async move {
+            let decode_path = path.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                if decoder::estimate_file_memory(&decode_path)?.is_some_and(|b| b > budget) {
+                    return Err("Over the memory budget".to_string());
+                }
+                let cancel = AtomicBool::new(false);
+                decoder::decode_file(&decode_path, policy, &cancel, |_| {})
+                    .map(|(data, info, report)| (data, info, report, decode_path))
+            })
+            .await
+            .unwrap();
+            (path, result)
+        },
+        |(path, result)| Message::Preloaded(path, result),
+    )
+}
+
+/// Memory budget for decoded audio, in megabytes, unless overridden by
+/// `TRANSCRIBE_MEMORY_BUDGET_MB`.
+const DEFAULT_MEMORY_BUDGET_MB: u64 = 2048;
+
+fn memory_budget_bytes() -> u64 {
+    let mb = std::env::var("TRANSCRIBE_MEMORY_BUDGET_MB")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MEMORY_BUDGET_MB);
+    mb * 1024 * 1024
+}
+
+fn spawn_engine_task(device_name: Option<String>) -> Task<Message> {
+    Task::perform(
+        async {
+            tokio::task::spawn_blocking(move || engine::spawn_engine(device_name))
+                .await
+                .unwrap()
+        },
+        Message::EngineReady,
+    )
 }
 
 fn title(app: &App) -> String {
-    match &app.filename {
+    // "Artist - Title" from the tags when there are any, else the filename
+    let tagged = app
+        .audio_info
+        .as_ref()
+        .and_then(|info| match (&info.artist, &info.title) {
+            (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+            (None, Some(title)) => Some(title.clone()),
+            _ => None,
+        });
+    match tagged.as_ref().or(app.filename.as_ref()) {
         Some(name) => format!("Transcribe - {name}"),
         None => "Transcribe".to_string(),
     }
@@ -84,15 +730,98 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
     match message {
         Message::EngineReady(result) => match result {
             Ok((tx, rx)) => {
+                tracing::info!("audio engine ready");
+                // Kept by the engine across loads, so sent even without a file
+                let _ = tx.send(AudioCommand::SetChannelMode(app.channel_mode));
+                let _ = tx.send(AudioCommand::SetVocalReduction(app.vocal_reduction));
+                let _ = tx.send(AudioCommand::SetStereoWidth(app.stereo_width));
+                send_eq(app, &tx);
+                send_filter(app, &tx);
+                send_metronome(app, &tx);
+                let _ = tx.send(AudioCommand::SetCountIn(app.count_in_bars));
+                let _ = tx.send(AudioCommand::SetLoopSnap(app.loop_snap));
+                let _ = tx.send(AudioCommand::SetLoopCrossfade(app.loop_crossfade));
+                let _ = tx.send(AudioCommand::SetLoopRepeats(
+                    app.loop_repeats,
+                    app.after_loops,
+                ));
+                let ramp = parse_tempo_ramp(app)
+                    .ok()
+                    .filter(|_| app.tempo_ramp_enabled)
+                    .map(|(_, ramp)| ramp);
+                let _ = tx.send(AudioCommand::SetTempoRamp(ramp));
+                // Restore the loaded file on a freshly (re)started engine
+                if let Some(data) = &app.audio_data {
+                    let _ = tx.send(AudioCommand::LoadAudio(data.clone()));
+                    let _ = tx.send(AudioCommand::SetBeats(app.beats.clone()));
+                    let _ = tx.send(AudioCommand::SetTempo(app.tempo));
+                    let _ = tx.send(AudioCommand::SetPitch(total_pitch(app)));
+                    let _ = tx.send(AudioCommand::SetGain(output_gain(app)));
+                    let _ = tx.send(AudioCommand::SetMuted(app.muted));
+                    let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                    let _ = tx.send(AudioCommand::SetLoopMode(app.loop_mode));
+                    let _ = tx.send(AudioCommand::Seek(app.position));
+                    if app.resume_on_engine_ready {
+                        let _ = tx.send(AudioCommand::Play);
+                    } else if app.status == PlaybackStatus::Playing {
+                        app.status = PlaybackStatus::Paused;
+                    }
+                }
+                app.resume_on_engine_ready = false;
                 app.cmd_tx = Some(tx);
                 app.event_rx = Some(rx);
                 Task::none()
             }
             Err(e) => {
+                tracing::error!(error = %e, "audio engine failed to start");
                 app.error = Some(format!("Audio engine error: {e}"));
                 Task::none()
             }
         },
+        Message::DevicesListed(devices) => {
+            app.output_devices = devices;
+            Task::none()
+        }
+        Message::SystemThemeChanged(mode) => {
+            app.system_theme = mode;
+            apply_theme(app);
+            Task::none()
+        }
+        Message::WindowResized(size) => {
+            // Minimizing reports an empty window on some platforms
+            if size.width > 0.0 && size.height > 0.0 {
+                app.window.width = size.width;
+                app.window.height = size.height;
+            }
+            Task::none()
+        }
+        Message::WindowMoved(position) => {
+            app.window.position = Some((position.x, position.y));
+            Task::none()
+        }
+        Message::CloseRequested => {
+            app.media_keys = None;
+            remember_position(app);
+            app.settings.window = Some(app.window);
+            if let Err(e) = app.settings.save() {
+                tracing::warn!(error = %e, "failed to save window geometry");
+            }
+            iced::exit()
+        }
+        Message::MidiPortsListed(ports) => {
+            app.midi_ports = ports;
+            // Reconnect to the remembered port once it shows up
+            if app.midi.is_none() {
+                if let Some(port) = app.midi_config.port.clone() {
+                    if app.midi_ports.contains(&port) {
+                        if let Err(e) = connect_midi(app, &port) {
+                            tracing::warn!(error = %e, "failed to reopen MIDI input");
+                        }
+                    }
+                }
+            }
+            Task::none()
+        }
         Message::Control(ctrl) => match ctrl {
             ControlMessage::OpenFile => Task::perform(
                 async {
@@ -104,6 +833,113 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
                 },
                 Message::FileDialogResult,
             ),
+            ControlMessage::AddToQueue => Task::perform(
+                async {
+                    let handles = rfd::AsyncFileDialog::new()
+                        .add_filter("Audio", &["mp3", "wav", "flac", "ogg", "aac"])
+                        .pick_files()
+                        .await;
+                    handles.map(|hs| hs.iter().map(|h| h.path().to_path_buf()).collect())
+                },
+                Message::QueuePicked,
+            ),
+            ControlMessage::UrlInput(input) => {
+                app.url_input = input;
+                Task::none()
+            }
+            ControlMessage::OpenUrl => {
+                let url = app.url_input.trim().to_string();
+                if !download::is_url(&url) {
+                    show_transient_error(app, format!("Not an http(s) URL: {url}"));
+                    return Task::none();
+                }
+                app.error = None;
+                start_download(app, url)
+            }
+            ControlMessage::RecentFileSelected(RecentFile(path)) => {
+                update(app, Message::FileDialogResult(Some(path)))
+            }
+            ControlMessage::SaveSession => {
+                if app.file_path.is_none() {
+                    app.error = Some("Open an audio file before saving a session".to_string());
+                    return Task::none();
+                }
+                Task::perform(
+                    async {
+                        let handle = rfd::AsyncFileDialog::new()
+                            .add_filter("Session", &["json"])
+                            .set_file_name("session.json")
+                            .save_file()
+                            .await;
+                        handle.map(|h| h.path().to_path_buf())
+                    },
+                    Message::SessionSavePicked,
+                )
+            }
+            ControlMessage::ExportProcessed => {
+                if app.audio_data.is_none() {
+                    app.error = Some("Open an audio file before exporting".to_string());
+                    return Task::none();
+                }
+                Task::perform(
+                    async {
+                        let handle = rfd::AsyncFileDialog::new()
+                            .add_filter("WAV", &["wav"])
+                            .set_file_name("export.wav")
+                            .save_file()
+                            .await;
+                        handle.map(|h| h.path().to_path_buf())
+                    },
+                    Message::ExportPicked,
+                )
+            }
+            ControlMessage::ExportAnnotations => Task::perform(
+                async {
+                    let handle = rfd::AsyncFileDialog::new()
+                        .add_filter("Markers", &["json"])
+                        .set_file_name("markers.json")
+                        .save_file()
+                        .await;
+                    handle.map(|h| h.path().to_path_buf())
+                },
+                Message::AnnotationsSavePicked,
+            ),
+            ControlMessage::ImportAnnotations => {
+                if app.audio_data.is_none() {
+                    app.error = Some("Open an audio file before importing markers".to_string());
+                    return Task::none();
+                }
+                Task::perform(
+                    async {
+                        let handle = rfd::AsyncFileDialog::new()
+                            .add_filter("Markers", &["json"])
+                            .pick_file()
+                            .await?;
+                        let path = handle.path().to_path_buf();
+                        Some(
+                            tokio::task::spawn_blocking(move || Annotations::load(&path))
+                                .await
+                                .unwrap(),
+                        )
+                    },
+                    Message::AnnotationsLoaded,
+                )
+            }
+            ControlMessage::LoadSession => Task::perform(
+                async {
+                    let handle = rfd::AsyncFileDialog::new()
+                        .add_filter("Session", &["json"])
+                        .pick_file()
+                        .await?;
+                    let path = handle.path().to_path_buf();
+                    Some(
+                        tokio::task::spawn_blocking(move || Session::load(&path))
+                            .await
+                            .unwrap(),
+                    )
+                },
+                Message::SessionLoaded,
+            ),
             ControlMessage::PlayPause => {
                 if let Some(tx) = &app.cmd_tx {
                     match app.status {
@@ -114,6 +950,7 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
                         _ => {
                             let _ = tx.send(AudioCommand::Play);
                             app.status = PlaybackStatus::Playing;
+                            app.position_updated = Instant::now();
                         }
                     }
                 }
@@ -121,10 +958,71 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
             }
             ControlMessage::Stop => {
                 if let Some(tx) = &app.cmd_tx {
+                    let target = match app.stop_behavior {
+                        StopBehavior::ResetToZero => 0.0,
+                        StopBehavior::ResetToLoopStart => {
+                            app.loop_region.map_or(0.0, |(start, _)| start)
+                        }
+                        StopBehavior::StayInPlace => app.position,
+                    };
+                    // Stop rewinds the engine to zero; seek on to the target
                     let _ = tx.send(AudioCommand::Stop);
+                    if target > 0.0 {
+                        let _ = tx.send(AudioCommand::Seek(target));
+                    }
                     app.status = PlaybackStatus::Stopped;
-                    app.position = 0.0;
-                    app.waveform_view.playback_position = 0.0;
+                    app.position = target;
+                    if app.duration > 0.0 {
+                        app.waveform_view.playback_position = target / app.duration;
+                    }
+                }
+                Task::none()
+            }
+            ControlMessage::DecodePolicyChanged(policy) => {
+                app.decode_policy = policy;
+                Task::none()
+            }
+            ControlMessage::ExportChannelsChanged(layout) => {
+                app.export_channels = layout;
+                Task::none()
+            }
+            ControlMessage::StopBehaviorChanged(behavior) => {
+                app.stop_behavior = behavior;
+                Task::none()
+            }
+            ControlMessage::ShowStopButtonToggled(show) => {
+                app.show_stop_button = show;
+                Task::none()
+            }
+            ControlMessage::ReplayGainToggled(enabled) => {
+                app.replay_gain = enabled;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetGain(output_gain(app)));
+                }
+                Task::none()
+            }
+            ControlMessage::MediaKeysToggled(enabled) => {
+                app.media_keys = None;
+                if enabled {
+                    match MediaKeys::register() {
+                        Ok(keys) => app.media_keys = Some(keys),
+                        Err(e) => show_transient_error(app, e),
+                    }
+                }
+                Task::none()
+            }
+            ControlMessage::ThemeChanged(choice) => {
+                app.settings.theme = choice;
+                apply_theme(app);
+                if let Err(e) = app.settings.save() {
+                    tracing::warn!(error = %e, "failed to save settings");
+                }
+                Task::none()
+            }
+            ControlMessage::ResumeToggled(enabled) => {
+                app.resume.enabled = enabled;
+                if let Err(e) = app.resume.save() {
+                    tracing::warn!(error = %e, "failed to save resume preference");
                 }
                 Task::none()
             }
@@ -135,146 +1033,1264 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
                 }
                 Task::none()
             }
-            ControlMessage::ClearLoop => {
-                app.loop_region = None;
-                app.waveform_view.loop_region = None;
+            ControlMessage::PitchChanged(p) => {
+                app.pitch = p;
                 if let Some(tx) = &app.cmd_tx {
-                    let _ = tx.send(AudioCommand::SetLoopRegion(None));
+                    let _ = tx.send(AudioCommand::SetPitch(total_pitch(app)));
                 }
                 Task::none()
             }
-        },
-        Message::FileDialogResult(path) => {
-            if let Some(path) = path {
-                let filename = path
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-                Task::perform(
-                    async move {
-                        tokio::task::spawn_blocking(move || {
-                            decoder::decode_file(&path).map(|data| (data, filename))
-                        })
-                        .await
-                        .unwrap()
-                    },
-                    Message::FileLoaded,
-                )
-            } else {
+            ControlMessage::PitchCentsChanged(cents) => {
+                app.pitch_cents = cents;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetPitch(total_pitch(app)));
+                }
                 Task::none()
             }
-        }
-        Message::FileLoaded(result) => match result {
-            Ok((data, filename)) => {
-                let peaks = WaveformPeaks::compute(&data);
-                let total_frames = data.num_frames();
-                let duration = data.duration;
-
-                app.waveform_view.set_peaks(peaks, total_frames, duration);
-                app.duration = duration;
-                app.filename = Some(filename);
-                app.position = 0.0;
-                app.loop_region = None;
-                app.waveform_view.loop_region = None;
-                app.waveform_view.playback_position = 0.0;
-                app.status = PlaybackStatus::Stopped;
-                app.error = None;
-
-                let arc_data = Arc::new(data);
-                app.audio_data = Some(arc_data.clone());
-
+            ControlMessage::VolumeChanged(v) => {
+                app.volume = v;
                 if let Some(tx) = &app.cmd_tx {
-                    let _ = tx.send(AudioCommand::LoadAudio(arc_data));
+                    let _ = tx.send(AudioCommand::SetGain(output_gain(app)));
                 }
-
                 Task::none()
             }
-            Err(e) => {
-                app.error = Some(e);
+            ControlMessage::ToggleMute => {
+                app.muted = !app.muted;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetMuted(app.muted));
+                }
                 Task::none()
             }
-        },
-        Message::Waveform(wm) => match wm {
-            WaveformMessage::Seek(time) => {
+            ControlMessage::ChannelModeChanged(mode) => {
+                app.channel_mode = mode;
                 if let Some(tx) = &app.cmd_tx {
-                    let _ = tx.send(AudioCommand::Seek(time));
-                    app.position = time;
-                    if app.duration > 0.0 {
-                        app.waveform_view.playback_position = time / app.duration;
-                    }
+                    let _ = tx.send(AudioCommand::SetChannelMode(mode));
                 }
                 Task::none()
             }
-            WaveformMessage::LoopSelected(start, end) => {
-                app.loop_region = Some((start, end));
-                if app.duration > 0.0 {
-                    app.waveform_view.loop_region =
-                        Some((start / app.duration, end / app.duration));
+            ControlMessage::VocalReductionChanged(amount) => {
+                app.vocal_reduction = amount;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetVocalReduction(amount));
                 }
+                Task::none()
+            }
+            ControlMessage::StereoWidthChanged(width) => {
+                app.stereo_width = width;
                 if let Some(tx) = &app.cmd_tx {
-                    let _ = tx.send(AudioCommand::SetLoopRegion(Some((start, end))));
+                    let _ = tx.send(AudioCommand::SetStereoWidth(width));
                 }
                 Task::none()
             }
-            WaveformMessage::DragStarted(frac) => {
-                app.drag_start = Some(frac);
+            ControlMessage::EqChanged(band, gain) => {
+                match band {
+                    EqBand::Low => app.eq_low = gain,
+                    EqBand::Mid => app.eq_mid = gain,
+                    EqBand::High => app.eq_high = gain,
+                }
+                if let Some(tx) = &app.cmd_tx {
+                    send_eq(app, tx);
+                }
                 Task::none()
             }
-            WaveformMessage::DragMoved(frac) => {
-                if let Some(start) = app.drag_start {
-                    let (lo, hi) = if start < frac {
-                        (start, frac)
-                    } else {
-                        (frac, start)
-                    };
-                    if (hi - lo).abs() > 0.005 {
-                        app.waveform_view.loop_region = Some((lo, hi));
-                    }
+            ControlMessage::HighPassChanged(hz) => {
+                app.hp_hz = hz;
+                if let Some(tx) = &app.cmd_tx {
+                    send_filter(app, tx);
                 }
                 Task::none()
             }
-        },
-        Message::Tick => {
-            if let Some(rx) = &app.event_rx {
-                while let Ok(event) = rx.try_recv() {
-                    match event {
-                        AudioEvent::PositionChanged(pos) => {
-                            app.position = pos;
-                            if app.duration > 0.0 {
-                                app.waveform_view.playback_position = pos / app.duration;
-                            }
-                        }
-                        AudioEvent::PlaybackFinished => {
-                            app.status = PlaybackStatus::Stopped;
-                            app.position = 0.0;
-                            app.waveform_view.playback_position = 0.0;
-                        }
+            ControlMessage::LowPassChanged(hz) => {
+                app.lp_hz = hz;
+                if let Some(tx) = &app.cmd_tx {
+                    send_filter(app, tx);
+                }
+                Task::none()
+            }
+            ControlMessage::PresetSelected(preset) => {
+                apply_preset(app, &preset);
+                app.preset_name_input = preset.name;
+                Task::none()
+            }
+            ControlMessage::PresetNameInput(input) => {
+                app.preset_name_input = input;
+                Task::none()
+            }
+            ControlMessage::SavePreset => {
+                let name = app.preset_name_input.trim();
+                if name.is_empty() {
+                    show_transient_error(app, "Enter a name for the preset".to_string());
+                    return Task::none();
+                }
+                let preset = current_preset(app, name.to_string());
+                if let Err(e) = presets::add(&mut app.presets, preset) {
+                    show_transient_error(app, e);
+                }
+                Task::none()
+            }
+            ControlMessage::MetronomeToggled(enabled) => {
+                app.metronome_enabled = enabled;
+                if let Some(tx) = &app.cmd_tx {
+                    send_metronome(app, tx);
+                }
+                Task::none()
+            }
+            ControlMessage::BeatFlashToggled(enabled) => {
+                app.beat_flash = enabled;
+                Task::none()
+            }
+            ControlMessage::MetronomeGainChanged(gain) => {
+                app.metronome_gain = gain;
+                if let Some(tx) = &app.cmd_tx {
+                    send_metronome(app, tx);
+                }
+                Task::none()
+            }
+            ControlMessage::BeatsPerBarChanged(beats) => {
+                app.beats_per_bar = beats;
+                if let Some(tx) = &app.cmd_tx {
+                    send_metronome(app, tx);
+                }
+                Task::none()
+            }
+            ControlMessage::CountInChanged(bars) => {
+                app.count_in_bars = bars;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetCountIn(bars));
+                }
+                Task::none()
+            }
+            ControlMessage::OutputDeviceSelected(name) => {
+                if app.output_device.as_ref() == Some(&name) {
+                    return Task::none();
+                }
+                if let Some(tx) = app.cmd_tx.take() {
+                    let _ = tx.send(AudioCommand::Shutdown);
+                }
+                app.event_rx = None;
+                tracing::info!(device = %name, "switching output device");
+                app.output_device = Some(name.clone());
+                spawn_engine_task(Some(name))
+            }
+            ControlMessage::MidiPortSelected(port) => {
+                match connect_midi(app, &port) {
+                    Ok(()) => {
+                        app.midi_config.port = Some(port);
+                        if let Err(e) = app.midi_config.save() {
+                            tracing::warn!(error = %e, "failed to save MIDI settings");
+                        }
+                    }
+                    Err(e) => app.error = Some(e),
+                }
+                Task::none()
+            }
+            ControlMessage::MidiPortsRefresh => list_midi_ports_task(),
+            ControlMessage::MidiDisconnect => {
+                app.midi = None;
+                app.midi_config.port = None;
+                if let Err(e) = app.midi_config.save() {
+                    tracing::warn!(error = %e, "failed to save MIDI settings");
+                }
+                Task::none()
+            }
+            ControlMessage::LoopLengthLockToggled(locked) => {
+                app.loop_length_locked = locked;
+                Task::none()
+            }
+            ControlMessage::LoopLengthInput(input) => {
+                if let Ok(len) = input.trim().parse::<f64>() {
+                    if len > 0.0 {
+                        app.locked_loop_length = len;
+                    }
+                }
+                app.loop_length_input = input;
+                Task::none()
+            }
+            ControlMessage::LoopModeChanged(mode) => {
+                app.loop_mode = mode;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopMode(mode));
+                }
+                Task::none()
+            }
+            ControlMessage::BeatSnapToggled(snap) => {
+                app.beat_snap = snap;
+                Task::none()
+            }
+            ControlMessage::LoopSnapToggled(snap) => {
+                app.loop_snap = snap;
+                if let Some(tx) = &app.cmd_tx {
+                    // Re-send the loop so the new setting applies to it
+                    let _ = tx.send(AudioCommand::SetLoopSnap(snap));
+                    let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                }
+                Task::none()
+            }
+            ControlMessage::LoopCrossfadeChanged(secs) => {
+                app.loop_crossfade = secs;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopCrossfade(secs));
+                }
+                Task::none()
+            }
+            ControlMessage::LoopNudgeChanged(secs) => {
+                app.loop_nudge = secs;
+                Task::none()
+            }
+            ControlMessage::ResetTempoPitch => {
+                // Loop and markers stay; only the speed and pitch go back
+                let _ = update(app, Message::Control(ControlMessage::TempoChanged(1.0)));
+                app.pitch_cents = 0.0;
+                update(app, Message::Control(ControlMessage::PitchChanged(0.0)))
+            }
+            ControlMessage::ShuttleMoved(position) => {
+                app.shuttle = position;
+                if let Some(tx) = &app.cmd_tx {
+                    let speed = shuttle_speed(position);
+                    let _ = tx.send(AudioCommand::SetShuttle(speed));
+                    // Pushing the shuttle plays even from a pause
+                    if speed != 0.0
+                        && app.status != PlaybackStatus::Playing
+                        && app.audio_data.is_some()
+                    {
+                        let _ = tx.send(AudioCommand::Play);
+                        app.status = PlaybackStatus::Playing;
+                        app.position_updated = Instant::now();
+                        app.shuttle_started = true;
+                    }
+                }
+                Task::none()
+            }
+            ControlMessage::ShuttleReleased => {
+                app.shuttle = 0.0;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetShuttle(0.0));
+                    if std::mem::take(&mut app.shuttle_started)
+                        && app.status == PlaybackStatus::Playing
+                    {
+                        let _ = tx.send(AudioCommand::Pause);
+                        app.status = PlaybackStatus::Paused;
+                    }
+                }
+                Task::none()
+            }
+            ControlMessage::GoToInput(input) => {
+                app.go_to_input = input;
+                Task::none()
+            }
+            ControlMessage::GoToSubmitted => {
+                if app.audio_data.is_none() {
+                    return Task::none();
+                }
+                match parse_time(&app.go_to_input) {
+                    Ok(time) if time <= app.duration => {
+                        seek_to(app, time);
+                        app.go_to_input.clear();
+                    }
+                    Ok(_) => {
+                        let message = format!(
+                            "{} is past the end of the file ({})",
+                            app.go_to_input.trim(),
+                            format_time(app.duration)
+                        );
+                        show_transient_error(app, message);
+                    }
+                    Err(e) => show_transient_error(app, e),
+                }
+                Task::none()
+            }
+            ControlMessage::FineStepChanged(secs) => {
+                app.fine_step = secs;
+                Task::none()
+            }
+            ControlMessage::SeekStepChanged(secs) => {
+                app.settings.seek_step = secs;
+                if let Err(e) = app.settings.save() {
+                    tracing::warn!(error = %e, "failed to save settings");
+                }
+                Task::none()
+            }
+            ControlMessage::LoopRepeatsInput(input) => {
+                // Anything that isn't a positive count loops forever
+                app.loop_repeats = input.trim().parse::<u32>().ok().filter(|n| *n > 0);
+                app.loop_repeats_input = input;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopRepeats(
+                        app.loop_repeats,
+                        app.after_loops,
+                    ));
+                }
+                Task::none()
+            }
+            ControlMessage::DownbeatOffsetChanged(offset) => {
+                app.downbeat_offset = offset;
+                sync_beats(app);
+                Task::none()
+            }
+            ControlMessage::OriginalBpmInput(input) => {
+                app.original_bpm = input.trim().parse::<f32>().ok().filter(|b| *b > 0.0);
+                app.original_bpm_input = input;
+                remember_bpm(app);
+                Task::none()
+            }
+            ControlMessage::TargetBpmInput(input) => {
+                app.target_bpm_input = input;
+                Task::none()
+            }
+            ControlMessage::TargetBpmSubmitted => {
+                let Some(original) = app.original_bpm else {
+                    app.error = Some("Enter the original BPM first".to_string());
+                    return Task::none();
+                };
+                let Some(target) = app
+                    .target_bpm_input
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|b| *b > 0.0)
+                else {
+                    app.error = Some("Target BPM must be a positive number".to_string());
+                    return Task::none();
+                };
+                update(
+                    app,
+                    Message::Control(ControlMessage::TempoChanged(
+                        (target / original).clamp(MIN_TEMPO, MAX_TEMPO),
+                    )),
+                )
+            }
+            ControlMessage::TempoRampToggled(enabled) => {
+                if !enabled {
+                    app.tempo_ramp_enabled = false;
+                    if let Some(tx) = &app.cmd_tx {
+                        let _ = tx.send(AudioCommand::SetTempoRamp(None));
+                    }
+                    return Task::none();
+                }
+                match parse_tempo_ramp(app) {
+                    Ok((start, ramp)) => {
+                        app.tempo_ramp_enabled = true;
+                        app.tempo = start;
+                        if let Some(tx) = &app.cmd_tx {
+                            let _ = tx.send(AudioCommand::SetTempo(start));
+                            let _ = tx.send(AudioCommand::SetTempoRamp(Some(ramp)));
+                        }
+                    }
+                    Err(e) => app.error = Some(e),
+                }
+                Task::none()
+            }
+            ControlMessage::TempoRampStartInput(input) => {
+                app.tempo_ramp_start_input = input;
+                Task::none()
+            }
+            ControlMessage::TempoRampTargetInput(input) => {
+                app.tempo_ramp_target_input = input;
+                update_tempo_ramp(app);
+                Task::none()
+            }
+            ControlMessage::TempoRampStepInput(input) => {
+                app.tempo_ramp_step_input = input;
+                update_tempo_ramp(app);
+                Task::none()
+            }
+            ControlMessage::AfterLoopsChanged(after) => {
+                app.after_loops = after;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopRepeats(app.loop_repeats, after));
+                }
+                Task::none()
+            }
+            ControlMessage::DbScaleToggled(db) => {
+                app.waveform_view.set_db_scale(db);
+                Task::none()
+            }
+            ControlMessage::FollowPlayheadToggled(follow) => {
+                app.follow_playhead = follow;
+                app.follow_suspended = false;
+                Task::none()
+            }
+            ControlMessage::TunerToggled(enabled) => {
+                app.tuner_enabled = enabled;
+                app.tuner_reading = None;
+                app.tuner_position = None;
+                Task::none()
+            }
+            ControlMessage::WaveformStyleChanged(style) => {
+                app.settings.waveform_style = style;
+                app.waveform_view.set_style(style);
+                if let Err(e) = app.settings.save() {
+                    tracing::warn!(error = %e, "failed to save settings");
+                }
+                Task::none()
+            }
+            ControlMessage::ShowRmsToggled(show) => {
+                app.waveform_view.set_show_rms(show);
+                Task::none()
+            }
+            ControlMessage::DragThresholdChanged(px) => {
+                app.waveform_view.drag_threshold_px = px;
+                Task::none()
+            }
+            ControlMessage::ScrollSeek(steps) => {
+                if app.audio_data.is_some() {
+                    seek_to(app, app.position + steps as f64 * app.settings.seek_step);
+                }
+                Task::none()
+            }
+            ControlMessage::LoopSection => {
+                let (start, end) = section_around(&app.markers, app.position, app.duration);
+                // A marker at the very end leaves nothing after it to loop
+                if app.audio_data.is_some() && end > start {
+                    app.loop_region = Some((start, end));
+                    sync_loop_overlay(app);
+                    if let Some(tx) = &app.cmd_tx {
+                        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                    }
+                }
+                Task::none()
+            }
+            ControlMessage::MarkerLabelInput(input) => {
+                app.marker_label_input = input;
+                Task::none()
+            }
+            ControlMessage::AddMarker => update(
+                app,
+                Message::Waveform(WaveformMessage::AddMarker(app.position)),
+            ),
+            ControlMessage::ClearLoop => {
+                app.loop_region = None;
+                app.waveform_view.loop_region = None;
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopRegion(None));
+                }
+                Task::none()
+            }
+            ControlMessage::ZoomToLoop => {
+                if let Some((start, end)) = app.loop_region.filter(|_| app.duration > 0.0) {
+                    app.waveform_view
+                        .zoom_to(start / app.duration, end / app.duration);
+                    // Stay on the loop even if the playhead is elsewhere
+                    app.follow_suspended = true;
+                }
+                Task::none()
+            }
+            ControlMessage::ToggleTimeDisplay => {
+                app.show_remaining = !app.show_remaining;
+                Task::none()
+            }
+            ControlMessage::ZoomToFit => {
+                app.waveform_view.zoom_to_fit();
+                Task::none()
+            }
+        },
+        Message::SessionSavePicked(path) => {
+            if let (Some(path), Some(audio_path)) = (path, &app.file_path) {
+                let session = Session {
+                    audio_path: audio_path.clone(),
+                    tempo: app.tempo,
+                    pitch: total_pitch(app),
+                    volume: app.volume,
+                    loop_region: app.loop_region,
+                    markers: app.markers.clone(),
+                    original_bpm: app.original_bpm,
+                    downbeat_offset: app.downbeat_offset,
+                };
+                if let Err(e) = session.save(&path) {
+                    tracing::error!(error = %e, "failed to save session");
+                    app.error = Some(e);
+                }
+            }
+            Task::none()
+        }
+        Message::AnnotationsSavePicked(path) => {
+            if let Some(path) = path {
+                let annotations = Annotations {
+                    markers: app.markers.clone(),
+                    loops: app.loops.clone(),
+                };
+                if let Err(e) = annotations.save(&path) {
+                    tracing::error!(error = %e, "failed to export markers");
+                    app.error = Some(e);
+                }
+            }
+            Task::none()
+        }
+        Message::AnnotationsLoaded(result) => {
+            match result {
+                Some(Ok(annotations)) => apply_annotations(app, annotations),
+                Some(Err(e)) => {
+                    tracing::error!(error = %e, "failed to import markers");
+                    app.error = Some(e);
+                }
+                None => {}
+            }
+            Task::none()
+        }
+        Message::ExportPicked(path) => match (path, &app.audio_data) {
+            (Some(path), Some(data)) => {
+                let data = data.clone();
+                let (tempo, pitch) = (app.tempo, total_pitch(app));
+                let channels = app.export_channels.count(data.channels);
+                tracing::info!(
+                    path = %path.display(),
+                    tempo,
+                    pitch,
+                    channels,
+                    "exporting processed audio"
+                );
+                Task::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            let samples = export::render_processed(&data, tempo, pitch);
+                            let samples = export::remix(&samples, data.channels, channels)?;
+                            export::write_wav(&path, &samples, data.sample_rate, channels)
+                                .map(|()| path)
+                        })
+                        .await
+                        .unwrap()
+                    },
+                    Message::ExportFinished,
+                )
+            }
+            _ => Task::none(),
+        },
+        Message::ExportFinished(result) => {
+            match result {
+                Ok(path) => tracing::info!(path = %path.display(), "export finished"),
+                Err(e) => {
+                    tracing::error!(error = %e, "export failed");
+                    app.error = Some(e);
+                }
+            }
+            Task::none()
+        }
+        Message::SessionLoaded(result) => match result {
+            Some(Ok(session)) => {
+                if !session.audio_path.exists() {
+                    app.error = Some(format!(
+                        "The session's audio file was not found at {}. It may have been \
+                         moved or renamed.",
+                        session.audio_path.display()
+                    ));
+                    return Task::none();
+                }
+                let path = session.audio_path.clone();
+                app.pending_session = Some(session);
+                open_file(path)
+            }
+            Some(Err(e)) => {
+                tracing::error!(error = %e, "failed to load session");
+                app.error = Some(e);
+                Task::none()
+            }
+            None => Task::none(),
+        },
+        Message::FileDialogResult(path) => {
+            if let Some(path) = path {
+                // A file opened by hand replaces any session still loading
+                app.pending_session = None;
+                app.play_on_load = false;
+                open_file(path)
+            } else {
+                Task::none()
+            }
+        }
+        Message::QueuePicked(paths) => {
+            let Some(paths) = paths else {
+                return Task::none();
+            };
+            let first_added = app.playlist.len();
+            app.playlist.extend(paths);
+            // With nothing open yet, start on the first file added
+            if app.file_path.is_none()
+                && app.decode_progress.is_none()
+                && first_added < app.playlist.len()
+            {
+                return play_queue_entry(app, first_added, false);
+            }
+            preload_next(app)
+        }
+        Message::Playlist(message) => match message {
+            PlaylistMessage::Play(index) => {
+                let playing = app.status == PlaybackStatus::Playing;
+                play_queue_entry(app, index, playing)
+            }
+            PlaylistMessage::Previous => {
+                let playing = app.status == PlaybackStatus::Playing;
+                match app.playlist_index.and_then(|i| i.checked_sub(1)) {
+                    Some(index) => play_queue_entry(app, index, playing),
+                    None => Task::none(),
+                }
+            }
+            PlaylistMessage::Next => {
+                let playing = app.status == PlaybackStatus::Playing;
+                match next_in_queue(app) {
+                    Some(index) => play_queue_entry(app, index, playing),
+                    None => Task::none(),
+                }
+            }
+            PlaylistMessage::MoveUp(index) => {
+                swap_queue_entries(app, index - 1, index);
+                preload_next(app)
+            }
+            PlaylistMessage::MoveDown(index) => {
+                swap_queue_entries(app, index, index + 1);
+                preload_next(app)
+            }
+            PlaylistMessage::Remove(index) => {
+                app.playlist.remove(index);
+                app.playlist_index = match app.playlist_index {
+                    Some(current) if current == index => None,
+                    Some(current) if current > index => Some(current - 1),
+                    current => current,
+                };
+                preload_next(app)
+            }
+            PlaylistMessage::KeepSettingsToggled(keep) => {
+                app.playlist_keep_settings = keep;
+                Task::none()
+            }
+        },
+        Message::FileEstimated(path, estimate) => match estimate {
+            Ok(Some(bytes)) if bytes > app.memory_budget_bytes => {
+                let description = format!(
+                    "Decoding this file needs about {} MB, which is over the memory \
+                     budget of {} MB. Load it anyway?",
+                    bytes / (1024 * 1024),
+                    app.memory_budget_bytes / (1024 * 1024)
+                );
+                Task::perform(
+                    async move {
+                        let result = rfd::AsyncMessageDialog::new()
+                            .set_level(rfd::MessageLevel::Warning)
+                            .set_title("Large file")
+                            .set_description(description)
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show()
+                            .await;
+                        (path, result == rfd::MessageDialogResult::Yes)
+                    },
+                    |(path, confirmed)| Message::LargeFileConfirmed(path, confirmed),
+                )
+            }
+            Ok(_) => start_decode(app, path),
+            Err(e) => {
+                tracing::error!(error = %e, "failed to probe file");
+                app.pending_session = None;
+                app.error = Some(e);
+                Task::none()
+            }
+        },
+        Message::LargeFileConfirmed(path, confirmed) => {
+            if confirmed {
+                start_decode(app, path)
+            } else {
+                app.pending_session = None;
+                Task::none()
+            }
+        }
+        Message::DownloadProgress(download_id, p) => {
+            if download_id == app.download_id && app.download_progress.is_some() {
+                app.download_progress = Some(p);
+            }
+            Task::none()
+        }
+        Message::Downloaded(download_id, _) if download_id != app.download_id => {
+            // Superseded by a newer download
+            Task::none()
+        }
+        Message::Downloaded(_, result) => {
+            app.download_progress = None;
+            app.download_cancel = None;
+            match result {
+                Ok(path) => {
+                    app.pending_session = None;
+                    app.play_on_load = false;
+                    open_file(path)
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to download file");
+                    app.error = Some(e);
+                    Task::none()
+                }
+            }
+        }
+        Message::DecodeProgress(load_id, p) => {
+            if app.load.is_current(load_id) && app.decode_progress.is_some() {
+                app.decode_progress = Some(p);
+            }
+            Task::none()
+        }
+        Message::FileLoaded(load_id, _) if !app.load.is_current(load_id) => {
+            // Superseded by a newer load
+            Task::none()
+        }
+        Message::FileLoaded(_, result) => match result {
+            Ok((data, mut info, report, path)) => {
+                // Keep the outgoing file's resume point before its state is reset
+                remember_position(app);
+                app.decode_progress = None;
+                app.load.finish();
+                let total_frames = data.num_frames();
+                let duration = data.duration;
+
+                app.waveform_view.set_audio(total_frames, duration);
+                app.duration = duration;
+                app.filename = Some(
+                    path.file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                );
+                if let Err(e) = recent::add(&mut app.recent_files, path.clone()) {
+                    tracing::warn!(error = %e, "failed to save recent files");
+                }
+                // Opening a queued file by hand makes it the current entry
+                if app.playlist_index.and_then(|i| app.playlist.get(i)) != Some(&path) {
+                    app.playlist_index = app.playlist.iter().position(|p| *p == path);
+                }
+                app.file_path = Some(path);
+                app.cover = info
+                    .cover
+                    .take()
+                    .map(|c| image::Handle::from_rgba(c.width, c.height, c.rgba));
+                app.audio_info = Some(info);
+                app.channel_layout = Some(analysis::detect_channel_layout(
+                    &data.samples,
+                    data.channels,
+                ));
+                app.tuner_reading = None;
+                app.level_meter.reset();
+                app.key = None;
+                app.detected_beats.clear();
+                app.downbeat_offset = 0.0;
+                app.beats.clear();
+                app.markers.clear();
+                app.waveform_view.markers.clear();
+                app.position = 0.0;
+                app.loop_region = None;
+                app.loops = vec![None; LOOP_SLOTS];
+                sync_loop_overlay(app);
+                app.waveform_view.playback_position = 0.0;
+                app.status = PlaybackStatus::Stopped;
+                app.error = None;
+                app.warning = match report.damaged_packets {
+                    0 => None,
+                    n if app.decode_policy == DecodePolicy::FillSilence => {
+                        Some(format!("{n} damaged packet(s) replaced with silence"))
+                    }
+                    n => Some(format!(
+                        "{n} damaged packet(s) skipped; later audio is shifted earlier"
+                    )),
+                };
+
+                let arc_data = Arc::new(data);
+                app.audio_data = Some(arc_data.clone());
+
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::LoadAudio(arc_data.clone()));
+                    let _ = tx.send(AudioCommand::SetGain(output_gain(app)));
+                }
+
+                app.original_bpm = app
+                    .file_path
+                    .as_ref()
+                    .and_then(|p| app.resume.bpms.get(p).copied());
+                app.original_bpm_input =
+                    app.original_bpm.map(|b| b.to_string()).unwrap_or_default();
+
+                if let Some(session) = app.pending_session.take() {
+                    apply_session(app, session);
+                } else if app.resume.enabled {
+                    let point = app
+                        .file_path
+                        .as_ref()
+                        .and_then(|p| app.resume.files.get(p).copied());
+                    if let Some(point) = point {
+                        apply_resume_point(app, point);
+                    }
+                }
+
+                if std::mem::take(&mut app.play_on_load) {
+                    if let Some(tx) = &app.cmd_tx {
+                        let _ = tx.send(AudioCommand::Play);
+                        app.status = PlaybackStatus::Playing;
+                        app.position_updated = Instant::now();
+                    }
+                }
+
+                let load_id = app.load.id;
+                let beat_data = arc_data.clone();
+                let key_data = arc_data.clone();
+                let source = app.file_path.clone();
+                Task::batch([
+                    Task::perform(
+                        async move {
+                            tokio::task::spawn_blocking(move || {
+                                WaveformPeaks::compute(&arc_data, source.as_deref())
+                            })
+                            .await
+                            .unwrap()
+                        },
+                        move |peaks| Message::PeaksComputed(load_id, peaks),
+                    ),
+                    Task::perform(
+                        async move {
+                            tokio::task::spawn_blocking(move || analysis::detect_beats(&beat_data))
+                                .await
+                                .unwrap()
+                        },
+                        move |beats| Message::BeatsDetected(load_id, beats),
+                    ),
+                    Task::perform(
+                        async move {
+                            tokio::task::spawn_blocking(move || analysis::detect_key(&key_data))
+                                .await
+                                .unwrap()
+                        },
+                        move |key| Message::KeyDetected(load_id, key),
+                    ),
+                    preload_next(app),
+                ])
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to load file");
+                app.decode_progress = None;
+                app.load.finish();
+                app.pending_session = None;
+                app.play_on_load = false;
+                app.error = Some(e);
+                Task::none()
+            }
+        },
+        Message::Preloaded(path, result) => {
+            // Dropped if the queue has moved on since it was started
+            if app.preload_path.as_ref() == Some(&path) {
+                match result {
+                    Ok(decoded) => app.preloaded = Some(decoded),
+                    Err(e) => {
+                        tracing::debug!(error = %e, "queue entry not preloaded");
+                        app.preload_path = None;
+                    }
+                }
+            }
+            Task::none()
+        }
+        Message::PeaksComputed(load_id, peaks) => {
+            if app.load.is_current(load_id) {
+                app.waveform_view.set_peaks(peaks);
+            }
+            Task::none()
+        }
+        Message::BeatsDetected(load_id, beats) => {
+            if app.load.is_current(load_id) && app.duration > 0.0 {
+                tracing::debug!(count = beats.len(), "beats detected");
+                app.detected_beats = beats;
+                sync_beats(app);
+            }
+            Task::none()
+        }
+        Message::KeyDetected(load_id, key) => {
+            if app.load.is_current(load_id) {
+                tracing::debug!(key = ?key, "key detected");
+                app.key = key;
+            }
+            Task::none()
+        }
+        Message::Waveform(WaveformMessage::OpenMenu(menu)) => {
+            app.context_menu = Some(menu);
+            Task::none()
+        }
+        // Anything done on or from the waveform closes the menu
+        Message::Waveform(wm) if app.context_menu.is_some() => {
+            app.context_menu = None;
+            update(app, Message::Waveform(wm))
+        }
+        Message::Waveform(wm) => match wm {
+            WaveformMessage::Seek(time) => {
+                // A drag that ends back under the threshold is a click
+                app.drag_start = None;
+                app.loop_editing = false;
+                sync_loop_overlay(app);
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::Seek(time));
+                    app.position = time;
+                    app.position_updated = Instant::now();
+                    if app.duration > 0.0 {
+                        app.waveform_view.playback_position = time / app.duration;
+                    }
+                }
+                Task::none()
+            }
+            WaveformMessage::LoopSelected(start, end) => {
+                let (start, end) = if app.loop_length_locked {
+                    // Only the start snaps; the length stays as locked
+                    let start = if app.beat_snap {
+                        nearest_beat(&app.beats, start)
+                    } else {
+                        start
+                    };
+                    (start, (start + app.locked_loop_length).min(app.duration))
+                } else {
+                    snap_to_beats(app, start, end)
+                };
+                app.loop_region = Some((start, end));
+                app.drag_start = None;
+                sync_loop_overlay(app);
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopRegion(Some((start, end))));
+                }
+                Task::none()
+            }
+            WaveformMessage::LoopPreview(start, end) => {
+                app.loop_editing = true;
+                app.waveform_view.loop_region = Some((start, end));
+                Task::none()
+            }
+            WaveformMessage::LoopAdjusted(start, end) => {
+                app.loop_editing = false;
+                // Dragging one edge onto the other leaves nothing to loop
+                app.loop_region = (end > start).then(|| snap_to_beats(app, start, end));
+                sync_loop_overlay(app);
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                }
+                Task::none()
+            }
+            WaveformMessage::DragStarted(frac) => {
+                app.drag_start = Some(frac);
+                Task::none()
+            }
+            WaveformMessage::Scrub(time) => {
+                if let Some(tx) = &app.cmd_tx {
+                    let _ = tx.send(AudioCommand::Scrub(time));
+                    // The playhead follows while paused; playing, it moves on release
+                    if app.status != PlaybackStatus::Playing && app.duration > 0.0 {
+                        app.waveform_view.playback_position = time / app.duration;
+                    }
+                }
+                Task::none()
+            }
+            WaveformMessage::AddMarker(time) => {
+                if app.audio_data.is_some() {
+                    let label = match app.marker_label_input.trim() {
+                        "" => format!("Marker {}", app.markers.len() + 1),
+                        label => label.to_string(),
+                    };
+                    let index = app.markers.partition_point(|(t, _)| *t <= time);
+                    app.markers.insert(index, (time, label));
+                    sync_markers(app);
+                }
+                Task::none()
+            }
+            WaveformMessage::RemoveMarker(index) => {
+                if index < app.markers.len() {
+                    app.markers.remove(index);
+                    sync_markers(app);
+                }
+                Task::none()
+            }
+            WaveformMessage::ZoomAt(anchor, factor) => {
+                app.waveform_view.zoom_at(anchor, factor);
+                app.follow_suspended = true;
+                Task::none()
+            }
+            WaveformMessage::ScrollTo(offset) => {
+                app.waveform_view.set_scroll_offset(offset);
+                app.follow_suspended = true;
+                Task::none()
+            }
+            WaveformMessage::ScaleVertically(factor) => {
+                app.waveform_view.scale_vertically(factor);
+                Task::none()
+            }
+            WaveformMessage::DragCancelled => {
+                app.drag_start = None;
+                app.loop_editing = false;
+                sync_loop_overlay(app);
+                Task::none()
+            }
+            WaveformMessage::DragMoved(frac) => {
+                if let Some(start) = app.drag_start {
+                    let (lo, hi) = if start < frac {
+                        (start, frac)
+                    } else {
+                        (frac, start)
+                    };
+                    let hi = if app.loop_length_locked && app.duration > 0.0 {
+                        (lo + app.locked_loop_length / app.duration).min(1.0)
+                    } else {
+                        hi
+                    };
+                    app.waveform_view.loop_region = Some((lo, hi));
+                }
+                Task::none()
+            }
+            WaveformMessage::ClearLoop => update(app, Message::Control(ControlMessage::ClearLoop)),
+            WaveformMessage::SetLoopStart(time) => {
+                // Without a loop, it runs to the end of the file
+                let end = app.loop_region.map_or(app.duration, |(_, end)| end);
+                if time < end {
+                    app.loop_region = Some((time, end));
+                    sync_loop_overlay(app);
+                    if let Some(tx) = &app.cmd_tx {
+                        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                    }
+                }
+                Task::none()
+            }
+            WaveformMessage::SetLoopEnd(time) => {
+                // Without a loop, it starts at the beginning of the file
+                let start = app.loop_region.map_or(0.0, |(start, _)| start);
+                if time > start {
+                    app.loop_region = Some((start, time));
+                    sync_loop_overlay(app);
+                    if let Some(tx) = &app.cmd_tx {
+                        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+                    }
+                }
+                Task::none()
+            }
+            // Handled above, along with closing the menu
+            WaveformMessage::OpenMenu(_) | WaveformMessage::CloseMenu => Task::none(),
+        },
+        Message::Tick => {
+            if app.last_counter_drain.elapsed() >= COUNTER_LOG_INTERVAL {
+                app.last_counter_drain = Instant::now();
+                let counters = engine::COUNTERS.drain();
+                tracing::debug!(
+                    callbacks = counters.callbacks,
+                    commands = counters.commands,
+                    overloaded_callbacks = counters.overloaded_callbacks,
+                    underruns = counters.underruns,
+                    "audio engine counters"
+                );
+            }
+            if let Some((message, _)) = app
+                .transient_error
+                .take_if(|(_, expires)| Instant::now() >= *expires)
+            {
+                // Unless a more lasting error has replaced it meanwhile
+                if app.error.as_ref() == Some(&message) {
+                    app.error = None;
+                }
+            }
+            if app.last_resume_save.elapsed() >= RESUME_SAVE_INTERVAL {
+                app.last_resume_save = Instant::now();
+                remember_position(app);
+            }
+            // While paused, read again only when the playhead has been moved
+            let moved = app.tuner_position != Some(app.position);
+            if app.tuner_enabled
+                && (app.status == PlaybackStatus::Playing || moved)
+                && app.last_tuner_update.elapsed() >= TUNER_INTERVAL
+            {
+                app.last_tuner_update = Instant::now();
+                app.tuner_position = Some(app.position);
+                if let Some(data) = &app.audio_data {
+                    // The note as heard, after pitch shifting
+                    let shift = 2f32.powf(total_pitch(app) / 12.0);
+                    app.tuner_reading = analysis::pitch_at(data, app.position)
+                        .map(|hz| NoteReading::from_frequency(hz * shift));
+                }
+            }
+            let mut device_lost = false;
+            let mut finished = false;
+            if let Some(rx) = &app.event_rx {
+                while let Ok(event) = rx.try_recv() {
+                    match event {
+                        AudioEvent::PositionChanged(pos) => {
+                            app.position = pos;
+                            app.position_updated = Instant::now();
+                            if app.duration > 0.0 {
+                                app.waveform_view.playback_position = pos / app.duration;
+                            }
+                        }
+                        AudioEvent::PlaybackFinished => {
+                            finished = true;
+                            app.status = PlaybackStatus::Stopped;
+                            app.position = 0.0;
+                            app.waveform_view.playback_position = 0.0;
+                        }
                         AudioEvent::Error(e) => {
+                            tracing::error!(error = %e, "audio engine error");
                             app.error = Some(e);
                         }
+                        AudioEvent::HighCpuLoad(ratio) => {
+                            tracing::warn!(ratio, "audio callbacks near their deadline");
+                            app.warning = Some(format!(
+                                "Audio processing is using {:.0}% of the available time; \
+                                 playback may glitch. Try a tempo closer to 100% or a \
+                                 larger audio buffer.",
+                                ratio * 100.0
+                            ));
+                        }
+                        AudioEvent::Underrun => app.underruns += 1,
+                        AudioEvent::DeviceChanged => device_lost = true,
+                        AudioEvent::TempoChanged(tempo) => app.tempo = tempo,
+                        AudioEvent::PausedAtLoopEnd => app.status = PlaybackStatus::Paused,
+                        AudioEvent::Levels(peak, sum_squares, frames) => {
+                            app.level_meter.push(peak, sum_squares, frames)
+                        }
+                        AudioEvent::LoopRepeatsFinished => {
+                            tracing::debug!(after = %app.after_loops, "loop repeats finished");
+                            if app.after_loops == AfterLoops::Stop {
+                                // The engine has stopped at the loop start
+                                app.status = PlaybackStatus::Paused;
+                                if let Some((start, _)) = app.loop_region {
+                                    app.position = start;
+                                    if app.duration > 0.0 {
+                                        app.waveform_view.playback_position = start / app.duration;
+                                    }
+                                }
+                            }
+                        }
                     }
                 }
             }
-            Task::none()
+            if device_lost {
+                return recover_from_device_loss(app);
+            }
+            interpolate_playhead(app);
+            update_beat_flash(app);
+            follow_playhead(app);
+            let media_keys: Vec<MediaKey> = match &app.media_keys {
+                Some(keys) => keys.pressed(),
+                None => Vec::new(),
+            };
+            let actions: Vec<MidiAction> = match &app.midi {
+                Some(listener) => listener.actions.try_iter().collect(),
+                None => Vec::new(),
+            };
+            let mut tasks = Vec::new();
+            // Carry on with the queue when its current entry ends
+            if finished && app.playlist_index.is_some() {
+                if let Some(next) = next_in_queue(app) {
+                    tasks.push(play_queue_entry(app, next, true));
+                }
+            }
+            for key in media_keys {
+                tasks.push(apply_media_key(app, key));
+            }
+            for action in actions {
+                tasks.push(apply_midi_action(app, action));
+            }
+            Task::batch(tasks)
         }
         Message::KeyEvent(key_event) => match key_event {
+            // Matched on the modified key so the layout decides what Shift gives
             keyboard::Event::KeyPressed {
-                key, modifiers: _, ..
-            } => match key.as_ref() {
+                modified_key: keyboard::Key::Character(c),
+                ..
+            } if matches!(c.as_str(), "," | "." | "<" | ">") => {
+                let step = app.loop_nudge;
+                match c.as_str() {
+                    "," => nudge_loop(app, -step, 0.0),
+                    "." => nudge_loop(app, step, 0.0),
+                    "<" => nudge_loop(app, 0.0, -step),
+                    _ => nudge_loop(app, 0.0, step),
+                }
+                Task::none()
+            }
+            keyboard::Event::KeyPressed { key, modifiers, .. } => match key.as_ref() {
                 keyboard::Key::Named(keyboard::key::Named::Space) => {
                     update(app, Message::Control(ControlMessage::PlayPause))
                 }
-                keyboard::Key::Named(keyboard::key::Named::ArrowLeft) => {
-                    let new_pos = (app.position - 5.0).max(0.0);
-                    if let Some(tx) = &app.cmd_tx {
-                        let _ = tx.send(AudioCommand::Seek(new_pos));
+                keyboard::Key::Named(
+                    arrow @ (keyboard::key::Named::ArrowLeft | keyboard::key::Named::ArrowRight),
+                ) => {
+                    let step = if modifiers.shift() && modifiers.command() {
+                        // A single sample frame
+                        match &app.audio_data {
+                            Some(data) => 1.0 / data.sample_rate as f64,
+                            None => return Task::none(),
+                        }
+                    } else if modifiers.shift() {
+                        app.fine_step
+                    } else if modifiers.alt() {
+                        ALT_SEEK_STEP
+                    } else {
+                        app.settings.seek_step
+                    };
+                    let step = if arrow == keyboard::key::Named::ArrowLeft {
+                        -step
+                    } else {
+                        step
+                    };
+                    seek_to(app, app.position + step);
+                    Task::none()
+                }
+                // Back to the top of the loop (or file) right away, still playing
+                keyboard::Key::Named(keyboard::key::Named::Enter)
+                | keyboard::Key::Character("r")
+                    if !modifiers.alt() && !modifiers.command() =>
+                {
+                    if app.audio_data.is_some() {
+                        let start = app.loop_region.map_or(0.0, |(start, _)| start);
+                        seek_to(app, start);
                     }
                     Task::none()
                 }
-                keyboard::Key::Named(keyboard::key::Named::ArrowRight) => {
-                    let new_pos = (app.position + 5.0).min(app.duration);
-                    if let Some(tx) = &app.cmd_tx {
-                        let _ = tx.send(AudioCommand::Seek(new_pos));
+                keyboard::Key::Character("b") if !modifiers.alt() => {
+                    update(app, Message::Control(ControlMessage::AddMarker))
+                }
+                keyboard::Key::Character("[") => {
+                    let position = app.position;
+                    if let Some((time, _)) = app
+                        .markers
+                        .iter()
+                        .rev()
+                        .find(|(t, _)| *t < position - MARKER_JUMP_TOLERANCE)
+                    {
+                        seek_to(app, *time);
+                    }
+                    Task::none()
+                }
+                keyboard::Key::Character("]") => {
+                    let position = app.position;
+                    if let Some((time, _)) = app
+                        .markers
+                        .iter()
+                        .find(|(t, _)| *t > position + MARKER_JUMP_TOLERANCE)
+                    {
+                        seek_to(app, *time);
+                    }
+                    Task::none()
+                }
+                keyboard::Key::Character("l") if !modifiers.alt() && !modifiers.command() => {
+                    update(app, Message::Control(ControlMessage::LoopSection))
+                }
+                keyboard::Key::Character("m") if !modifiers.alt() => {
+                    update(app, Message::Control(ControlMessage::ToggleMute))
+                }
+                keyboard::Key::Character("z") if !modifiers.alt() && !modifiers.command() => {
+                    let ctrl = if modifiers.shift() {
+                        ControlMessage::ZoomToFit
+                    } else {
+                        ControlMessage::ZoomToLoop
+                    };
+                    update(app, Message::Control(ctrl))
+                }
+                keyboard::Key::Character("0") if !modifiers.alt() => {
+                    update(app, Message::Control(ControlMessage::ResetTempoPitch))
+                }
+                // 1..9 recalls a stored loop; with Shift, stores the current one there
+                keyboard::Key::Character(c) if !modifiers.alt() && !modifiers.command() => {
+                    if let Some(slot) = loop_slot(c) {
+                        if modifiers.shift() {
+                            store_loop(app, slot);
+                        } else {
+                            recall_loop(app, slot);
+                        }
+                    }
+                    Task::none()
+                }
+                // Alt+0..9 jumps to 0%..90% of the file
+                keyboard::Key::Character(c) if modifiers.alt() => {
+                    if let Some(digit) = c.parse::<u8>().ok().filter(|d| *d <= 9) {
+                        if app.audio_data.is_some() {
+                            seek_to(app, digit as f64 / 10.0 * app.duration);
+                        }
                     }
                     Task::none()
                 }
@@ -285,14 +2301,578 @@ fn update(app: &mut App, message: Message) -> Task<Message> {
     }
 }
 
+/// Redraw the loop overlay from `app.loop_region` and `app.loops`, discarding
+/// any drag preview. The overlay holds fractions, so it maps onto the current
+/// canvas width.
+fn sync_loop_overlay(app: &mut App) {
+    let duration = app.duration;
+    let to_fractions = |region: Option<(f64, f64)>| match region {
+        Some((start, end)) if duration > 0.0 => Some((start / duration, end / duration)),
+        _ => None,
+    };
+    app.waveform_view.loop_region = to_fractions(app.loop_region);
+    app.waveform_view.stored_loops = app.loops.iter().map(|l| to_fractions(*l)).collect();
+}
+
+/// The loop slot a digit key stands for, counting from zero.
+fn loop_slot(key: &str) -> Option<usize> {
+    key.parse::<usize>()
+        .ok()
+        .filter(|d| (1..=LOOP_SLOTS).contains(d))
+        .map(|d| d - 1)
+}
+
+/// Keep the current loop in `slot`, or empty the slot when there is no loop.
+fn store_loop(app: &mut App, slot: usize) {
+    app.loops[slot] = app.loop_region;
+    tracing::debug!(slot = slot + 1, region = ?app.loop_region, "stored loop");
+    sync_loop_overlay(app);
+}
+
+/// Make the loop in `slot` the current one, if the slot holds one.
+fn recall_loop(app: &mut App, slot: usize) {
+    let Some(region) = app.loops[slot] else {
+        return;
+    };
+    app.loop_region = Some(region);
+    sync_loop_overlay(app);
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+    }
+}
+
+/// The stretch between the markers either side of `position`, running to the
+/// start or end of the file where there is no marker on that side.
+fn section_around(markers: &[(f64, String)], position: f64, duration: f64) -> (f64, f64) {
+    let next = markers.partition_point(|(t, _)| *t <= position);
+    let start = next.checked_sub(1).map_or(0.0, |i| markers[i].0);
+    let end = markers.get(next).map_or(duration, |(t, _)| *t);
+    (start, end)
+}
+
+/// Listen on the MIDI input called `port`, replacing any open one.
+fn connect_midi(app: &mut App, port: &str) -> Result<(), String> {
+    // Close the old port first; some systems only allow one connection
+    app.midi = None;
+    app.midi = Some(midi::listen(port, app.midi_config.bindings.clone())?);
+    Ok(())
+}
+
+/// Carry out a MIDI controller action as the matching control would.
+fn apply_midi_action(app: &mut App, action: MidiAction) -> Task<Message> {
+    match action {
+        MidiAction::PlayPause => update(app, Message::Control(ControlMessage::PlayPause)),
+        MidiAction::RestartLoop => {
+            if app.audio_data.is_some() {
+                seek_to(app, app.loop_region.map_or(0.0, |(start, _)| start));
+            }
+            Task::none()
+        }
+        MidiAction::TempoUp => {
+            let tempo = (app.tempo + MIDI_TEMPO_STEP).min(MAX_TEMPO);
+            update(app, Message::Control(ControlMessage::TempoChanged(tempo)))
+        }
+        MidiAction::TempoDown => {
+            let tempo = (app.tempo - MIDI_TEMPO_STEP).max(MIN_TEMPO);
+            update(app, Message::Control(ControlMessage::TempoChanged(tempo)))
+        }
+    }
+}
+
+/// Carry out a media key press: play/pause and stop as the buttons do,
+/// next and previous seek by the arrow-key step.
+fn apply_media_key(app: &mut App, key: MediaKey) -> Task<Message> {
+    match key {
+        MediaKey::PlayPause => update(app, Message::Control(ControlMessage::PlayPause)),
+        MediaKey::Stop => update(app, Message::Control(ControlMessage::Stop)),
+        MediaKey::Next | MediaKey::Previous => {
+            if app.audio_data.is_some() {
+                let step = if key == MediaKey::Next {
+                    app.settings.seek_step
+                } else {
+                    -app.settings.seek_step
+                };
+                seek_to(app, app.position + step);
+            }
+            Task::none()
+        }
+    }
+}
+
+/// Whether the dark theme is in use, following the system when asked to.
+/// Without a known system preference, dark is assumed.
+fn is_dark(app: &App) -> bool {
+    match app.settings.theme {
+        ThemeChoice::Dark => true,
+        ThemeChoice::Light => false,
+        ThemeChoice::System => app.system_theme != theme::Mode::Light,
+    }
+}
+
+/// Give the waveform the colours for the current theme.
+fn apply_theme(app: &mut App) {
+    let colors = app.settings.colors(is_dark(app));
+    app.waveform_view.set_colors(colors);
+}
+
+fn send_eq(app: &App, tx: &Sender<AudioCommand>) {
+    let _ = tx.send(AudioCommand::SetEq {
+        low_gain: app.eq_low,
+        mid_gain: app.eq_mid,
+        high_gain: app.eq_high,
+    });
+}
+
+fn send_filter(app: &App, tx: &Sender<AudioCommand>) {
+    let _ = tx.send(AudioCommand::SetFilter {
+        hp_hz: app.hp_hz,
+        lp_hz: app.lp_hz,
+    });
+}
+
+/// The current EQ, filter and stereo settings as a preset called `name`.
+fn current_preset(app: &App, name: String) -> DspPreset {
+    DspPreset {
+        name,
+        eq_low: app.eq_low,
+        eq_mid: app.eq_mid,
+        eq_high: app.eq_high,
+        hp_hz: app.hp_hz,
+        lp_hz: app.lp_hz,
+        stereo_width: app.stereo_width,
+        vocal_reduction: app.vocal_reduction,
+    }
+}
+
+/// Take on the settings in `preset` and pass them to the engine.
+fn apply_preset(app: &mut App, preset: &DspPreset) {
+    app.eq_low = preset.eq_low;
+    app.eq_mid = preset.eq_mid;
+    app.eq_high = preset.eq_high;
+    app.hp_hz = preset.hp_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+    app.lp_hz = preset.lp_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+    app.stereo_width = preset.stereo_width;
+    app.vocal_reduction = preset.vocal_reduction;
+    if let Some(tx) = &app.cmd_tx {
+        send_eq(app, tx);
+        send_filter(app, tx);
+        let _ = tx.send(AudioCommand::SetStereoWidth(app.stereo_width));
+        let _ = tx.send(AudioCommand::SetVocalReduction(app.vocal_reduction));
+    }
+}
+
+/// Linear output gain: the volume, plus the file's ReplayGain when it has
+/// tags and they are honoured.
+fn output_gain(app: &App) -> f32 {
+    let tagged = app
+        .audio_info
+        .as_ref()
+        .filter(|_| app.replay_gain)
+        .and_then(AudioInfo::replay_gain);
+    volume_gain(app.volume) * tagged.unwrap_or(1.0)
+}
+
+fn send_metronome(app: &App, tx: &Sender<AudioCommand>) {
+    let _ = tx.send(AudioCommand::SetMetronome {
+        enabled: app.metronome_enabled,
+        gain: app.metronome_gain,
+        beats_per_bar: app.beats_per_bar,
+    });
+}
+
+/// Read the tempo ramp inputs as (start tempo, ramp), with tempos as ratios.
+fn parse_tempo_ramp(app: &App) -> Result<(f32, TempoRamp), String> {
+    let percent = |input: &str, name: &str| {
+        input
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|p| *p > 0.0)
+            .map(|p| p / 100.0)
+            .ok_or_else(|| format!("Tempo ramp {name} must be a positive percentage"))
+    };
+    let start = percent(&app.tempo_ramp_start_input, "start")?;
+    let target = percent(&app.tempo_ramp_target_input, "target")?;
+    let step = percent(&app.tempo_ramp_step_input, "step")?;
+    let in_range = |t: f32| (MIN_TEMPO..=MAX_TEMPO).contains(&t);
+    if !in_range(start) || !in_range(target) {
+        return Err(format!(
+            "Tempo ramp must stay between {:.0}% and {:.0}%",
+            MIN_TEMPO * 100.0,
+            MAX_TEMPO * 100.0
+        ));
+    }
+    Ok((start, TempoRamp { target, step }))
+}
+
+/// Pass edited target and step on to a running ramp. The start only
+/// matters when the ramp is switched on.
+fn update_tempo_ramp(app: &mut App) {
+    if !app.tempo_ramp_enabled {
+        return;
+    }
+    // Keep the previous ramp while the input is half-typed
+    if let (Ok((_, ramp)), Some(tx)) = (parse_tempo_ramp(app), &app.cmd_tx) {
+        let _ = tx.send(AudioCommand::SetTempoRamp(Some(ramp)));
+    }
+}
+
+/// The detected beats moved by `offset` seconds, leaving out any that end up
+/// outside a file `duration` seconds long.
+fn shift_beats(beats: &[f64], offset: f64, duration: f64) -> Vec<f64> {
+    beats
+        .iter()
+        .map(|b| b + offset)
+        .filter(|b| (0.0..=duration).contains(b))
+        .collect()
+}
+
+/// Rebuild the beat grid from the detected beats and the downbeat offset,
+/// and give it to the waveform and the metronome.
+fn sync_beats(app: &mut App) {
+    app.beats = shift_beats(&app.detected_beats, app.downbeat_offset, app.duration);
+    app.waveform_view.beats = if app.duration > 0.0 {
+        app.beats.iter().map(|b| b / app.duration).collect()
+    } else {
+        Vec::new()
+    };
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::SetBeats(app.beats.clone()));
+    }
+}
+
+/// The beat in `beats` (sorted, in seconds) nearest to `time`, or `time`
+/// itself when there are none.
+fn nearest_beat(beats: &[f64], time: f64) -> f64 {
+    let i = beats.partition_point(|&b| b < time);
+    let before = i.checked_sub(1).map(|i| beats[i]);
+    let after = beats.get(i).copied();
+    before
+        .into_iter()
+        .chain(after)
+        .min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+        .unwrap_or(time)
+}
+
+/// Round a loop's start and end to the nearest detected beats, when beat
+/// snapping is on and there are beats. A loop that would shrink to nothing
+/// runs to the next beat instead.
+fn snap_to_beats(app: &App, start: f64, end: f64) -> (f64, f64) {
+    if !app.beat_snap || app.beats.is_empty() {
+        return (start, end);
+    }
+    let start = nearest_beat(&app.beats, start);
+    let mut end = nearest_beat(&app.beats, end);
+    if end <= start {
+        end = app
+            .beats
+            .iter()
+            .copied()
+            .find(|&b| b > start)
+            .unwrap_or(app.duration);
+    }
+    (start, end)
+}
+
+/// Pitch shift in semitones, with the fine adjustment included.
+fn total_pitch(app: &App) -> f32 {
+    app.pitch + app.pitch_cents / 100.0
+}
+
+/// Move the loop start and end by the given amounts in seconds, as long as
+/// the loop stays inside the file and doesn't turn inside out.
+fn nudge_loop(app: &mut App, start_delta: f64, end_delta: f64) {
+    let Some((start, end)) = app.loop_region else {
+        return;
+    };
+    let start = (start + start_delta).max(0.0);
+    let end = (end + end_delta).min(app.duration);
+    if end <= start {
+        return;
+    }
+    app.loop_region = Some((start, end));
+    sync_loop_overlay(app);
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+    }
+}
+
+/// Restore a session's settings on the freshly loaded file and the engine.
+fn apply_session(app: &mut App, session: Session) {
+    app.tempo = session.tempo;
+    // Whole semitones on the coarse slider, the rest in cents
+    app.pitch = session.pitch.round();
+    app.pitch_cents = ((session.pitch - app.pitch) * 100.0).round();
+    app.volume = session.volume;
+    app.loop_region = session
+        .loop_region
+        .filter(|(start, end)| start < end && *end <= app.duration);
+    app.markers = session.markers;
+    app.markers.retain(|(time, _)| *time <= app.duration);
+    app.markers.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // The beats are still being detected; the offset applies when they arrive
+    app.downbeat_offset = session.downbeat_offset;
+    if let Some(bpm) = session.original_bpm {
+        app.original_bpm = Some(bpm);
+        app.original_bpm_input = bpm.to_string();
+        remember_bpm(app);
+    }
+    sync_loop_overlay(app);
+    sync_markers(app);
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::SetTempo(app.tempo));
+        let _ = tx.send(AudioCommand::SetPitch(total_pitch(app)));
+        let _ = tx.send(AudioCommand::SetGain(output_gain(app)));
+        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+    }
+}
+
+/// Replace the markers and stored loops with imported ones, leaving out
+/// anything that doesn't fit the current file.
+fn apply_annotations(app: &mut App, mut annotations: Annotations) {
+    let changed = annotations.fit_to(app.duration);
+    if changed > 0 {
+        tracing::warn!(changed, "imported markers ran past the end of the file");
+        show_transient_error(
+            app,
+            format!("{changed} imported markers or loops were outside the file and were adjusted"),
+        );
+    }
+    app.markers = annotations.markers;
+    app.markers.sort_by(|a, b| a.0.total_cmp(&b.0));
+    app.loops = annotations.loops;
+    app.loops.resize(LOOP_SLOTS, None);
+    sync_loop_overlay(app);
+    sync_markers(app);
+}
+
+/// Record where the current file is, writing the resume state out if it
+/// changed.
+fn remember_position(app: &mut App) {
+    let Some(path) = &app.file_path else {
+        return;
+    };
+    if !app.resume.enabled {
+        return;
+    }
+    let point = ResumePoint {
+        position: app.position,
+        tempo: app.tempo,
+        loop_region: app.loop_region,
+    };
+    if app.resume.files.get(path) == Some(&point) {
+        return;
+    }
+    app.resume.files.insert(path.clone(), point);
+    if let Err(e) = app.resume.save() {
+        tracing::warn!(error = %e, "failed to save resume positions");
+    }
+}
+
+/// Store the current file's original BPM so it is filled in next time.
+fn remember_bpm(app: &mut App) {
+    let Some(path) = &app.file_path else {
+        return;
+    };
+    let changed = match app.original_bpm {
+        Some(bpm) => app.resume.bpms.insert(path.clone(), bpm) != Some(bpm),
+        None => app.resume.bpms.remove(path).is_some(),
+    };
+    if changed {
+        if let Err(e) = app.resume.save() {
+            tracing::warn!(error = %e, "failed to save original BPM");
+        }
+    }
+}
+
+/// Continue a reopened file from where it was left off.
+fn apply_resume_point(app: &mut App, point: ResumePoint) {
+    tracing::info!(
+        position = point.position,
+        "resuming where the file was left off"
+    );
+    app.tempo = point.tempo;
+    app.loop_region = point
+        .loop_region
+        .filter(|(start, end)| start < end && *end <= app.duration);
+    sync_loop_overlay(app);
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::SetTempo(app.tempo));
+        let _ = tx.send(AudioCommand::SetLoopRegion(app.loop_region));
+    }
+    seek_to(app, point.position);
+}
+
+/// Mirror `app.markers` into the waveform view as fractions.
+fn sync_markers(app: &mut App) {
+    app.waveform_view.markers = if app.duration > 0.0 {
+        app.markers
+            .iter()
+            .map(|(time, label)| (time / app.duration, label.clone()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+}
+
+/// Draw the playhead where playback should have got to since the last
+/// reported position. Only the drawing moves; `position` stays as reported.
+fn interpolate_playhead(app: &mut App) {
+    // Ping-pong loops run backwards half the time, so they keep stepping
+    let ping_pong = app.loop_region.is_some() && app.loop_mode == LoopMode::PingPong;
+    if app.status != PlaybackStatus::Playing || ping_pong || app.duration <= 0.0 {
+        return;
+    }
+    let elapsed = app
+        .position_updated
+        .elapsed()
+        .min(MAX_PLAYHEAD_INTERPOLATION);
+    // A held shuttle changes the speed, and runs backwards to the left
+    let speed = shuttle_speed(app.shuttle);
+    let rate = if speed == 0.0 { 1.0 } else { speed };
+    let mut position = app.position + elapsed.as_secs_f64() * (app.tempo * rate) as f64;
+    // The engine reports the wrap; don't run past the end meanwhile
+    let end = match app.loop_region {
+        Some((start, end)) if app.position < end && app.position >= start => end,
+        _ => app.duration,
+    };
+    position = position.clamp(0.0, end);
+    app.waveform_view.playback_position = position / app.duration;
+}
+
+/// Light the beat flash for the last beat the drawn playhead passed, so it
+/// keeps time with what is heard.
+fn update_beat_flash(app: &mut App) {
+    let playing = app.status == PlaybackStatus::Playing;
+    let position = app.waveform_view.playback_position * app.duration;
+    app.waveform_view.beat_flash = if app.beat_flash && playing {
+        waveform::beat_flash(&app.beats, position, app.tempo, app.beats_per_bar)
+    } else {
+        None
+    };
+}
+
+/// Keep the playhead in view on a zoomed waveform, paging on when it runs
+/// off either edge. Waits while the waveform is being dragged on, and while
+/// the user has scrolled away from the playhead.
+fn follow_playhead(app: &mut App) {
+    let view = &mut app.waveform_view;
+    if !app.follow_playhead || view.zoom <= 1.0 || app.status != PlaybackStatus::Playing {
+        return;
+    }
+    if app.drag_start.is_some() || app.loop_editing {
+        return;
+    }
+    let playhead = view.playback_position;
+    let (start, end) = view.visible_window();
+    let in_view = (start..end).contains(&playhead);
+    if app.follow_suspended {
+        app.follow_suspended = !in_view;
+        return;
+    }
+    if !in_view {
+        view.set_scroll_offset(playhead - view.visible_span() * FOLLOW_MARGIN);
+    }
+}
+
+/// Report `message` as an error that clears after a few seconds.
+fn show_transient_error(app: &mut App, message: String) {
+    app.error = Some(message.clone());
+    app.transient_error = Some((message, Instant::now() + TRANSIENT_ERROR_DURATION));
+}
+
+/// Seek to `time` (clamped to the file) and move the playhead immediately.
+fn seek_to(app: &mut App, time: f64) {
+    let time = time.clamp(0.0, app.duration);
+    // A jump brings a followed view along, even one scrolled away by hand
+    app.follow_suspended = false;
+    if let Some(tx) = &app.cmd_tx {
+        let _ = tx.send(AudioCommand::Seek(time));
+        app.position = time;
+        app.position_updated = Instant::now();
+        if app.duration > 0.0 {
+            app.waveform_view.playback_position = time / app.duration;
+        }
+    }
+}
+
 fn view(app: &App) -> Element<'_, Message> {
-    let controls = controls::view_controls(
-        app.status,
-        app.position,
-        app.duration,
-        app.tempo,
-        app.loop_region.is_some(),
-    )
+    let controls = controls::view_controls(ControlState {
+        status: app.status,
+        position: app.position,
+        duration: app.duration,
+        show_remaining: app.show_remaining,
+        meter: app.level_meter.reading(),
+        tempo: app.tempo,
+        shuttle: app.shuttle,
+        pitch: app.pitch,
+        pitch_cents: app.pitch_cents,
+        volume: app.volume,
+        muted: app.muted,
+        channel_mode: app.channel_mode,
+        vocal_reduction: app.vocal_reduction,
+        stereo_width: app.stereo_width,
+        eq_low: app.eq_low,
+        eq_mid: app.eq_mid,
+        eq_high: app.eq_high,
+        hp_hz: app.hp_hz,
+        lp_hz: app.lp_hz,
+        presets: &app.presets,
+        preset_name_input: &app.preset_name_input,
+        metronome_enabled: app.metronome_enabled,
+        beat_flash: app.beat_flash,
+        metronome_gain: app.metronome_gain,
+        beats_per_bar: app.beats_per_bar,
+        count_in_bars: app.count_in_bars,
+        // As drawn, so the readout follows a drag before it is committed
+        loop_region: app
+            .waveform_view
+            .loop_region
+            .map(|(start, end)| (start * app.duration, end * app.duration)),
+        zoomed: app.waveform_view.zoom > 1.0,
+        output_devices: &app.output_devices,
+        output_device: app.output_device.as_ref(),
+        midi_ports: &app.midi_ports,
+        midi_port: app.midi.as_ref().and(app.midi_config.port.as_ref()),
+        loop_length_locked: app.loop_length_locked,
+        loop_length_input: &app.loop_length_input,
+        loop_mode: app.loop_mode,
+        loop_snap: app.loop_snap,
+        beat_snap: app.beat_snap,
+        has_beats: !app.beats.is_empty(),
+        loop_crossfade: app.loop_crossfade,
+        loop_nudge: app.loop_nudge,
+        fine_step: app.fine_step,
+        seek_step: app.settings.seek_step,
+        go_to_input: &app.go_to_input,
+        loop_repeats_input: &app.loop_repeats_input,
+        after_loops: app.after_loops,
+        original_bpm: app.original_bpm,
+        original_bpm_input: &app.original_bpm_input,
+        downbeat_offset: app.downbeat_offset,
+        target_bpm_input: &app.target_bpm_input,
+        tempo_ramp_enabled: app.tempo_ramp_enabled,
+        tempo_ramp_start_input: &app.tempo_ramp_start_input,
+        tempo_ramp_target_input: &app.tempo_ramp_target_input,
+        tempo_ramp_step_input: &app.tempo_ramp_step_input,
+        drag_threshold_px: app.waveform_view.drag_threshold_px,
+        show_rms: app.waveform_view.show_rms,
+        tuner_enabled: app.tuner_enabled,
+        follow_playhead: app.follow_playhead,
+        waveform_style: app.settings.waveform_style,
+        db_scale: app.waveform_view.db_scale,
+        stop_behavior: app.stop_behavior,
+        theme: app.settings.theme,
+        show_stop_button: app.show_stop_button,
+        replay_gain: app.replay_gain,
+        resume_enabled: app.resume.enabled,
+        media_keys: app.media_keys.is_some(),
+        decode_policy: app.decode_policy,
+        export_channels: app.export_channels,
+        marker_label_input: &app.marker_label_input,
+        recent_files: &app.recent_files,
+        url_input: &app.url_input,
+    })
     .map(Message::Control);
 
     let waveform: Element<Message> = if app.audio_data.is_some() {
@@ -300,7 +2880,23 @@ fn view(app: &App) -> Element<'_, Message> {
             .width(Length::Fill)
             .height(Length::Fixed(200.0))
             .into();
-        canvas_el.map(Message::Waveform)
+        let waveform_el: Element<WaveformMessage> = match &app.context_menu {
+            Some(menu) => stack![
+                canvas_el,
+                context_menu::view_context_menu(menu, app.loop_region.is_some())
+            ]
+            .into(),
+            None => canvas_el,
+        };
+        // The overview only helps once there is more file than fits
+        let waveform_el = if app.waveform_view.zoom > 1.0 {
+            column![waveform_el, minimap::view_minimap(&app.waveform_view)]
+                .spacing(2)
+                .into()
+        } else {
+            waveform_el
+        };
+        waveform_el.map(Message::Waveform)
     } else {
         center(text("Open an audio file to begin").size(18))
             .width(Length::Fill)
@@ -308,7 +2904,95 @@ fn view(app: &App) -> Element<'_, Message> {
             .into()
     };
 
-    let mut content = column![controls, waveform].spacing(5);
+    let mut content = column![controls].spacing(5);
+
+    if let Some(progress) = app.download_progress {
+        content = content.push(
+            container(
+                row![
+                    text("Downloading…").size(14),
+                    progress_bar(0.0..=1.0, progress)
+                ]
+                .spacing(10)
+                .align_y(iced::Alignment::Center),
+            )
+            .padding([0, 10]),
+        );
+    }
+
+    if let Some(progress) = app.decode_progress {
+        content = content.push(
+            container(
+                row![text("Loading…").size(14), progress_bar(0.0..=1.0, progress)]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center),
+            )
+            .padding([0, 10]),
+        );
+    }
+
+    content = content.push(waveform);
+
+    let mut file_info = Row::new().spacing(20).align_y(iced::Alignment::Center);
+    if app.audio_data.is_some() {
+        file_info = file_info.push(view_cover(app.cover.as_ref()));
+    }
+    if let Some(info) = &app.audio_info {
+        file_info = file_info.push(text(info.to_string()).size(14));
+        if let Some(album) = &info.album {
+            file_info = file_info.push(text(format!("Album: {album}")).size(14));
+        }
+    }
+    if let Some(layout) = app.channel_layout {
+        file_info = file_info.push(text(format!("Channels: {layout}")).size(14));
+    }
+    if let Some(key) = app.key {
+        // Name the key it sounds in once shifted by whole semitones
+        let shift = total_pitch(app).round() as i32;
+        let label = if shift == 0 {
+            format!("Key: {key}")
+        } else {
+            format!("Key: {key} (shifted to {})", key.transposed(shift))
+        };
+        file_info = file_info.push(text(label).size(14));
+    }
+    if app.underruns > 0 {
+        let label = if app.underruns == 1 {
+            "1 dropout".to_string()
+        } else {
+            format!("{} dropouts", app.underruns)
+        };
+        file_info = file_info.push(
+            text(label)
+                .size(12)
+                .color(iced::Color::from_rgb(1.0, 0.8, 0.3).scale_alpha(0.7)),
+        );
+    }
+    content = content.push(container(file_info).padding([0, 10]));
+
+    if !app.playlist.is_empty() {
+        content = content.push(
+            playlist::view_playlist(
+                &app.playlist,
+                app.playlist_index,
+                app.playlist_keep_settings,
+            )
+            .map(Message::Playlist),
+        );
+    }
+
+    if app.audio_data.is_some() && app.tuner_enabled {
+        content = content.push(container(tuner::view_tuner(app.tuner_reading)).padding([0, 10]));
+    }
+
+    if let Some(warning) = &app.warning {
+        content = content.push(
+            container(
+                text(format!("Warning: {warning}")).color(iced::Color::from_rgb(1.0, 0.8, 0.3)),
+            )
+            .padding(10),
+        );
+    }
 
     if let Some(err) = &app.error {
         content = content.push(
@@ -323,24 +3007,129 @@ fn view(app: &App) -> Element<'_, Message> {
         .into()
 }
 
+/// The cover art thumbnail, or a blank square of the same size.
+fn view_cover(cover: Option<&image::Handle>) -> Element<'_, Message> {
+    const SIZE: f32 = 48.0;
+    match cover {
+        Some(handle) => image(handle.clone())
+            .width(Length::Fixed(SIZE))
+            .height(Length::Fixed(SIZE))
+            .into(),
+        None => container(text("No art").size(10))
+            .center_x(Length::Fixed(SIZE))
+            .center_y(Length::Fixed(SIZE))
+            .style(container::bordered_box)
+            .into(),
+    }
+}
+
 fn subscription(_app: &App) -> Subscription<Message> {
-    let tick =
-        iced::time::every(std::time::Duration::from_millis(16)).map(|_| Message::Tick);
+    let tick = iced::time::every(std::time::Duration::from_millis(16)).map(|_| Message::Tick);
 
     let keys = keyboard::listen().map(Message::KeyEvent);
 
-    Subscription::batch([tick, keys])
+    let close = window::close_requests().map(|_| Message::CloseRequested);
+
+    let system_theme = iced::system::theme_changes().map(Message::SystemThemeChanged);
+
+    let geometry = iced::event::listen_with(|event, _status, _id| match event {
+        iced::Event::Window(window::Event::Resized(size)) => Some(Message::WindowResized(size)),
+        iced::Event::Window(window::Event::Moved(position)) => Some(Message::WindowMoved(position)),
+        _ => None,
+    });
+
+    Subscription::batch([tick, keys, close, system_theme, geometry])
+}
+
+fn theme(app: &App) -> Theme {
+    if is_dark(app) {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
 }
 
-fn theme(_app: &App) -> Theme {
-    Theme::Dark
+/// Window size on first launch, before one has been saved.
+const DEFAULT_WINDOW_SIZE: Size = Size::new(1200.0, 580.0);
+
+/// Smallest restored window size, in case a tiny one was saved.
+const MIN_WINDOW_SIZE: Size = Size::new(400.0, 200.0);
+
+/// The saved window geometry, or the default size with no position.
+fn initial_window(settings: &Settings) -> WindowGeometry {
+    settings
+        .window
+        .map(|g| WindowGeometry {
+            width: g.width.max(MIN_WINDOW_SIZE.width),
+            height: g.height.max(MIN_WINDOW_SIZE.height),
+            position: g.position,
+        })
+        .unwrap_or(WindowGeometry {
+            width: DEFAULT_WINDOW_SIZE.width,
+            height: DEFAULT_WINDOW_SIZE.height,
+            position: None,
+        })
 }
 
 pub fn run() -> iced::Result {
+    let geometry = initial_window(&Settings::load());
+    let position = match geometry.position {
+        Some((x, y)) => window::Position::Specific(Point::new(x, y)),
+        None => window::Position::default(),
+    };
     iced::application(boot, update, view)
         .title(title)
         .subscription(subscription)
         .theme(theme)
-        .window_size((1000.0, 400.0))
+        .window_size((geometry.width, geometry.height))
+        .position(position)
+        .exit_on_close_request(false)
         .run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_load_is_current() {
+        let mut load = LoadTracker::default();
+        let (first, first_cancel) = load.start();
+        assert!(load.is_current(first));
+        assert!(!first_cancel.load(Ordering::Relaxed));
+
+        // A second load supersedes the first: its decode is told to stop and
+        // a FileLoaded tagged with its id is dropped
+        let (second, second_cancel) = load.start();
+        assert!(first_cancel.load(Ordering::Relaxed));
+        assert!(!load.is_current(first));
+        assert!(load.is_current(second));
+        assert!(!second_cancel.load(Ordering::Relaxed));
+
+        // Once finished, a later load has nothing to cancel
+        load.finish();
+        load.start();
+        assert!(!second_cancel.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn the_downbeat_offset_moves_the_whole_grid() {
+        let beats = [0.25, 0.75, 1.25, 1.75];
+        assert_eq!(shift_beats(&beats, 0.5, 2.0), [0.75, 1.25, 1.75]);
+        // Moved back by a beat, so the second detected beat is now the first
+        assert_eq!(shift_beats(&beats, -0.5, 2.0), [0.25, 0.75, 1.25]);
+        assert_eq!(shift_beats(&beats, 0.0, 2.0), beats);
+    }
+
+    #[test]
+    fn sections_run_between_the_nearest_markers() {
+        let markers = vec![(10.0, "Verse".to_string()), (25.0, "Chorus".to_string())];
+        assert_eq!(section_around(&markers, 12.0, 60.0), (10.0, 25.0));
+        // On a marker, its section is the one that starts there
+        assert_eq!(section_around(&markers, 10.0, 60.0), (10.0, 25.0));
+        // No marker before or after: the file start or end stands in
+        assert_eq!(section_around(&markers, 4.0, 60.0), (0.0, 10.0));
+        assert_eq!(section_around(&markers, 40.0, 60.0), (25.0, 60.0));
+        assert_eq!(section_around(&[], 40.0, 60.0), (0.0, 60.0));
+    }
+}
@@ -0,0 +1,525 @@
+use std::fmt;
+
+use super::types::AudioData;
+
+/// How the channels of a file relate to each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelLayout {
+    /// A single channel.
+    Mono,
+    /// Several channels carrying the same signal.
+    DualMono,
+    /// Channels carrying different signals.
+    TrueStereo,
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelLayout::Mono => write!(f, "Mono"),
+            ChannelLayout::DualMono => write!(f, "Dual-mono"),
+            ChannelLayout::TrueStereo => write!(f, "True stereo"),
+        }
+    }
+}
+
+/// Difference energy (relative to signal energy) below which channels count as identical.
+/// Roughly -60 dB, which tolerates lossy-codec noise between otherwise equal channels.
+const DUAL_MONO_THRESHOLD: f64 = 1e-6;
+
+/// Classify interleaved samples as mono, dual-mono or true stereo by comparing
+/// the energy of each channel's difference from the first channel against the
+/// total signal energy.
+pub fn detect_channel_layout(samples: &[f32], channels: u16) -> ChannelLayout {
+    let ch = channels as usize;
+    if ch <= 1 {
+        return ChannelLayout::Mono;
+    }
+
+    let mut signal_energy = 0.0f64;
+    let mut diff_energy = 0.0f64;
+    for frame in samples.chunks_exact(ch) {
+        let first = frame[0] as f64;
+        for &s in frame {
+            let s = s as f64;
+            signal_energy += s * s;
+            diff_energy += (s - first) * (s - first);
+        }
+    }
+
+    if signal_energy == 0.0 || diff_energy / signal_energy < DUAL_MONO_THRESHOLD {
+        ChannelLayout::DualMono
+    } else {
+        ChannelLayout::TrueStereo
+    }
+}
+
+/// Frames examined around the playhead when estimating pitch.
+const PITCH_WINDOW_FRAMES: usize = 2048;
+/// Lowest and highest fundamentals the pitch estimator looks for, in Hz.
+const MIN_PITCH_HZ: f32 = 50.0;
+const MAX_PITCH_HZ: f32 = 2000.0;
+/// Normalized correlation a period must reach to count as a clear pitch.
+/// Chords and noise stay below this, so they read as "no pitch".
+const PITCH_CLARITY_THRESHOLD: f32 = 0.9;
+/// Fraction of the strongest peak an earlier peak needs to be chosen over it,
+/// which keeps the estimator from locking onto a multiple of the period.
+const PITCH_PEAK_RATIO: f32 = 0.9;
+/// RMS below which a window counts as silence.
+const SILENCE_RMS: f32 = 1e-3;
+
+/// Estimate the fundamental frequency of the audio around `time` seconds,
+/// or `None` when there is no single clear pitch (silence, chords, noise).
+pub fn pitch_at(audio: &AudioData, time: f64) -> Option<f32> {
+    let ch = audio.channels as usize;
+    let total = audio.num_frames();
+    let centre = (time.max(0.0) * audio.sample_rate as f64) as usize;
+    let start = centre.saturating_sub(PITCH_WINDOW_FRAMES / 2).min(total);
+    let end = (start + PITCH_WINDOW_FRAMES).min(total);
+    let window: Vec<f32> = audio.samples[start * ch..end * ch]
+        .chunks_exact(ch)
+        .map(|frame| frame.iter().sum::<f32>() / ch as f32)
+        .collect();
+    estimate_pitch(&window, audio.sample_rate)
+}
+
+/// Estimate the fundamental frequency of a mono window using the normalized
+/// square difference function (McLeod pitch method).
+pub fn estimate_pitch(window: &[f32], sample_rate: u32) -> Option<f32> {
+    let sr = sample_rate as f32;
+    let min_lag = ((sr / MAX_PITCH_HZ) as usize).max(1);
+    let max_lag = ((sr / MIN_PITCH_HZ) as usize).min(window.len() / 2);
+    if min_lag + 1 >= max_lag {
+        return None;
+    }
+
+    let energy: f32 = window.iter().map(|s| s * s).sum();
+    if (energy / window.len() as f32).sqrt() < SILENCE_RMS {
+        return None;
+    }
+
+    // 1.0 at a lag where the signal repeats exactly, near 0 where it doesn't
+    let nsdf: Vec<f32> = (0..=max_lag)
+        .map(|lag| {
+            let mut acf = 0.0f32;
+            let mut norm = 0.0f32;
+            for (a, b) in window.iter().zip(&window[lag..]) {
+                acf += a * b;
+                norm += a * a + b * b;
+            }
+            if norm > 0.0 {
+                2.0 * acf / norm
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    let is_peak = |lag: usize| nsdf[lag] > nsdf[lag - 1] && nsdf[lag] >= nsdf[lag + 1];
+    let best = (min_lag..max_lag)
+        .filter(|&lag| is_peak(lag))
+        .map(|lag| nsdf[lag])
+        .fold(f32::MIN, f32::max);
+    if best < PITCH_CLARITY_THRESHOLD {
+        return None;
+    }
+    let lag =
+        (min_lag..max_lag).find(|&lag| is_peak(lag) && nsdf[lag] >= best * PITCH_PEAK_RATIO)?;
+
+    // Parabolic interpolation for a sub-sample period
+    let (l, c, r) = (nsdf[lag - 1], nsdf[lag], nsdf[lag + 1]);
+    let denom = l - 2.0 * c + r;
+    let offset = if denom != 0.0 {
+        0.5 * (l - r) / denom
+    } else {
+        0.0
+    };
+    Some(sr / (lag as f32 + offset))
+}
+
+/// Find the zero crossing in interleaved `samples` nearest to `frame`, looking
+/// at most `max_distance` frames either way. Channels are averaged to mono
+/// first. Returns the first frame after the sign change, or `frame` itself
+/// when there is no crossing in range.
+pub fn nearest_zero_crossing(
+    samples: &[f32],
+    channels: u16,
+    frame: usize,
+    max_distance: usize,
+) -> usize {
+    let ch = channels as usize;
+    let total = samples.len() / ch;
+    if total < 2 || frame >= total {
+        return frame;
+    }
+    let mono = |f: usize| samples[f * ch..(f + 1) * ch].iter().sum::<f32>() / ch as f32;
+    // A crossing "at" f lies between f - 1 and f
+    let crosses = |f: usize| {
+        let (a, b) = (mono(f - 1), mono(f));
+        (a <= 0.0 && b > 0.0) || (a >= 0.0 && b < 0.0) || b == 0.0
+    };
+    for distance in 0..=max_distance {
+        if frame > distance && crosses(frame - distance) {
+            return frame - distance;
+        }
+        let after = frame + distance;
+        if distance > 0 && after < total && crosses(after) {
+            return after;
+        }
+    }
+    frame
+}
+
+/// Frames per step of the onset envelope used for beat detection.
+const BEAT_HOP: usize = 512;
+/// Tempo range the beat detector considers.
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+/// Tempo the detector leans towards when several multiples fit about as well,
+/// and how strongly (in octaves of spread).
+const PREFERRED_BPM: f64 = 120.0;
+const BPM_PREFERENCE_OCTAVES: f64 = 1.0;
+/// Fraction of the beat period either side of a predicted beat searched for
+/// the onset it actually lands on.
+const BEAT_SEARCH_FRACTION: f64 = 0.1;
+
+/// Detect beat times in seconds.
+///
+/// Builds an onset envelope from rises in short-term energy, takes the tempo
+/// from its autocorrelation, picks the phase where onsets line up best with
+/// that period, then follows the beats through the file, nudging each one
+/// onto the strongest nearby onset so gentle tempo drift is tracked.
+/// Returns nothing when no steady pulse is found.
+pub fn detect_beats(audio: &AudioData) -> Vec<f64> {
+    let ch = audio.channels as usize;
+    let hop_secs = BEAT_HOP as f64 / audio.sample_rate as f64;
+
+    let energies: Vec<f32> = audio
+        .samples
+        .chunks(BEAT_HOP * ch)
+        .map(|hop| {
+            let energy: f32 = hop
+                .chunks_exact(ch)
+                .map(|frame| {
+                    let mono = frame.iter().sum::<f32>() / ch as f32;
+                    mono * mono
+                })
+                .sum();
+            (1.0 + energy).ln()
+        })
+        .collect();
+    // onsets[i] is the rise into hop i + 1
+    let onsets: Vec<f32> = energies
+        .windows(2)
+        .map(|w| (w[1] - w[0]).max(0.0))
+        .collect();
+
+    let min_lag = ((60.0 / MAX_BPM / hop_secs).floor() as usize).max(1);
+    let max_lag = (60.0 / MIN_BPM / hop_secs).ceil() as usize;
+    if onsets.len() < max_lag * 4 {
+        return Vec::new();
+    }
+
+    let mean = onsets.iter().sum::<f32>() / onsets.len() as f32;
+    let centred: Vec<f32> = onsets.iter().map(|o| o - mean).collect();
+    let acf: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| {
+            let sum: f32 = centred
+                .iter()
+                .zip(&centred[lag..])
+                .map(|(a, b)| a * b)
+                .sum();
+            sum / (centred.len() - lag) as f32
+        })
+        .collect();
+    let weighted = |lag: usize| {
+        let bpm = 60.0 / (lag as f64 * hop_secs);
+        let octaves = (bpm / PREFERRED_BPM).log2() / BPM_PREFERENCE_OCTAVES;
+        acf[lag] as f64 * (-0.5 * octaves * octaves).exp()
+    };
+    let Some(lag) = (min_lag..=max_lag).max_by(|&a, &b| weighted(a).total_cmp(&weighted(b))) else {
+        return Vec::new();
+    };
+    if acf[lag] <= 0.0 {
+        return Vec::new();
+    }
+
+    // Parabolic interpolation for a period between whole hops
+    let (l, c, r) = (acf[lag - 1], acf[lag], acf[lag + 1]);
+    let denom = l - 2.0 * c + r;
+    let offset = if denom != 0.0 {
+        (0.5 * (l - r) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let period = lag as f64 + offset as f64;
+
+    let phase = (0..lag)
+        .max_by(|&a, &b| {
+            let score = |start: usize| -> f32 {
+                (0..)
+                    .map(|k| (start as f64 + k as f64 * period).round() as usize)
+                    .take_while(|&i| i < onsets.len())
+                    .map(|i| onsets[i])
+                    .sum()
+            };
+            score(a).total_cmp(&score(b))
+        })
+        .unwrap_or(0);
+
+    let reach = (period * BEAT_SEARCH_FRACTION).round() as usize;
+    let mut beats = Vec::new();
+    let mut predicted = phase as f64;
+    while (predicted.round() as usize) < onsets.len() {
+        let centre = predicted.round() as usize;
+        let lo = centre.saturating_sub(reach);
+        let hi = (centre + reach).min(onsets.len() - 1);
+        // Stay on the grid unless a clear onset is nearby
+        let best = (lo..=hi)
+            .max_by(|&a, &b| onsets[a].total_cmp(&onsets[b]))
+            .filter(|&i| onsets[i] > mean)
+            .map(|i| i as f64)
+            .unwrap_or(predicted);
+        beats.push((best + 1.0) * hop_secs);
+        predicted = best + period;
+    }
+    beats
+}
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Sample rate the audio is reduced to before key detection; enough for the
+/// notes that carry the harmony.
+const KEY_SAMPLE_RATE: u32 = 11025;
+/// Frames per key-detection window at `KEY_SAMPLE_RATE`, about a third of a second.
+const KEY_WINDOW: usize = 4096;
+/// MIDI notes whose energy is gathered into the chroma, C2 to B6.
+const KEY_NOTES: std::ops::Range<i32> = 36..96;
+/// Correlation with the best key profile below which no key is reported.
+const KEY_MIN_CORRELATION: f32 = 0.5;
+
+/// Krumhansl-Kessler key profiles: how well each pitch class, counted up
+/// from the tonic, fits a major and a minor key.
+const MAJOR_PROFILE: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// A musical key, such as A minor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    /// Pitch class of the tonic, 0 for C up to 11 for B.
+    pub tonic: usize,
+    pub minor: bool,
+}
+
+impl Key {
+    /// The key `semitones` higher (or lower, when negative).
+    pub fn transposed(self, semitones: i32) -> Key {
+        Key {
+            tonic: (self.tonic as i32 + semitones).rem_euclid(12) as usize,
+            minor: self.minor,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{} {mode}", NOTE_NAMES[self.tonic])
+    }
+}
+
+/// Detect the key of the whole file.
+///
+/// Gathers the energy of each note across the file into a 12-bin chroma,
+/// then correlates it with the major and minor key profiles at each of the
+/// twelve tonics. Returns `None` for silence or when no key fits clearly.
+pub fn detect_key(audio: &AudioData) -> Option<Key> {
+    // Box-filter decimation; the leftover aliasing barely moves the chroma
+    let factor = (audio.sample_rate / KEY_SAMPLE_RATE).max(1) as usize;
+    let sample_rate = audio.sample_rate as f32 / factor as f32;
+    let mono: Vec<f32> = audio
+        .to_mono()
+        .chunks(factor)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect();
+
+    let hann: Vec<f32> = (0..KEY_WINDOW)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / KEY_WINDOW as f32).cos())
+        .collect();
+    // Goertzel coefficient for each note
+    let coeffs: Vec<(usize, f32)> = KEY_NOTES
+        .filter_map(|midi| {
+            let hz = 440.0 * 2f32.powf((midi - 69) as f32 / 12.0);
+            (hz < sample_rate / 2.0).then(|| {
+                let w = std::f32::consts::TAU * hz / sample_rate;
+                (midi.rem_euclid(12) as usize, 2.0 * w.cos())
+            })
+        })
+        .collect();
+
+    let mut chroma = [0.0f32; 12];
+    let mut windowed = vec![0.0f32; KEY_WINDOW];
+    for window in mono.chunks_exact(KEY_WINDOW) {
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if (energy / KEY_WINDOW as f32).sqrt() < SILENCE_RMS {
+            continue;
+        }
+        for ((w, s), h) in windowed.iter_mut().zip(window).zip(&hann) {
+            *w = s * h;
+        }
+        for &(class, coeff) in &coeffs {
+            let (mut s1, mut s2) = (0.0f32, 0.0f32);
+            for &x in &windowed {
+                let s0 = x + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s0;
+            }
+            let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+            chroma[class] += power.max(0.0).sqrt();
+        }
+    }
+    if chroma.iter().all(|&c| c == 0.0) {
+        return None;
+    }
+
+    let (key, fit) = (0..12)
+        .flat_map(|tonic| [(tonic, false), (tonic, true)])
+        .map(|(tonic, minor)| {
+            let profile = if minor {
+                &MINOR_PROFILE
+            } else {
+                &MAJOR_PROFILE
+            };
+            let rotated: Vec<f32> = (0..12).map(|i| profile[(i + 12 - tonic) % 12]).collect();
+            (Key { tonic, minor }, correlation(&chroma, &rotated))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    (fit >= KEY_MIN_CORRELATION).then_some(key)
+}
+
+/// Pearson correlation of two equally long series.
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let mut cov = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    cov / (var_a * var_b).sqrt()
+}
+
+/// A frequency as the nearest equal-tempered note (A4 = 440 Hz).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteReading {
+    /// MIDI note number of the nearest note.
+    pub midi: i32,
+    /// Deviation from that note, from -50 to +50 cents.
+    pub cents: f32,
+}
+
+impl NoteReading {
+    pub fn from_frequency(hz: f32) -> Self {
+        let note = 69.0 + 12.0 * (hz / 440.0).log2();
+        let midi = note.round();
+        NoteReading {
+            midi: midi as i32,
+            cents: (note - midi) * 100.0,
+        }
+    }
+}
+
+impl fmt::Display for NoteReading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = NOTE_NAMES[self.midi.rem_euclid(12) as usize];
+        let octave = self.midi.div_euclid(12) - 1;
+        write!(f, "{name}{octave}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleaved stereo sine with `period` frames per cycle, the same on both channels.
+    fn stereo_sine(frames: usize, period: f32) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|f| {
+                let s = (f as f32 * std::f32::consts::TAU / period + 0.3).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_crossing_lands_next_to_a_sign_change() {
+        let samples = stereo_sine(4800, 97.0);
+        let left = |f: usize| samples[2 * f];
+        for frame in (10..4700).step_by(37) {
+            let snapped = nearest_zero_crossing(&samples, 2, frame, 60);
+            // The nearest sign change is within half a period, so one is in reach
+            assert!(
+                snapped.abs_diff(frame) <= 49,
+                "{frame} snapped to {snapped}"
+            );
+            let changes = (snapped.saturating_sub(1)..=snapped + 1)
+                .any(|f| f > 0 && (left(f - 1) <= 0.0) != (left(f) <= 0.0));
+            assert!(changes, "no sign change within a frame of {snapped}");
+        }
+    }
+
+    #[test]
+    fn zero_crossing_out_of_reach_leaves_the_frame() {
+        // Crosses zero between frames 452 and 453, then every 500 frames
+        let samples = stereo_sine(4000, 1000.0);
+        assert_eq!(nearest_zero_crossing(&samples, 2, 700, 100), 700);
+        assert_eq!(nearest_zero_crossing(&samples, 2, 700, 0), 700);
+        assert_eq!(nearest_zero_crossing(&samples, 2, 700, 300), 453);
+
+        let positive = vec![0.5; 2 * 1000];
+        assert_eq!(nearest_zero_crossing(&positive, 2, 500, 200), 500);
+    }
+
+    #[test]
+    fn identical_channels_are_dual_mono() {
+        let samples = stereo_sine(4800, 97.0);
+        assert_eq!(detect_channel_layout(&samples, 2), ChannelLayout::DualMono);
+        // Digital silence has nothing to tell the channels apart
+        assert_eq!(
+            detect_channel_layout(&[0.0; 200], 2),
+            ChannelLayout::DualMono
+        );
+    }
+
+    #[test]
+    fn decorrelated_channels_are_true_stereo() {
+        let samples: Vec<f32> = (0..4800)
+            .flat_map(|f| {
+                let t = f as f32 * std::f32::consts::TAU;
+                [(t / 97.0).sin(), (t / 61.0).cos()]
+            })
+            .collect();
+        assert_eq!(
+            detect_channel_layout(&samples, 2),
+            ChannelLayout::TrueStereo
+        );
+    }
+
+    #[test]
+    fn single_channel_is_mono() {
+        let samples: Vec<f32> = stereo_sine(4800, 97.0).into_iter().step_by(2).collect();
+        assert_eq!(detect_channel_layout(&samples, 1), ChannelLayout::Mono);
+    }
+}
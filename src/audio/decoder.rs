@@ -1,17 +1,122 @@
+use std::fmt;
 use std::fs::File;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::core::probe::Hint;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::TimeBase;
 
 use super::types::AudioData;
 
-/// Decode an audio file into an `AudioData` struct with all samples in memory.
-pub fn decode_file(path: &Path) -> Result<AudioData, String> {
+/// What to do with packets the codec fails to decode.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DecodePolicy {
+    /// Fail the whole load.
+    Strict,
+    /// Drop the packet, shortening the audio.
+    #[default]
+    Lenient,
+    /// Replace the packet with silence of the same length to keep timing intact.
+    FillSilence,
+}
+
+impl DecodePolicy {
+    pub const ALL: [DecodePolicy; 3] = [
+        DecodePolicy::Strict,
+        DecodePolicy::Lenient,
+        DecodePolicy::FillSilence,
+    ];
+}
+
+impl fmt::Display for DecodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodePolicy::Strict => write!(f, "Fail"),
+            DecodePolicy::Lenient => write!(f, "Skip"),
+            DecodePolicy::FillSilence => write!(f, "Fill with silence"),
+        }
+    }
+}
+
+/// Cover art is shrunk to fit a square this many pixels across.
+const COVER_SIZE: u32 = 64;
+
+/// Smallest change in decoded fraction worth reporting as progress.
+const PROGRESS_STEP: f32 = 0.01;
+
+/// Problems recovered from while decoding.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecodeReport {
+    /// Packets that failed to decode and were skipped or filled with silence.
+    pub damaged_packets: usize,
+}
+
+/// What the file says about its audio, for display and playback gain.
+#[derive(Debug, Clone, Default)]
+pub struct AudioInfo {
+    /// Short codec name, such as "mp3" or "flac".
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Bit depth of the stored samples; lossy codecs have none.
+    pub bits_per_sample: Option<u32>,
+    /// Tags, when the file has them.
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// ReplayGain adjustments in dB, when the file is tagged with them.
+    pub track_gain_db: Option<f32>,
+    pub album_gain_db: Option<f32>,
+    /// Embedded cover art, already decoded and shrunk.
+    pub cover: Option<CoverArt>,
+}
+
+/// A decoded cover image.
+#[derive(Clone)]
+pub struct CoverArt {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA pixels, row by row.
+    pub rgba: Vec<u8>,
+}
+
+impl fmt::Debug for CoverArt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CoverArt({}x{})", self.width, self.height)
+    }
+}
+
+impl AudioInfo {
+    /// Linear gain from the ReplayGain tags, preferring the track gain.
+    pub fn replay_gain(&self) -> Option<f32> {
+        self.track_gain_db
+            .or(self.album_gain_db)
+            .map(|db| 10.0_f32.powf(db / 20.0))
+    }
+}
+
+impl fmt::Display for AudioInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {} Hz", self.codec.to_uppercase(), self.sample_rate)?;
+        if let Some(bits) = self.bits_per_sample {
+            write!(f, ", {bits}-bit")?;
+        }
+        match self.channels {
+            1 => write!(f, ", mono"),
+            2 => write!(f, ", stereo"),
+            n => write!(f, ", {n} channels"),
+        }
+    }
+}
+
+/// Open and probe an audio file, returning its format reader along with any
+/// metadata found ahead of the container (such as ID3v2).
+fn probe_file(path: &Path) -> Result<ProbeResult, String> {
     let file = File::open(path).map_err(|e| format!("Failed to open file: {e}"))?;
     let mss = MediaSourceStream::new(Box::new(file), Default::default());
 
@@ -20,15 +125,131 @@ pub fn decode_file(path: &Path) -> Result<AudioData, String> {
         hint.with_extension(ext);
     }
 
-    let probed = symphonia::default::get_probe()
+    symphonia::default::get_probe()
         .format(
             &hint,
             mss,
             &FormatOptions::default(),
             &MetadataOptions::default(),
         )
-        .map_err(|e| format!("Failed to probe format: {e}"))?;
+        .map_err(|e| format!("Failed to probe format: {e}"))
+}
+
+/// Fill in the title, artist, album and ReplayGain from `revision`, keeping
+/// any already found.
+fn read_tags(info: &mut AudioInfo, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        // ID3 keeps ReplayGain in TXXX frames, which may not get a standard key
+        let key = tag.key.to_ascii_uppercase();
+        let gain = match tag.std_key {
+            Some(StandardTagKey::ReplayGainTrackGain) => Some(&mut info.track_gain_db),
+            Some(StandardTagKey::ReplayGainAlbumGain) => Some(&mut info.album_gain_db),
+            _ if key.ends_with("REPLAYGAIN_TRACK_GAIN") => Some(&mut info.track_gain_db),
+            _ if key.ends_with("REPLAYGAIN_ALBUM_GAIN") => Some(&mut info.album_gain_db),
+            _ => None,
+        };
+        if let Some(gain) = gain {
+            if gain.is_none() {
+                *gain = parse_replay_gain(&tag.value.to_string());
+            }
+            continue;
+        }
+        let slot = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => &mut info.title,
+            Some(StandardTagKey::Artist) => &mut info.artist,
+            Some(StandardTagKey::Album) => &mut info.album,
+            _ => continue,
+        };
+        let value = tag.value.to_string();
+        if slot.is_none() && !value.trim().is_empty() {
+            *slot = Some(value.trim().to_string());
+        }
+    }
+}
+
+/// Read a ReplayGain value such as "-6.54 dB" as a number of dB.
+fn parse_replay_gain(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    number
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|db| db.is_finite())
+}
+
+/// Decode the front cover from `revision` (or its first picture) into a
+/// thumbnail, unless a cover was already found. Runs on the decoding worker,
+/// so a large image never holds up the UI.
+fn read_cover(info: &mut AudioInfo, revision: &MetadataRevision) {
+    if info.cover.is_some() {
+        return;
+    }
+    let visuals = revision.visuals();
+    let Some(visual) = visuals
+        .iter()
+        .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+        .or(visuals.first())
+    else {
+        return;
+    };
+    match image::load_from_memory(&visual.data) {
+        Ok(img) => {
+            let thumb = img.thumbnail(COVER_SIZE, COVER_SIZE).to_rgba8();
+            info.cover = Some(CoverArt {
+                width: thumb.width(),
+                height: thumb.height(),
+                rgba: thumb.into_raw(),
+            });
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, media_type = %visual.media_type, "unreadable cover art");
+        }
+    }
+}
+
+/// Rough number of bytes needed to hold a decoded file in memory: the
+/// interleaved f32 samples plus the mono mixdown used for waveform peaks.
+pub fn estimate_memory_bytes(frames: u64, channels: u16) -> u64 {
+    let samples = frames * channels as u64 * std::mem::size_of::<f32>() as u64;
+    let mono = frames * std::mem::size_of::<f32>() as u64;
+    samples + mono
+}
+
+/// Estimate the memory a file will need once decoded, without decoding it.
+/// Returns `None` when the container doesn't report a frame count.
+pub fn estimate_file_memory(path: &Path) -> Result<Option<u64>, String> {
+    let format = probe_file(path)?.format;
+    let track = format.default_track().ok_or("No default track found")?;
+    let channels = track
+        .codec_params
+        .channels
+        .map(|c| c.count() as u16)
+        .unwrap_or(2);
+    Ok(track
+        .codec_params
+        .n_frames
+        .map(|frames| estimate_memory_bytes(frames, channels)))
+}
 
+/// Decode an audio file into an `AudioData` struct with all samples in memory,
+/// handling undecodable packets according to `policy`. Also returns what the
+/// file says about itself.
+///
+/// `progress` is called with the decoded fraction (0.0 to 1.0) as decoding
+/// advances, when the container reports its length. Setting `cancel` stops
+/// decoding at the next packet with an error.
+pub fn decode_file(
+    path: &Path,
+    policy: DecodePolicy,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(f32),
+) -> Result<(AudioData, AudioInfo, DecodeReport), String> {
+    tracing::info!(path = %path.display(), "decoding file");
+    let mut probed = probe_file(path)?;
     let mut format = probed.format;
 
     let track = format
@@ -46,13 +267,41 @@ pub fn decode_file(path: &Path) -> Result<AudioData, String> {
         .map(|c| c.count() as u16)
         .unwrap_or(2);
 
-    let mut decoder = symphonia::default::get_codecs()
+    let codecs = symphonia::default::get_codecs();
+    let mut decoder = codecs
         .make(&track.codec_params, &DecoderOptions::default())
         .map_err(|e| format!("Failed to create decoder: {e}"))?;
+    let mut info = AudioInfo {
+        codec: codecs
+            .get_codec(track.codec_params.codec)
+            .map(|c| c.short_name.to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        sample_rate,
+        channels,
+        bits_per_sample: track.codec_params.bits_per_sample,
+        ..AudioInfo::default()
+    };
+    // Tags in the container win over ones found while probing
+    if let Some(revision) = format.metadata().current() {
+        read_tags(&mut info, revision);
+        read_cover(&mut info, revision);
+    }
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        read_tags(&mut info, revision);
+        read_cover(&mut info, revision);
+    }
 
     let mut samples: Vec<f32> = Vec::new();
+    let mut report = DecodeReport::default();
+    let total_frames = track.codec_params.n_frames.filter(|&n| n > 0);
+    let mut reported = 0.0f32;
 
     loop {
+        if cancel.load(Ordering::Relaxed) {
+            tracing::info!(path = %path.display(), "decoding cancelled");
+            return Err("Decoding cancelled".to_string());
+        }
+
         let packet = match format.next_packet() {
             Ok(p) => p,
             Err(symphonia::core::errors::Error::IoError(ref e))
@@ -70,7 +319,8 @@ pub fn decode_file(path: &Path) -> Result<AudioData, String> {
         let decoded = match decoder.decode(&packet) {
             Ok(d) => d,
             Err(symphonia::core::errors::Error::DecodeError(e)) => {
-                eprintln!("Decode warning: {e}");
+                let frames = packet_frames(packet.dur, track.codec_params.time_base, sample_rate);
+                recover_damaged_packet(policy, e, frames, channels, &mut samples, &mut report)?;
                 continue;
             }
             Err(e) => return Err(format!("Decode error: {e}")),
@@ -82,15 +332,226 @@ pub fn decode_file(path: &Path) -> Result<AudioData, String> {
         let mut sample_buf = SampleBuffer::<f32>::new(num_frames as u64, spec);
         sample_buf.copy_interleaved_ref(decoded);
         samples.extend_from_slice(sample_buf.samples());
+
+        if let Some(total) = total_frames {
+            let decoded = (samples.len() / channels as usize) as f32 / total as f32;
+            if decoded - reported >= PROGRESS_STEP {
+                reported = decoded.min(1.0);
+                progress(reported);
+            }
+        }
     }
 
     let num_frames = samples.len() / channels as usize;
     let duration = num_frames as f64 / sample_rate as f64;
-
-    Ok(AudioData {
-        samples,
+    tracing::info!(
+        codec = %info.codec,
         sample_rate,
         channels,
+        frames = num_frames,
         duration,
-    })
+        damaged_packets = report.damaged_packets,
+        "decoded file"
+    );
+
+    Ok((
+        AudioData {
+            samples,
+            sample_rate,
+            channels,
+            duration,
+        },
+        info,
+        report,
+    ))
+}
+
+/// Deal with a packet of `frames` frames the codec couldn't decode as `policy`
+/// says: fail the load, drop the packet, or put silence in its place.
+fn recover_damaged_packet(
+    policy: DecodePolicy,
+    error: &str,
+    frames: usize,
+    channels: u16,
+    samples: &mut Vec<f32>,
+    report: &mut DecodeReport,
+) -> Result<(), String> {
+    if policy == DecodePolicy::Strict {
+        return Err(format!("Damaged packet: {error}"));
+    }
+    tracing::warn!(error, ?policy, "undecodable packet");
+    report.damaged_packets += 1;
+    if policy == DecodePolicy::FillSilence {
+        samples.resize(samples.len() + frames * channels as usize, 0.0);
+    }
+    Ok(())
+}
+
+/// Length of a packet in frames, from its duration in the track's time base.
+/// Without a time base the duration is assumed to already be in frames.
+fn packet_frames(dur: u64, time_base: Option<TimeBase>, sample_rate: u32) -> usize {
+    match time_base {
+        Some(tb) => {
+            let time = tb.calc_time(dur);
+            ((time.seconds as f64 + time.frac) * sample_rate as f64).round() as usize
+        }
+        None => dur as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Recover from a damaged 1152-frame stereo packet after two good frames.
+    fn recover(policy: DecodePolicy) -> (Result<(), String>, Vec<f32>, DecodeReport) {
+        let mut samples = vec![0.5; 4];
+        let mut report = DecodeReport::default();
+        let result =
+            recover_damaged_packet(policy, "bad frame", 1152, 2, &mut samples, &mut report);
+        (result, samples, report)
+    }
+
+    #[test]
+    fn strict_fails_on_a_damaged_packet() {
+        let (result, samples, report) = recover(DecodePolicy::Strict);
+        assert_eq!(result, Err("Damaged packet: bad frame".to_string()));
+        assert_eq!(samples, vec![0.5; 4]);
+        assert_eq!(report.damaged_packets, 0);
+    }
+
+    #[test]
+    fn lenient_drops_a_damaged_packet() {
+        let (result, samples, report) = recover(DecodePolicy::Lenient);
+        assert_eq!(result, Ok(()));
+        assert_eq!(samples, vec![0.5; 4]);
+        assert_eq!(report.damaged_packets, 1);
+    }
+
+    #[test]
+    fn fill_silence_keeps_the_packet_length() {
+        let (result, samples, report) = recover(DecodePolicy::FillSilence);
+        assert_eq!(result, Ok(()));
+        assert_eq!(samples.len(), 4 + 1152 * 2);
+        assert_eq!(&samples[..4], &[0.5; 4]);
+        assert!(samples[4..].iter().all(|s| *s == 0.0));
+        assert_eq!(report.damaged_packets, 1);
+    }
+
+    const ADPCM_BLOCK_ALIGN: usize = 1024;
+    /// Frames in one block of the IMA ADPCM fixture: a 4-byte preamble
+    /// sample, then two 4-bit samples per byte.
+    const ADPCM_BLOCK_FRAMES: usize = (ADPCM_BLOCK_ALIGN - 4) * 2 + 1;
+
+    /// Write a mono 8 kHz IMA ADPCM WAV of three blocks whose middle block
+    /// has an out-of-range step index, so the codec rejects just that packet.
+    /// Blocks this size are one packet each.
+    fn write_damaged_wav(name: &str) -> std::path::PathBuf {
+        let mut data = Vec::new();
+        for block in 0..3 {
+            data.extend_from_slice(&0i16.to_le_bytes());
+            data.push(if block == 1 { 0xff } else { 0 });
+            data.push(0);
+            data.resize(data.len() + ADPCM_BLOCK_ALIGN - 4, 0x17);
+        }
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&0x11u16.to_le_bytes()); // IMA ADPCM
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&8000u32.to_le_bytes());
+        let byte_rate = (8000 * ADPCM_BLOCK_ALIGN / ADPCM_BLOCK_FRAMES) as u32;
+        fmt.extend_from_slice(&byte_rate.to_le_bytes());
+        fmt.extend_from_slice(&(ADPCM_BLOCK_ALIGN as u16).to_le_bytes());
+        fmt.extend_from_slice(&4u16.to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&(ADPCM_BLOCK_FRAMES as u16).to_le_bytes());
+
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&((4 + 8 + fmt.len() + 8 + data.len()) as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&fmt);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+
+        let path = std::env::temp_dir().join(format!("{name}-{}.wav", std::process::id()));
+        std::fs::write(&path, wav).unwrap();
+        path
+    }
+
+    fn decode_damaged(policy: DecodePolicy) -> Result<(AudioData, DecodeReport), String> {
+        let path = write_damaged_wav(&format!("damaged-{policy:?}"));
+        let result = decode_file(&path, policy, &AtomicBool::new(false), |_| {});
+        std::fs::remove_file(&path).unwrap();
+        result.map(|(audio, _, report)| (audio, report))
+    }
+
+    #[test]
+    fn strict_fails_to_load_a_damaged_file() {
+        let err = decode_damaged(DecodePolicy::Strict).unwrap_err();
+        assert!(err.starts_with("Damaged packet"), "{err}");
+    }
+
+    #[test]
+    fn lenient_loads_a_damaged_file_without_the_bad_block() {
+        let (audio, report) = decode_damaged(DecodePolicy::Lenient).unwrap();
+        assert_eq!(audio.num_frames(), 2 * ADPCM_BLOCK_FRAMES);
+        assert_eq!(report.damaged_packets, 1);
+    }
+
+    #[test]
+    fn fill_silence_loads_a_damaged_file_at_full_length() {
+        let (audio, report) = decode_damaged(DecodePolicy::FillSilence).unwrap();
+        assert_eq!(audio.num_frames(), 3 * ADPCM_BLOCK_FRAMES);
+        let bad_block = &audio.samples[ADPCM_BLOCK_FRAMES..2 * ADPCM_BLOCK_FRAMES];
+        assert!(bad_block.iter().all(|s| *s == 0.0));
+        assert_eq!(report.damaged_packets, 1);
+    }
+
+    #[test]
+    fn packet_length_follows_the_time_base() {
+        assert_eq!(packet_frames(1152, None, 48000), 1152);
+        assert_eq!(
+            packet_frames(1152, Some(TimeBase::new(1, 48000)), 48000),
+            1152
+        );
+        // 24 ms at 48 kHz, in a millisecond time base
+        assert_eq!(packet_frames(24, Some(TimeBase::new(1, 1000)), 48000), 1152);
+    }
+
+    #[test]
+    fn memory_estimate_counts_samples_and_mono_mixdown() {
+        // A minute of 44.1 kHz stereo: two channels plus the mono copy, as f32
+        let frames = 60 * 44100;
+        assert_eq!(estimate_memory_bytes(frames, 2), frames * 3 * 4);
+        assert_eq!(estimate_memory_bytes(frames, 1), frames * 2 * 4);
+        assert_eq!(estimate_memory_bytes(frames, 6), frames * 7 * 4);
+        assert_eq!(estimate_memory_bytes(0, 2), 0);
+        // Long files don't overflow: ten hours of 8-channel 192 kHz
+        let frames = 10 * 3600 * 192_000;
+        assert_eq!(estimate_memory_bytes(frames, 8), frames * 9 * 4);
+    }
+
+    #[test]
+    fn replay_gain_tags_become_a_linear_gain() {
+        let info = AudioInfo {
+            track_gain_db: parse_replay_gain("-6.02 dB"),
+            album_gain_db: parse_replay_gain("+2.5 dB"),
+            ..AudioInfo::default()
+        };
+        let gain = info.replay_gain().unwrap();
+        assert!((gain - 0.5).abs() < 1e-3, "{gain}");
+
+        // Without a track gain the album gain is used
+        let album = AudioInfo {
+            album_gain_db: parse_replay_gain("6.02"),
+            ..AudioInfo::default()
+        };
+        let gain = album.replay_gain().unwrap();
+        assert!((gain - 2.0).abs() < 1e-3, "{gain}");
+
+        assert_eq!(parse_replay_gain("loud"), None);
+        assert_eq!(AudioInfo::default().replay_gain(), None);
+    }
 }
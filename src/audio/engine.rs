@@ -1,15 +1,102 @@
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam_channel::{Receiver, Sender};
 
+use super::analysis;
+use super::filter::{Equalizer, SweepFilter};
+use super::metronome::{CountIn, Metronome};
+use super::mix;
 use super::stretcher::Stretcher;
-use super::types::{AudioCommand, AudioData, AudioEvent};
+use super::types::{
+    AfterLoops, AudioCommand, AudioData, AudioEvent, ChannelMode, LoopMode, TempoRamp,
+};
 
 /// Size of chunks fed into SoundTouch at a time.
 const CHUNK_SIZE: usize = 1024;
+/// Frames the receive buffer holds, sized on load. Longer callbacks receive
+/// from SoundTouch in several passes, so the callback never allocates.
+const MAX_EXPECTED_CALLBACK_FRAMES: usize = 8192;
 /// How often (in output frames) to send position updates.
 const POSITION_UPDATE_INTERVAL: usize = 2048;
+/// Fraction of the buffer deadline above which a callback counts as overloaded.
+const LOAD_WARN_RATIO: f32 = 0.8;
+/// Consecutive overloaded callbacks before a `HighCpuLoad` event is sent.
+const LOAD_WARN_STRIKES: usize = 32;
+/// Gap between callbacks, in buffer lengths, beyond which the device is
+/// taken to have run out of audio.
+const UNDERRUN_GAP_RATIO: f32 = 2.0;
+
+/// How far (in seconds) a loop boundary may move to land on a zero crossing.
+const ZERO_CROSSING_SEARCH_SECS: f64 = 0.01;
+
+/// Time constant for gain changes, so dragging the volume slider doesn't zipper.
+const GAIN_SMOOTHING_SECS: f32 = 0.005;
+
+/// Length of the fade when playback starts, pauses or stops, so it doesn't click.
+const TRANSPORT_FADE_SECS: f32 = 0.01;
+
+/// Length of the snippet played for each scrub position, in seconds.
+const SCRUB_GRAIN_SECS: f64 = 0.06;
+
+/// A windowed snippet of the file played while scrubbing.
+struct ScrubGrain {
+    /// First file frame of the snippet.
+    start: f64,
+    /// Output frames played so far.
+    played: usize,
+}
+
+/// What a fade-out on pause or stop leads up to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FadeOut {
+    Pause,
+    Stop,
+}
+
+/// Lock-free counters bumped on the audio thread. The UI thread drains
+/// them for logging, so the callback itself never logs.
+pub struct EngineCounters {
+    callbacks: AtomicU64,
+    commands: AtomicU64,
+    overloaded_callbacks: AtomicU64,
+    underruns: AtomicU64,
+}
+
+/// Counter values accumulated since the last `EngineCounters::drain`.
+#[derive(Debug, Clone, Copy)]
+pub struct CounterSnapshot {
+    pub callbacks: u64,
+    pub commands: u64,
+    pub overloaded_callbacks: u64,
+    pub underruns: u64,
+}
+
+impl EngineCounters {
+    const fn new() -> Self {
+        Self {
+            callbacks: AtomicU64::new(0),
+            commands: AtomicU64::new(0),
+            overloaded_callbacks: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+        }
+    }
+
+    /// Take the current counts, resetting them to zero.
+    pub fn drain(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            callbacks: self.callbacks.swap(0, Ordering::Relaxed),
+            commands: self.commands.swap(0, Ordering::Relaxed),
+            overloaded_callbacks: self.overloaded_callbacks.swap(0, Ordering::Relaxed),
+            underruns: self.underruns.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+pub static COUNTERS: EngineCounters = EngineCounters::new();
 
 #[allow(dead_code)]
 struct EngineState {
@@ -17,23 +104,140 @@ struct EngineState {
     position: usize, // current frame position
     playing: bool,
     tempo: f32,
+    /// Tempo change applied each time the loop comes round.
+    tempo_ramp: Option<TempoRamp>,
+    /// Pitch shift in semitones.
+    pitch: f32,
+    /// Target output gain (linear).
+    gain: f32,
+    /// Silence the output while playback (and the playhead) carries on.
+    muted: bool,
+    /// Gain currently applied, moving towards `gain` a little every frame.
+    current_gain: f32,
+    /// Per-frame step factor for `current_gain`.
+    gain_smoothing: f32,
+    /// Fade applied on starting and stopping (0.0 to 1.0), on top of `current_gain`.
+    transport_gain: f32,
+    /// Per-frame change of `transport_gain`.
+    transport_step: f32,
+    /// Playback is fading out, to pause or stop once silent.
+    fading_out: Option<FadeOut>,
+    /// A seek that arrived during the fade-out, applied once it is over.
+    seek_after_fade: Option<f64>,
+    /// Scrub snippet playing while paused.
+    scrub: Option<ScrubGrain>,
+    /// Start frame of the snippet to play once the current one ends, so
+    /// snippets follow on from each other rather than cutting in.
+    scrub_next: Option<f64>,
     loop_region: Option<(usize, usize)>, // frame range
+    loop_mode: LoopMode,
+    /// Passes through the loop before `after_loops` applies; `None` loops forever.
+    loop_repeats: Option<u32>,
+    after_loops: AfterLoops,
+    /// Passes left in the current run of repeats.
+    loops_remaining: Option<u32>,
+    /// The repeats are done and playback carries on past the loop.
+    loop_suspended: bool,
+    /// Move loop boundaries to nearby zero crossings so the wrap doesn't click.
+    snap_loop_to_zero: bool,
+    /// Output levels gathered since the last report, for the level meter.
+    meter_peak: f32,
+    meter_sum_squares: f64,
+    meter_frames: usize,
+    /// Feeding the loop backwards (ping-pong mode only).
+    reversed: bool,
+    /// Shuttle speed while the shuttle control is held; see `AudioCommand::SetShuttle`.
+    shuttle: f32,
+    /// The loop end was reached and SoundTouch is playing out its last
+    /// frames before pausing (pause-at-end mode only).
+    draining_loop_end: bool,
+    /// Scratch space for frames fed in reverse order, sized on load.
+    reverse_buf: Vec<f32>,
+    /// Scratch space for crossfaded frames at the loop seam, sized on load.
+    fade_buf: Vec<f32>,
+    /// Length of the crossfade at the loop seam, in seconds.
+    loop_crossfade: f32,
+    /// Scratch space for frames received from SoundTouch, sized on load.
+    recv_buf: Vec<f32>,
     stretcher: Option<Stretcher>,
+    metronome: Metronome,
+    /// Bars of clicks played before a loop starts or restarts; 0 turns it off.
+    count_in_bars: u32,
+    /// Count-in being played; the audio waits until it is over.
+    count_in: Option<CountIn>,
+    /// Device sample rate; the stretcher resamples the file to it.
     output_sample_rate: u32,
+    output_channels: u16,
+    channel_mode: ChannelMode,
+    /// Fraction of the mid (L+R) signal subtracted from stereo sources.
+    vocal_reduction: f32,
+    /// Side-to-mid ratio for stereo sources (see `mix::set_stereo_width`).
+    stereo_width: f32,
+    /// Three-band EQ on the stretched audio.
+    equalizer: Equalizer,
+    /// High-pass and low-pass pair after the EQ.
+    sweep_filter: SweepFilter,
+    /// File-to-device channel mix, built on load (see `mix::channel_matrix`).
+    channel_matrix: Vec<f32>,
     frames_since_update: usize,
+    load_strikes: usize,
+    load_warned: bool,
+    /// Start of the previous callback while playing, for spotting gaps.
+    last_callback: Option<Instant>,
 }
 
 impl EngineState {
-    fn new(output_sample_rate: u32) -> Self {
+    fn new(output_sample_rate: u32, output_channels: u16) -> Self {
         Self {
             audio: None,
             position: 0,
             playing: false,
             tempo: 1.0,
+            tempo_ramp: None,
+            pitch: 0.0,
+            gain: 1.0,
+            muted: false,
+            current_gain: 1.0,
+            gain_smoothing: 1.0 - (-1.0 / (GAIN_SMOOTHING_SECS * output_sample_rate as f32)).exp(),
+            transport_gain: 0.0,
+            transport_step: 1.0 / (TRANSPORT_FADE_SECS * output_sample_rate as f32),
+            fading_out: None,
+            seek_after_fade: None,
+            scrub: None,
+            scrub_next: None,
             loop_region: None,
+            loop_mode: LoopMode::Restart,
+            snap_loop_to_zero: true,
+            loop_repeats: None,
+            after_loops: AfterLoops::default(),
+            loops_remaining: None,
+            loop_suspended: false,
+            meter_peak: 0.0,
+            meter_sum_squares: 0.0,
+            meter_frames: 0,
+            reversed: false,
+            shuttle: 0.0,
+            draining_loop_end: false,
+            reverse_buf: Vec::new(),
+            fade_buf: Vec::new(),
+            loop_crossfade: 0.0,
+            recv_buf: Vec::new(),
             stretcher: None,
+            metronome: Metronome::new(output_sample_rate),
+            count_in_bars: 0,
+            count_in: None,
             output_sample_rate,
+            output_channels,
+            channel_mode: ChannelMode::default(),
+            vocal_reduction: 0.0,
+            stereo_width: 1.0,
+            equalizer: Equalizer::new(output_sample_rate),
+            sweep_filter: SweepFilter::new(output_sample_rate),
+            channel_matrix: Vec::new(),
             frames_since_update: 0,
+            load_strikes: 0,
+            load_warned: false,
+            last_callback: None,
         }
     }
 
@@ -45,62 +249,386 @@ impl EngineState {
                 self.audio = Some(data);
                 self.position = 0;
                 self.playing = false;
+                self.fading_out = None;
+                self.seek_after_fade = None;
+                self.scrub = None;
+                self.scrub_next = None;
                 self.loop_region = None;
-                let mut stretcher = Stretcher::new(sr, ch);
-                stretcher.set_tempo(self.tempo);
+                self.reversed = false;
+                self.draining_loop_end = false;
+                self.restart_loop_count();
+                self.count_in = None;
+                self.metronome.clear_beats();
+                self.reverse_buf = vec![0.0; CHUNK_SIZE * ch as usize];
+                self.fade_buf = vec![0.0; CHUNK_SIZE * ch as usize];
+                self.recv_buf = vec![0.0; MAX_EXPECTED_CALLBACK_FRAMES * ch as usize];
+                self.equalizer.set_channels(ch as usize);
+                self.sweep_filter.set_channels(ch as usize);
+                self.channel_matrix = mix::channel_matrix(
+                    ch as usize,
+                    self.output_channels as usize,
+                    self.channel_mode,
+                );
+                self.load_warned = false;
+                let mut stretcher = Stretcher::new(sr, ch, self.output_sample_rate);
+                stretcher.set_tempo(shuttled_tempo(self.tempo, self.shuttle));
+                stretcher.set_pitch(self.pitch);
                 self.stretcher = Some(stretcher);
             }
             AudioCommand::Play => {
-                if self.audio.is_some() {
+                match self.fading_out {
+                    // Fade back up from wherever the fade-out had got to
+                    Some(FadeOut::Pause) => self.fading_out = None,
+                    // A stop rewinds, so it completes before playing again
+                    Some(FadeOut::Stop) => self.finish_fade_out(event_tx),
+                    None => {}
+                }
+                if self.audio.is_some() && !self.playing {
+                    // Paused at the loop end: hear the loop again from the top
+                    if let (LoopMode::PauseAtEnd, Some((start, end))) =
+                        (self.loop_mode, self.loop_region)
+                    {
+                        if self.position >= end {
+                            self.position = start;
+                            self.restart_loop_count();
+                        }
+                    }
                     self.playing = true;
+                    self.scrub = None;
+                    self.scrub_next = None;
+                    self.transport_gain = 0.0;
+                    self.begin_count_in();
                 }
             }
             AudioCommand::Pause => {
-                self.playing = false;
+                if self.count_in.take().is_some() {
+                    // Only clicks are playing, so there is nothing to fade.
+                    // Playing again starts a fresh count-in.
+                    self.playing = false;
+                } else if self.playing {
+                    self.fading_out.get_or_insert(FadeOut::Pause);
+                }
             }
             AudioCommand::Stop => {
-                self.playing = false;
-                self.position = 0;
-                if let Some(s) = &mut self.stretcher {
-                    s.clear();
+                if self.playing && self.count_in.is_none() {
+                    self.fading_out = Some(FadeOut::Stop);
+                } else {
+                    self.stop(event_tx);
                 }
-                let _ = event_tx.send(AudioEvent::PositionChanged(0.0));
             }
-            AudioCommand::Seek(time) => {
-                if let Some(audio) = &self.audio {
-                    let frame = (time * audio.sample_rate as f64) as usize;
-                    self.position = frame.min(audio.num_frames());
-                    if let Some(s) = &mut self.stretcher {
-                        s.clear();
+            AudioCommand::Scrub(time) => {
+                // Playback is heard already, so scrubbing only sounds while paused
+                if let (Some(audio), false) = (&self.audio, self.playing) {
+                    let sr = audio.sample_rate as f64;
+                    let start = (time * sr - SCRUB_GRAIN_SECS * sr / 2.0).max(0.0);
+                    if self.scrub.is_some() {
+                        self.scrub_next = Some(start);
+                    } else {
+                        self.scrub = Some(ScrubGrain { start, played: 0 });
                     }
-                    let pos_secs = self.position as f64 / audio.sample_rate as f64;
-                    let _ = event_tx.send(AudioEvent::PositionChanged(pos_secs));
+                }
+            }
+            AudioCommand::Seek(time) => {
+                if self.fading_out.is_some() {
+                    self.seek_after_fade = Some(time);
+                } else {
+                    self.seek(time, event_tx);
                 }
             }
             AudioCommand::SetTempo(tempo) => {
                 self.tempo = tempo;
                 if let Some(s) = &mut self.stretcher {
-                    s.set_tempo(tempo);
+                    s.set_tempo(shuttled_tempo(tempo, self.shuttle));
+                }
+            }
+            AudioCommand::SetShuttle(speed) => {
+                let turned = (speed < 0.0) != (self.shuttle < 0.0);
+                self.shuttle = speed;
+                if let (Some(s), Some(audio)) = (&mut self.stretcher, &self.audio) {
+                    s.set_tempo(shuttled_tempo(self.tempo, speed));
+                    if turned {
+                        // Drop audio queued in the old direction and carry
+                        // on from what was last heard
+                        let latency = s.latency_frames();
+                        self.position = if speed < 0.0 {
+                            self.position.saturating_sub(latency)
+                        } else {
+                            (self.position + latency).min(audio.num_frames())
+                        };
+                        s.clear();
+                    }
+                }
+            }
+            AudioCommand::SetBeats(beats) => {
+                if let Some(audio) = &self.audio {
+                    self.metronome.set_beats(&beats, audio.sample_rate);
+                }
+            }
+            AudioCommand::SetMetronome {
+                enabled,
+                gain,
+                beats_per_bar,
+            } => {
+                self.metronome.enabled = enabled;
+                self.metronome.gain = gain;
+                self.metronome.beats_per_bar = beats_per_bar;
+            }
+            AudioCommand::SetCountIn(bars) => {
+                self.count_in_bars = bars;
+            }
+            AudioCommand::SetTempoRamp(ramp) => {
+                self.tempo_ramp = ramp;
+            }
+            AudioCommand::SetPitch(pitch) => {
+                self.pitch = pitch;
+                if let Some(s) = &mut self.stretcher {
+                    s.set_pitch(pitch);
                 }
             }
+            AudioCommand::SetGain(gain) => {
+                self.gain = gain;
+            }
+            AudioCommand::SetMuted(muted) => {
+                self.muted = muted;
+            }
             AudioCommand::SetLoopRegion(region) => {
+                self.reversed = false;
+                self.draining_loop_end = false;
+                self.restart_loop_count();
                 if let Some(audio) = &self.audio {
-                    self.loop_region = region.map(|(start, end)| {
-                        let sr = audio.sample_rate as f64;
-                        let start_frame = (start * sr) as usize;
-                        let end_frame = (end * sr) as usize;
-                        (start_frame, end_frame.min(audio.num_frames()))
-                    });
+                    // An empty region would wrap forever without producing audio
+                    self.loop_region = region
+                        .map(|(start, end)| {
+                            let sr = audio.sample_rate as f64;
+                            let mut start_frame = (start * sr) as usize;
+                            let mut end_frame = ((end * sr) as usize).min(audio.num_frames());
+                            if self.snap_loop_to_zero {
+                                let reach = (ZERO_CROSSING_SEARCH_SECS * sr) as usize;
+                                let snap = |frame| {
+                                    analysis::nearest_zero_crossing(
+                                        &audio.samples,
+                                        audio.channels,
+                                        frame,
+                                        reach,
+                                    )
+                                };
+                                start_frame = snap(start_frame);
+                                end_frame = snap(end_frame);
+                            }
+                            (start_frame, end_frame)
+                        })
+                        .filter(|(start, end)| end > start);
                 }
             }
-            AudioCommand::Shutdown => {}
+            AudioCommand::SetLoopRepeats(repeats, after) => {
+                self.loop_repeats = repeats.filter(|n| *n > 0);
+                self.after_loops = after;
+                self.restart_loop_count();
+            }
+            AudioCommand::SetLoopCrossfade(secs) => {
+                self.loop_crossfade = secs.max(0.0);
+            }
+            AudioCommand::SetLoopSnap(snap) => {
+                self.snap_loop_to_zero = snap;
+            }
+            AudioCommand::SetLoopMode(mode) => {
+                self.loop_mode = mode;
+                self.reversed = false;
+                self.draining_loop_end = false;
+            }
+            AudioCommand::SetChannelMode(mode) => {
+                self.channel_mode = mode;
+                if let Some(audio) = &self.audio {
+                    self.channel_matrix = mix::channel_matrix(
+                        audio.channels as usize,
+                        self.output_channels as usize,
+                        mode,
+                    );
+                }
+            }
+            AudioCommand::SetVocalReduction(amount) => {
+                self.vocal_reduction = amount.clamp(0.0, 1.0);
+            }
+            AudioCommand::SetStereoWidth(width) => {
+                self.stereo_width = width.max(0.0);
+            }
+            AudioCommand::SetEq {
+                low_gain,
+                mid_gain,
+                high_gain,
+            } => {
+                self.equalizer.set_gains(low_gain, mid_gain, high_gain);
+            }
+            AudioCommand::SetFilter { hp_hz, lp_hz } => {
+                self.sweep_filter.set_cutoffs(hp_hz, lp_hz);
+            }
+            AudioCommand::Shutdown => {
+                self.playing = false;
+            }
+        }
+    }
+
+    fn stop(&mut self, event_tx: &Sender<AudioEvent>) {
+        self.playing = false;
+        self.count_in = None;
+        self.position = 0;
+        self.reversed = false;
+        self.draining_loop_end = false;
+        self.restart_loop_count();
+        if let Some(s) = &mut self.stretcher {
+            s.clear();
+        }
+        let _ = event_tx.send(AudioEvent::PositionChanged(0.0));
+    }
+
+    fn seek(&mut self, time: f64, event_tx: &Sender<AudioEvent>) {
+        if let Some(audio) = &self.audio {
+            // Rounded so a time computed from a frame number lands on that frame
+            let frame = (time * audio.sample_rate as f64).round() as usize;
+            self.position = frame.min(audio.num_frames());
+            self.reversed = false;
+            self.draining_loop_end = false;
+            if let Some(s) = &mut self.stretcher {
+                s.clear();
+            }
+            let pos_secs = self.position as f64 / audio.sample_rate as f64;
+            self.restart_loop_count();
+            let _ = event_tx.send(AudioEvent::PositionChanged(pos_secs));
+        }
+    }
+
+    /// Carry out the pause or stop a fade-out was leading up to, then any seek
+    /// that was held back meanwhile.
+    fn finish_fade_out(&mut self, event_tx: &Sender<AudioEvent>) {
+        match self.fading_out.take() {
+            Some(FadeOut::Pause) => self.playing = false,
+            Some(FadeOut::Stop) => self.stop(event_tx),
+            None => return,
+        }
+        if let Some(time) = self.seek_after_fade.take() {
+            self.seek(time, event_tx);
+        }
+    }
+
+    /// Write any scrub snippet into the start of the silent `output`. Snippets
+    /// skip the stretcher: they're played as recorded, Hann-windowed so they
+    /// fade in and out.
+    fn render_scrub(&mut self, output: &mut [f32], channels: u16) {
+        let Some(audio) = &self.audio else {
+            return;
+        };
+        let in_channels = audio.channels as usize;
+        let step = audio.sample_rate as f64 / self.output_sample_rate as f64;
+        let len = (SCRUB_GRAIN_SECS * self.output_sample_rate as f64) as usize;
+        let gain = if self.muted { 0.0 } else { self.gain };
+        for frame in output.chunks_exact_mut(channels as usize) {
+            if self.scrub.is_none() {
+                self.scrub = self
+                    .scrub_next
+                    .take()
+                    .map(|start| ScrubGrain { start, played: 0 });
+            }
+            let Some(grain) = &mut self.scrub else {
+                break;
+            };
+            let source = (grain.start + grain.played as f64 * step) as usize;
+            if source < audio.num_frames() {
+                let window = 0.5 - 0.5 * (TAU * grain.played as f32 / len as f32).cos();
+                mix::mix_frame(
+                    &self.channel_matrix,
+                    &audio.samples[source * in_channels..(source + 1) * in_channels],
+                    frame,
+                    gain * window,
+                );
+            }
+            grain.played += 1;
+            if grain.played >= len {
+                self.scrub = None;
+            }
+        }
+    }
+
+    /// Start counting loop passes afresh and re-enable a suspended loop.
+    fn restart_loop_count(&mut self) {
+        self.loops_remaining = self.loop_repeats;
+        self.loop_suspended = false;
+    }
+
+    /// Count in before an active loop, rewinding to the loop start if playback
+    /// is outside it. Seeking during the count-in only moves where audio resumes.
+    fn begin_count_in(&mut self) {
+        let (Some(audio), Some(stretcher)) = (&self.audio, &mut self.stretcher) else {
+            return;
+        };
+        let Some((start, end)) = self.loop_region.filter(|_| !self.loop_suspended) else {
+            return;
+        };
+        if self.count_in_bars == 0 || self.reversed {
+            return;
+        }
+        // Audio still inside SoundTouch would play before the clicks, so drop
+        // it and pick up from what was last heard
+        let heard = self.position.saturating_sub(stretcher.latency_frames());
+        self.position = if (start..end).contains(&heard) {
+            heard
+        } else {
+            start
+        };
+        stretcher.clear();
+        self.count_in = Some(self.metronome.count_in(
+            self.count_in_bars,
+            audio.sample_rate,
+            stretcher.input_frames_per_output_frame(),
+        ));
+    }
+
+    /// Track how long a callback took relative to its real-time deadline and
+    /// warn once when it stays close to the limit. Runs on the audio thread,
+    /// so it only touches plain fields and a non-blocking send.
+    fn record_load(&mut self, started: Instant, frames: usize, event_tx: &Sender<AudioEvent>) {
+        if frames == 0 || !self.playing {
+            self.load_strikes = 0;
+            self.last_callback = None;
+            return;
+        }
+        let deadline = frames as f32 / self.output_sample_rate as f32;
+        let ratio = started.elapsed().as_secs_f32() / deadline;
+
+        // Running past the deadline, or the device waiting too long between
+        // callbacks, leaves it without audio to play
+        let gap = self
+            .last_callback
+            .map(|last| started.duration_since(last).as_secs_f32() / deadline);
+        self.last_callback = Some(started);
+        if ratio >= 1.0 || gap.is_some_and(|gap| gap > UNDERRUN_GAP_RATIO) {
+            COUNTERS.underruns.fetch_add(1, Ordering::Relaxed);
+            let _ = event_tx.try_send(AudioEvent::Underrun);
+        }
+
+        if ratio < LOAD_WARN_RATIO {
+            self.load_strikes = 0;
+            return;
+        }
+
+        self.load_strikes += 1;
+        COUNTERS
+            .overloaded_callbacks
+            .fetch_add(1, Ordering::Relaxed);
+        if self.load_strikes >= LOAD_WARN_STRIKES && !self.load_warned {
+            self.load_warned = true;
+            let _ = event_tx.try_send(AudioEvent::HighCpuLoad(ratio));
         }
     }
 
     /// Fill the output buffer with processed audio.
     fn fill_buffer(&mut self, output: &mut [f32], channels: u16, event_tx: &Sender<AudioEvent>) {
+        // Also finishes a fade cut short by playback ending on its own
+        if self.fading_out.is_some() && (self.transport_gain <= 0.0 || !self.playing) {
+            self.finish_fade_out(event_tx);
+        }
         if !self.playing {
             output.fill(0.0);
+            self.render_scrub(output, channels);
             return;
         }
 
@@ -125,67 +653,261 @@ impl EngineState {
         let total_frames = audio.num_frames();
         let mut out_pos = 0;
         let out_frames = output.len() / out_channels;
-
-        // Temporary buffer for receiving from SoundTouch
-        let mut recv_buf = vec![0.0f32; out_frames * audio_channels];
+        let recv_frames = self.recv_buf.len() / audio_channels;
 
         while out_pos < out_frames {
             // Try to receive from SoundTouch first
-            let needed = out_frames - out_pos;
-            let recv_slice = &mut recv_buf[..needed * audio_channels];
+            let needed = (out_frames - out_pos).min(recv_frames);
+            let recv_slice = &mut self.recv_buf[..needed * audio_channels];
             let got_samples = stretcher.receive_samples(recv_slice);
             let got_frames = got_samples / audio_channels;
 
             if got_frames > 0 {
-                // Write received frames to output, handling channel conversion
+                // Write received frames to output, handling channel conversion and gain
+                // Muting ramps to zero like any other gain change, so it doesn't click
+                let target_gain = if self.muted { 0.0 } else { self.gain };
+                if audio_channels == 2 && self.vocal_reduction > 0.0 {
+                    mix::reduce_centre(&mut recv_slice[..got_samples], self.vocal_reduction);
+                }
+                if audio_channels == 2 && self.stereo_width != 1.0 {
+                    mix::set_stereo_width(&mut recv_slice[..got_samples], self.stereo_width);
+                }
+                self.equalizer.process(&mut recv_slice[..got_samples]);
+                self.sweep_filter.process(&mut recv_slice[..got_samples]);
                 for f in 0..got_frames {
-                    for c in 0..out_channels {
-                        let src_c = c % audio_channels;
-                        output[(out_pos + f) * out_channels + c] =
-                            recv_slice[f * audio_channels + src_c];
-                    }
+                    self.current_gain += (target_gain - self.current_gain) * self.gain_smoothing;
+                    self.transport_gain = if self.fading_out.is_some() {
+                        (self.transport_gain - self.transport_step).max(0.0)
+                    } else {
+                        (self.transport_gain + self.transport_step).min(1.0)
+                    };
+                    mix::mix_frame(
+                        &self.channel_matrix,
+                        &recv_slice[f * audio_channels..(f + 1) * audio_channels],
+                        &mut output[(out_pos + f) * out_channels..(out_pos + f + 1) * out_channels],
+                        self.current_gain * self.transport_gain,
+                    );
                 }
+                if self.metronome.enabled && !self.reversed && self.shuttle >= 0.0 {
+                    // Clicks follow what is heard, so they track the stretched output
+                    let step = stretcher.input_frames_per_output_frame();
+                    let heard_end = self.position as f64 - stretcher.latency_frames() as f64;
+                    self.metronome.render(
+                        &mut output[out_pos * out_channels..(out_pos + got_frames) * out_channels],
+                        out_channels,
+                        heard_end - got_frames as f64 * step,
+                        step,
+                    );
+                }
+                // Metered as heard, after the gain and any clicks
+                for &s in &output[out_pos * out_channels..(out_pos + got_frames) * out_channels] {
+                    self.meter_peak = self.meter_peak.max(s.abs());
+                    self.meter_sum_squares += (s * s) as f64;
+                }
+                self.meter_frames += got_frames;
                 out_pos += got_frames;
                 self.frames_since_update += got_frames;
 
+                if self.fading_out.is_some() && self.transport_gain <= 0.0 {
+                    // Silent now; the pause or stop takes effect next callback
+                    output[out_pos * out_channels..].fill(0.0);
+                    return;
+                }
+
                 if self.frames_since_update >= POSITION_UPDATE_INTERVAL {
                     self.frames_since_update = 0;
-                    let pos_secs = self.position as f64 / audio.sample_rate as f64;
+                    // Report what is being heard, not how far SoundTouch has been fed
+                    let latency = stretcher.latency_frames();
+                    let heard = if self.reversed || self.shuttle < 0.0 {
+                        self.position + latency
+                    } else {
+                        self.position.saturating_sub(latency)
+                    };
+                    let pos_secs = heard as f64 / audio.sample_rate as f64;
                     let _ = event_tx.send(AudioEvent::PositionChanged(pos_secs));
+                    let _ = event_tx.try_send(AudioEvent::Levels(
+                        self.meter_peak,
+                        self.meter_sum_squares,
+                        self.meter_frames,
+                    ));
+                    self.meter_peak = 0.0;
+                    self.meter_sum_squares = 0.0;
+                    self.meter_frames = 0;
                 }
                 continue;
             }
 
-            // Need to feed more samples to SoundTouch
-            if self.position >= total_frames {
-                // Check for loop
-                if let Some((start, _)) = self.loop_region {
-                    self.position = start;
+            // SoundTouch has run dry, so a pending count-in can play
+            if let Some(count_in) = &mut self.count_in {
+                let used = self.metronome.render_count_in(
+                    &mut output[out_pos * out_channels..],
+                    out_channels,
+                    count_in,
+                );
+                out_pos += used;
+                if out_pos < out_frames {
+                    self.count_in = None;
+                    // Drop the silence a flush pads the tail with
                     stretcher.clear();
-                    continue;
-                } else {
-                    // Playback finished
-                    self.playing = false;
-                    let _ = event_tx.send(AudioEvent::PlaybackFinished);
-                    // Fill rest with silence
-                    for i in out_pos * out_channels..output.len() {
-                        output[i] = 0.0;
+                }
+                continue;
+            }
+
+            // Need to feed more samples to SoundTouch
+            let loop_region = if self.loop_suspended {
+                None
+            } else {
+                self.loop_region
+            };
+            let rewinding = self.shuttle < 0.0;
+            if self.reversed || rewinding {
+                // Ping-pong walks back towards the loop start, and a shuttle
+                // pushed left towards the file start, feeding frames in reverse
+                let start = match (rewinding, loop_region) {
+                    (true, _) => 0,
+                    (false, Some((start, _))) => start,
+                    // The loop went away on the way back
+                    (false, None) => {
+                        self.reversed = false;
+                        continue;
                     }
-                    return;
+                };
+                if self.position <= start {
+                    if rewinding {
+                        // Rewound to the start of the file; wait there
+                        output[out_pos * out_channels..].fill(0.0);
+                        return;
+                    }
+                    self.reversed = false;
+                    continue;
+                }
+                let feed_frames = CHUNK_SIZE.min(self.position - start);
+                let from = self.position - feed_frames;
+                for (i, frame) in audio.samples
+                    [from * audio_channels..self.position * audio_channels]
+                    .chunks_exact(audio_channels)
+                    .rev()
+                    .enumerate()
+                {
+                    self.reverse_buf[i * audio_channels..(i + 1) * audio_channels]
+                        .copy_from_slice(frame);
                 }
+                stretcher.put_samples(&self.reverse_buf[..feed_frames * audio_channels]);
+                self.position = from;
+                continue;
+            }
+
+            // A loop ends at or before the end of the file, so it's handled below
+            if self.position >= total_frames && loop_region.is_none() {
+                // Playback finished
+                self.playing = false;
+                let _ = event_tx.send(AudioEvent::PlaybackFinished);
+                // Fill rest with silence
+                output[out_pos * out_channels..].fill(0.0);
+                return;
             }
 
             // Determine how many frames to feed
             let mut feed_frames = CHUNK_SIZE.min(total_frames - self.position);
 
             // Respect loop end boundary
-            if let Some((start, end)) = self.loop_region {
-                if self.position >= end {
-                    self.position = start;
+            if let Some((start, end)) = loop_region {
+                // Restart loops blend their last frames into the audio just before
+                // the start, so the jump back lands in the middle of a continuous signal.
+                // The last pass doesn't jump back, and a count-in puts a gap in
+                // the audio, so neither is blended.
+                let last_pass = self.loops_remaining == Some(1);
+                let fade =
+                    if self.loop_mode == LoopMode::Restart && !last_pass && self.count_in_bars == 0
+                    {
+                        ((self.loop_crossfade * audio.sample_rate as f32) as usize)
+                            .min(start)
+                            .min(end - start)
+                    } else {
+                        0
+                    };
+                if self.position >= end && self.loop_mode == LoopMode::PauseAtEnd {
+                    if !self.draining_loop_end {
+                        // Let the last notes out of SoundTouch before pausing
+                        stretcher.flush();
+                        self.draining_loop_end = true;
+                        continue;
+                    }
+                    self.draining_loop_end = false;
+                    self.playing = false;
+                    self.position = end;
                     stretcher.clear();
+                    let end_secs = end as f64 / audio.sample_rate as f64;
+                    let _ = event_tx.try_send(AudioEvent::PositionChanged(end_secs));
+                    let _ = event_tx.try_send(AudioEvent::PausedAtLoopEnd);
+                    output[out_pos * out_channels..].fill(0.0);
+                    return;
+                }
+                if self.position >= end {
+                    if let Some(remaining) = &mut self.loops_remaining {
+                        *remaining -= 1;
+                        if *remaining == 0 {
+                            let _ = event_tx.try_send(AudioEvent::LoopRepeatsFinished);
+                            if self.after_loops == AfterLoops::Stop {
+                                self.playing = false;
+                                self.position = start;
+                                self.loops_remaining = self.loop_repeats;
+                                stretcher.clear();
+                                output[out_pos * out_channels..].fill(0.0);
+                                return;
+                            }
+                            self.loop_suspended = true;
+                            continue;
+                        }
+                    }
+                    // Ramps carry on from wherever the tempo is, so a manual
+                    // change mid-ramp is kept and ramped onwards from
+                    if let Some(ramp) = self.tempo_ramp {
+                        let next = ramp.next_tempo(self.tempo);
+                        if next != self.tempo {
+                            self.tempo = next;
+                            stretcher.set_tempo(shuttled_tempo(next, self.shuttle));
+                            let _ = event_tx.try_send(AudioEvent::TempoChanged(next));
+                        }
+                    }
+                    if self.loop_mode == LoopMode::PingPong {
+                        self.reversed = true;
+                    } else {
+                        self.position = start;
+                        if self.count_in_bars > 0 {
+                            // Let the end of the loop play out, then count in again
+                            stretcher.flush();
+                            self.count_in = Some(self.metronome.count_in(
+                                self.count_in_bars,
+                                audio.sample_rate,
+                                stretcher.input_frames_per_output_frame(),
+                            ));
+                        } else if fade == 0 {
+                            // Crossfaded audio already leads into the start; keep it
+                            stretcher.clear();
+                        }
+                    }
                     continue;
                 }
-                feed_frames = feed_frames.min(end - self.position);
+                let fade_start = end - fade;
+                if self.position >= fade_start {
+                    let n = feed_frames.min(end - self.position);
+                    let loop_len = end - start;
+                    for i in 0..n {
+                        let outgoing = self.position + i;
+                        let incoming = outgoing - loop_len;
+                        let t = (outgoing - fade_start) as f32 / fade as f32;
+                        for c in 0..audio_channels {
+                            self.fade_buf[i * audio_channels + c] =
+                                audio.samples[outgoing * audio_channels + c] * (1.0 - t)
+                                    + audio.samples[incoming * audio_channels + c] * t;
+                        }
+                    }
+                    stretcher.put_samples(&self.fade_buf[..n * audio_channels]);
+                    self.position += n;
+                    continue;
+                }
+                feed_frames = feed_frames.min(fade_start - self.position);
             }
 
             let start_sample = self.position * audio_channels;
@@ -196,15 +918,72 @@ impl EngineState {
     }
 }
 
+/// Tempo fed to SoundTouch: the set tempo, scaled while the shuttle is held.
+fn shuttled_tempo(tempo: f32, shuttle: f32) -> f32 {
+    if shuttle == 0.0 {
+        tempo
+    } else {
+        tempo * shuttle.abs()
+    }
+}
+
+/// List the names of all output devices exposed by the default host.
+///
+/// Nothing is filtered beyond "supports output", so loopback, monitor and
+/// virtual-cable devices show up alongside the physical outputs.
+pub fn output_device_names() -> Vec<String> {
+    let host = cpal::default_host();
+    let devices = match host.output_devices() {
+        Ok(d) => d,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to enumerate output devices");
+            return Vec::new();
+        }
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for device in devices {
+        if let Ok(desc) = device.description() {
+            let name = desc.name().to_string();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    tracing::debug!(?names, "enumerated output devices");
+    names
+}
+
+/// Find an output device by name, falling back to the host default.
+fn find_output_device(host: &cpal::Host, name: Option<&str>) -> Result<cpal::Device, String> {
+    if let Some(name) = name {
+        let devices = host
+            .output_devices()
+            .map_err(|e| format!("Failed to enumerate output devices: {e}"))?;
+        for device in devices {
+            if device.description().is_ok_and(|d| d.name() == name) {
+                return Ok(device);
+            }
+        }
+        return Err(format!("Output device not found: {name}"));
+    }
+    host.default_output_device()
+        .ok_or_else(|| "No audio output device found".to_string())
+}
+
 /// Spawn the audio engine thread and return command/event channels.
-pub fn spawn_engine() -> Result<(Sender<AudioCommand>, Receiver<AudioEvent>), String> {
+///
+/// Plays through the named output device, or the system default if `None`.
+/// Sending `AudioCommand::Shutdown` closes the stream.
+pub fn spawn_engine(
+    device_name: Option<String>,
+) -> Result<(Sender<AudioCommand>, Receiver<AudioEvent>), String> {
     let (cmd_tx, cmd_rx) = crossbeam_channel::bounded::<AudioCommand>(64);
     let (event_tx, event_rx) = crossbeam_channel::bounded::<AudioEvent>(256);
+    let (shutdown_tx, shutdown_rx) = crossbeam_channel::bounded::<()>(1);
 
     let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .ok_or("No audio output device found")?;
+    let device = find_output_device(&host, device_name.as_deref())?;
 
     let config = device
         .default_output_config()
@@ -213,23 +992,45 @@ pub fn spawn_engine() -> Result<(Sender<AudioCommand>, Receiver<AudioEvent>), St
     let sample_rate = config.sample_rate();
     let channels = config.channels();
     let sample_format = config.sample_format();
+    tracing::info!(
+        device = ?device_name,
+        sample_rate,
+        channels,
+        ?sample_format,
+        "starting audio engine"
+    );
 
-    let mut state = EngineState::new(sample_rate);
+    let mut state = EngineState::new(sample_rate, channels);
     let event_tx_clone = event_tx.clone();
+    let error_shutdown_tx = shutdown_tx.clone();
 
     let stream = match sample_format {
         cpal::SampleFormat::F32 => device
             .build_output_stream(
                 &config.into(),
                 move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    let started = Instant::now();
+                    COUNTERS.callbacks.fetch_add(1, Ordering::Relaxed);
                     // Process commands
                     while let Ok(cmd) = cmd_rx.try_recv() {
+                        COUNTERS.commands.fetch_add(1, Ordering::Relaxed);
+                        if matches!(cmd, AudioCommand::Shutdown) {
+                            let _ = shutdown_tx.try_send(());
+                        }
                         state.handle_command(cmd, &event_tx_clone);
                     }
                     state.fill_buffer(data, channels, &event_tx_clone);
+                    state.record_load(started, data.len() / channels as usize, &event_tx_clone);
                 },
-                |err| {
-                    eprintln!("Audio stream error: {err}");
+                move |err| {
+                    tracing::error!(error = %err, "audio stream error");
+                    let _ =
+                        event_tx.try_send(AudioEvent::Error(format!("Audio stream error: {err}")));
+                    if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                        // The stream is dead; release it so the UI can rebuild on another device
+                        let _ = error_shutdown_tx.try_send(());
+                        let _ = event_tx.try_send(AudioEvent::DeviceChanged);
+                    }
                 },
                 None,
             )
@@ -241,16 +1042,215 @@ pub fn spawn_engine() -> Result<(Sender<AudioCommand>, Receiver<AudioEvent>), St
         .play()
         .map_err(|e| format!("Failed to start stream: {e}"))?;
 
-    // Keep stream alive by moving it into a thread
+    // Keep stream alive by moving it into a thread until shutdown is requested
     std::thread::Builder::new()
         .name("audio-keepalive".into())
         .spawn(move || {
             let _stream = stream;
-            loop {
-                std::thread::park();
-            }
+            let _ = shutdown_rx.recv();
+            tracing::info!("audio engine shut down");
         })
         .map_err(|e| format!("Failed to spawn keepalive thread: {e}"))?;
 
     Ok((cmd_tx, event_rx))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Counts allocations made on the current thread, so tests running
+    /// alongside don't disturb the count.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|a| a.set(a.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.with(|a| a.set(a.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Ten seconds of a stereo 440 Hz sine.
+    fn sine_audio() -> Arc<AudioData> {
+        let frames = 10 * SAMPLE_RATE as usize;
+        let samples = (0..frames)
+            .flat_map(|f| {
+                let s = 0.5 * (f as f32 * 440.0 * TAU / SAMPLE_RATE as f32).sin();
+                [s, s]
+            })
+            .collect();
+        Arc::new(AudioData {
+            samples,
+            sample_rate: SAMPLE_RATE,
+            channels: 2,
+            duration: 10.0,
+        })
+    }
+
+    /// Ten seconds of stereo held at `level`, so any change in the output
+    /// comes from the engine's gain.
+    fn constant_audio(level: f32) -> Arc<AudioData> {
+        Arc::new(AudioData {
+            samples: vec![level; 2 * 10 * SAMPLE_RATE as usize],
+            sample_rate: SAMPLE_RATE,
+            channels: 2,
+            duration: 10.0,
+        })
+    }
+
+    /// An engine with `audio` loaded, and the channel to its events.
+    fn engine(audio: Arc<AudioData>) -> (EngineState, Sender<AudioEvent>, Receiver<AudioEvent>) {
+        let (event_tx, event_rx) = crossbeam_channel::bounded(256);
+        let mut state = EngineState::new(SAMPLE_RATE, 2);
+        state.handle_command(AudioCommand::LoadAudio(audio), &event_tx);
+        (state, event_tx, event_rx)
+    }
+
+    #[test]
+    fn callbacks_do_not_allocate() {
+        let (mut state, event_tx, event_rx) = engine(sine_audio());
+        state.handle_command(AudioCommand::Play, &event_tx);
+        // Longer than the receive buffer, so it is filled in several passes.
+        // Counted from the first callback after loading.
+        let mut output = vec![0.0; 2 * (MAX_EXPECTED_CALLBACK_FRAMES + 512)];
+        for _ in 0..8 {
+            let before = ALLOCATIONS.with(Cell::get);
+            state.fill_buffer(&mut output, 2, &event_tx);
+            let allocations = ALLOCATIONS.with(Cell::get) - before;
+            event_rx.try_iter().for_each(drop);
+            assert_eq!(allocations, 0, "fill_buffer allocated");
+        }
+        assert!(output.iter().any(|s| s.abs() > 0.1), "nothing was played");
+    }
+
+    /// Render `callbacks` buffers of 256 frames, returning the left channel.
+    fn render(
+        state: &mut EngineState,
+        event_tx: &Sender<AudioEvent>,
+        event_rx: &Receiver<AudioEvent>,
+        callbacks: usize,
+    ) -> Vec<f32> {
+        let mut left = Vec::new();
+        let mut output = vec![0.0; 2 * 256];
+        for _ in 0..callbacks {
+            state.fill_buffer(&mut output, 2, event_tx);
+            event_rx.try_iter().for_each(drop);
+            left.extend(output.iter().step_by(2));
+        }
+        left
+    }
+
+    #[test]
+    fn play_and_pause_ramp_instead_of_stepping() {
+        let level = 0.5;
+        let (mut state, event_tx, event_rx) = engine(constant_audio(level));
+        let fade_frames = (TRANSPORT_FADE_SECS * SAMPLE_RATE as f32) as usize;
+        // Largest change between frames a ramp makes, with some slack
+        let max_step = 1.5 * level / fade_frames as f32;
+        let assert_ramped = |samples: &[f32], what: &str| {
+            for (i, pair) in samples.windows(2).enumerate() {
+                assert!(
+                    (pair[1] - pair[0]).abs() <= max_step,
+                    "{what}: jumped from {} to {} at frame {i}",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        };
+
+        for _ in 0..2 {
+            state.handle_command(AudioCommand::Play, &event_tx);
+            let started = render(&mut state, &event_tx, &event_rx, 8);
+            let first = started.iter().position(|s| *s != 0.0).unwrap();
+            assert!(
+                started[first] <= max_step,
+                "play starts at {}",
+                started[first]
+            );
+            assert!(
+                (started.last().unwrap() - level).abs() < 1e-3,
+                "play ends the fade at {}",
+                started.last().unwrap()
+            );
+            assert_ramped(&started, "play");
+
+            state.handle_command(AudioCommand::Pause, &event_tx);
+            let paused = render(&mut state, &event_tx, &event_rx, 8);
+            assert!(
+                (paused[0] - level).abs() <= max_step,
+                "pause starts at {}",
+                paused[0]
+            );
+            assert_eq!(*paused.last().unwrap(), 0.0, "pause ends the fade");
+            assert_ramped(&paused, "pause");
+            assert!(!state.playing, "still playing after the fade-out");
+        }
+    }
+
+    #[test]
+    fn ping_pong_reverses_at_each_boundary() {
+        let (mut state, event_tx, event_rx) = engine(sine_audio());
+        state.handle_command(AudioCommand::SetTempo(1.0), &event_tx);
+        state.handle_command(AudioCommand::SetLoopSnap(false), &event_tx);
+        state.handle_command(AudioCommand::SetLoopMode(LoopMode::PingPong), &event_tx);
+        state.handle_command(AudioCommand::SetLoopRegion(Some((1.0, 2.0))), &event_tx);
+        state.handle_command(AudioCommand::Seek(1.0), &event_tx);
+        state.handle_command(AudioCommand::Play, &event_tx);
+        let (start, end) = state.loop_region.unwrap();
+
+        let mut output = vec![0.0; 2 * 64];
+        let mut positions = vec![state.position];
+        for _ in 0..5000 {
+            state.fill_buffer(&mut output, 2, &event_tx);
+            event_rx.try_iter().for_each(drop);
+            assert!(
+                (start..=end).contains(&state.position),
+                "fed frame {} outside the loop",
+                state.position
+            );
+            if positions.last() != Some(&state.position) {
+                positions.push(state.position);
+            }
+        }
+
+        // Where the feeding turns round, and whether it was going forwards
+        let turns: Vec<(usize, bool)> = positions
+            .windows(3)
+            .filter(|w| (w[1] > w[0]) != (w[2] > w[1]))
+            .map(|w| (w[1], w[1] > w[0]))
+            .collect();
+        assert!(turns.len() >= 4, "only turned {} times", turns.len());
+        for (i, &(position, forwards)) in turns.iter().enumerate() {
+            // Starting forwards from the loop start, the first turn is at the end
+            assert_eq!(forwards, i % 2 == 0, "turn {i}");
+            let boundary = if forwards { end } else { start };
+            // SoundTouch may take a few chunks before giving out more audio,
+            // so the turn is only seen to within those
+            assert!(
+                position.abs_diff(boundary) <= 4 * CHUNK_SIZE,
+                "turn {i} at frame {position}, boundary {boundary}"
+            );
+        }
+    }
+}
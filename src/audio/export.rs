@@ -0,0 +1,170 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::mix;
+use super::stretcher::Stretcher;
+use super::types::{AudioData, ChannelMode};
+
+/// Frames fed into SoundTouch at a time while rendering.
+const RENDER_CHUNK: usize = 4096;
+
+/// Render the whole of `audio` through a stretcher at `tempo` and `pitch`
+/// (semitones), returning interleaved samples at the file's sample rate.
+pub fn render_processed(audio: &AudioData, tempo: f32, pitch: f32) -> Vec<f32> {
+    let channels = audio.channels as usize;
+    let mut stretcher = Stretcher::new(audio.sample_rate, audio.channels, audio.sample_rate);
+    stretcher.set_tempo(tempo);
+    stretcher.set_pitch(pitch);
+
+    // SoundTouch produces about 1/tempo output frames per input frame
+    let expected_frames = (audio.num_frames() as f64 / tempo as f64).round() as usize;
+    let mut out = Vec::with_capacity(expected_frames * channels);
+    let mut recv = vec![0.0; RENDER_CHUNK * channels];
+    let mut drain = |stretcher: &mut Stretcher, out: &mut Vec<f32>| loop {
+        let got = stretcher.receive_samples(&mut recv);
+        if got == 0 {
+            break;
+        }
+        out.extend_from_slice(&recv[..got]);
+    };
+
+    for chunk in audio.samples.chunks(RENDER_CHUNK * channels) {
+        stretcher.put_samples(chunk);
+        drain(&mut stretcher, &mut out);
+    }
+    stretcher.flush();
+    drain(&mut stretcher, &mut out);
+
+    // Flushing pads the tail with silence past the real end
+    out.truncate(expected_frames * channels);
+    out
+}
+
+/// Channel layout of an exported file.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportChannels {
+    /// As many channels as the source file.
+    #[default]
+    Source,
+    Mono,
+    Stereo,
+}
+
+impl ExportChannels {
+    pub const ALL: [ExportChannels; 3] = [
+        ExportChannels::Source,
+        ExportChannels::Mono,
+        ExportChannels::Stereo,
+    ];
+
+    /// Channel count of the exported file for a source with `source` channels.
+    pub fn count(self, source: u16) -> u16 {
+        match self {
+            ExportChannels::Source => source,
+            ExportChannels::Mono => 1,
+            ExportChannels::Stereo => 2,
+        }
+    }
+}
+
+impl fmt::Display for ExportChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportChannels::Source => write!(f, "Source channels"),
+            ExportChannels::Mono => write!(f, "Mono"),
+            ExportChannels::Stereo => write!(f, "Stereo"),
+        }
+    }
+}
+
+/// Mix interleaved `samples` from `from` channels to `to`, the way playback
+/// maps them to the output device. Only layouts the mixer folds properly
+/// are accepted: anything to mono, and up to 7.1 to stereo.
+pub fn remix(samples: &[f32], from: u16, to: u16) -> Result<Vec<f32>, String> {
+    if from == to {
+        return Ok(samples.to_vec());
+    }
+    if from == 0 || !(to == 1 || (to == 2 && from <= 8)) {
+        return Err(format!("Can't export {from} channels as {to}"));
+    }
+    let (from, to) = (from as usize, to as usize);
+    let matrix = mix::channel_matrix(from, to, ChannelMode::Stereo);
+    let mut out = vec![0.0; samples.len() / from * to];
+    for (input, output) in samples.chunks_exact(from).zip(out.chunks_exact_mut(to)) {
+        mix::mix_frame(&matrix, input, output, 1.0);
+    }
+    Ok(out)
+}
+
+/// Write interleaved samples as a 16-bit PCM WAV file.
+pub fn write_wav(
+    path: &Path,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+) -> Result<(), String> {
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    let mut w = BufWriter::new(file);
+
+    let data_len = (samples.len() * 2) as u32;
+    let block_align = channels * 2;
+    let byte_rate = sample_rate * block_align as u32;
+
+    let mut write = || -> std::io::Result<()> {
+        w.write_all(b"RIFF")?;
+        w.write_all(&(36 + data_len).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+        w.write_all(b"fmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        w.write_all(&1u16.to_le_bytes())?; // PCM
+        w.write_all(&channels.to_le_bytes())?;
+        w.write_all(&sample_rate.to_le_bytes())?;
+        w.write_all(&byte_rate.to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&16u16.to_le_bytes())?;
+        w.write_all(b"data")?;
+        w.write_all(&data_len.to_le_bytes())?;
+        for &s in samples {
+            let v = (s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            w.write_all(&v.to_le_bytes())?;
+        }
+        w.flush()
+    };
+    write().map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mono_export_of_stereo_is_the_mono_mixdown() {
+        let samples: Vec<f32> = (0..1000)
+            .flat_map(|i| {
+                let t = i as f32 / 100.0;
+                [t.sin() * 0.8, (t * 3.0).cos() * 0.3]
+            })
+            .collect();
+        let audio = AudioData {
+            samples,
+            sample_rate: 44100,
+            channels: 2,
+            duration: 1000.0 / 44100.0,
+        };
+        let mono = remix(&audio.samples, 2, ExportChannels::Mono.count(2)).unwrap();
+        assert_eq!(mono, audio.to_mono());
+        let source = remix(&audio.samples, 2, ExportChannels::Source.count(2)).unwrap();
+        assert_eq!(source, audio.samples);
+    }
+
+    #[test]
+    fn layouts_the_mixer_cannot_fold_are_refused() {
+        assert!(remix(&[0.0; 10], 10, 2).is_err());
+        assert!(remix(&[0.0; 12], 2, 6).is_err());
+        assert!(remix(&[0.0; 10], 10, 1).is_ok());
+        assert!(remix(&[0.0; 12], 6, 2).is_ok());
+    }
+}
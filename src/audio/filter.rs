@@ -0,0 +1,318 @@
+//! Biquad filters for shaping what is heard, applied after the stretcher.
+//!
+//! Coefficients follow the RBJ Audio EQ Cookbook; filters run in transposed
+//! direct form II, with two samples of state per channel.
+
+use std::f32::consts::{SQRT_2, TAU};
+
+/// Corner of the low shelf, in Hz.
+const LOW_SHELF_HZ: f32 = 250.0;
+/// Centre of the mid band, in Hz.
+const MID_PEAK_HZ: f32 = 1000.0;
+/// Width of the mid band; about two octaves.
+const MID_PEAK_Q: f32 = 0.7;
+/// Corner of the high shelf, in Hz.
+const HIGH_SHELF_HZ: f32 = 4000.0;
+
+/// Cutoff range of the sweep filters. A high-pass at the bottom or a
+/// low-pass at the top is switched off.
+pub const MIN_CUTOFF_HZ: f32 = 20.0;
+pub const MAX_CUTOFF_HZ: f32 = 20000.0;
+/// Resonance of the sweep filters; a little above Butterworth for a mild peak.
+const SWEEP_Q: f32 = 1.0;
+/// Time constant for cutoff changes, so dragging a cutoff doesn't zipper.
+const CUTOFF_SMOOTHING_SECS: f32 = 0.02;
+/// Frames between coefficient updates while a cutoff is moving.
+const CUTOFF_UPDATE_FRAMES: usize = 32;
+/// Cutoffs this close to their target (as a ratio) snap onto it.
+const CUTOFF_SNAP_RATIO: f32 = 1.001;
+
+/// Normalized biquad coefficients (`a0` divided out).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    /// Passes everything through unchanged.
+    pub const IDENTITY: Biquad = Biquad {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// Boost or cut everything below `freq` by `gain_db`.
+    pub fn low_shelf(freq: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = TAU * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        // Shelf slope of 1, the steepest without overshoot
+        let alpha = sin / 2.0 * SQRT_2;
+        let k = 2.0 * a.sqrt() * alpha;
+        Self::normalized(
+            a * ((a + 1.0) - (a - 1.0) * cos + k),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+            a * ((a + 1.0) - (a - 1.0) * cos - k),
+            (a + 1.0) + (a - 1.0) * cos + k,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+            (a + 1.0) + (a - 1.0) * cos - k,
+        )
+    }
+
+    /// Boost or cut everything above `freq` by `gain_db`.
+    pub fn high_shelf(freq: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = TAU * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / 2.0 * SQRT_2;
+        let k = 2.0 * a.sqrt() * alpha;
+        Self::normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos + k),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - k),
+            (a + 1.0) - (a - 1.0) * cos + k,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - k,
+        )
+    }
+
+    /// Boost or cut a band around `freq` by `gain_db`.
+    pub fn peaking(freq: f32, gain_db: f32, q: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = TAU * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// Cut everything above `freq`.
+    pub fn low_pass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = TAU * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Cut everything below `freq`.
+    pub fn high_pass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = TAU * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Filter one sample, updating `state`.
+    #[inline]
+    fn process(&self, x: f32, state: &mut [f32; 2]) -> f32 {
+        let y = self.b0 * x + state[0];
+        state[0] = self.b1 * x - self.a1 * y + state[1];
+        state[1] = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// Low shelf, mid peak and high shelf, each with its own gain.
+pub struct Equalizer {
+    sample_rate: f32,
+    bands: [Biquad; 3],
+    /// All gains are zero, so processing is skipped.
+    flat: bool,
+    /// Filter state per channel, per band.
+    state: Vec<[[f32; 2]; 3]>,
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            bands: [Biquad::IDENTITY; 3],
+            flat: true,
+            state: Vec::new(),
+        }
+    }
+
+    /// Size the filter state for interleaved audio with `channels` channels.
+    pub fn set_channels(&mut self, channels: usize) {
+        self.state = vec![[[0.0; 2]; 3]; channels];
+    }
+
+    /// Set the band gains in dB.
+    pub fn set_gains(&mut self, low_db: f32, mid_db: f32, high_db: f32) {
+        let sr = self.sample_rate;
+        self.bands = [
+            Biquad::low_shelf(LOW_SHELF_HZ, low_db, sr),
+            Biquad::peaking(MID_PEAK_HZ, mid_db, MID_PEAK_Q, sr),
+            Biquad::high_shelf(HIGH_SHELF_HZ, high_db, sr),
+        ];
+        self.flat = low_db == 0.0 && mid_db == 0.0 && high_db == 0.0;
+        if self.flat {
+            // Start from silence when the EQ comes back in
+            self.state.iter_mut().for_each(|s| *s = [[0.0; 2]; 3]);
+        }
+    }
+
+    /// Equalize interleaved `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.state.len();
+        if self.flat || channels == 0 {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                for (band, band_state) in self.bands.iter().zip(state.iter_mut()) {
+                    *sample = band.process(*sample, band_state);
+                }
+            }
+        }
+    }
+}
+
+/// Resonant high-pass and low-pass pair for isolating a part. Cutoffs glide
+/// to new settings rather than jumping.
+pub struct SweepFilter {
+    sample_rate: f32,
+    /// Cutoffs as set, in Hz.
+    hp_target: f32,
+    lp_target: f32,
+    /// Cutoffs currently applied, gliding towards the targets.
+    hp_cutoff: f32,
+    lp_cutoff: f32,
+    high_pass: Biquad,
+    low_pass: Biquad,
+    /// Filter state per channel: high-pass, then low-pass.
+    state: Vec<[[f32; 2]; 2]>,
+}
+
+impl SweepFilter {
+    /// Fully open, so nothing is filtered.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            hp_target: MIN_CUTOFF_HZ,
+            lp_target: MAX_CUTOFF_HZ,
+            hp_cutoff: MIN_CUTOFF_HZ,
+            lp_cutoff: MAX_CUTOFF_HZ,
+            high_pass: Biquad::IDENTITY,
+            low_pass: Biquad::IDENTITY,
+            state: Vec::new(),
+        }
+    }
+
+    /// Size the filter state for interleaved audio with `channels` channels.
+    pub fn set_channels(&mut self, channels: usize) {
+        self.state = vec![[[0.0; 2]; 2]; channels];
+    }
+
+    /// Set the cutoffs in Hz; they are reached over a few tens of milliseconds.
+    pub fn set_cutoffs(&mut self, hp_hz: f32, lp_hz: f32) {
+        self.hp_target = hp_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+        self.lp_target = lp_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+    }
+
+    fn hp_active(&self) -> bool {
+        self.hp_cutoff > MIN_CUTOFF_HZ
+    }
+
+    fn lp_active(&self) -> bool {
+        self.lp_cutoff < MAX_CUTOFF_HZ
+    }
+
+    /// Move the cutoffs `frames` further towards their targets, gliding in
+    /// log frequency so the sweep sounds even, and recompute coefficients.
+    fn glide(&mut self, frames: usize) {
+        if self.hp_cutoff == self.hp_target && self.lp_cutoff == self.lp_target {
+            return;
+        }
+        let k = 1.0 - (-(frames as f32) / (CUTOFF_SMOOTHING_SECS * self.sample_rate)).exp();
+        let step = |current: f32, target: f32| {
+            let next = current * (target / current).powf(k);
+            let ratio = next.max(target) / next.min(target);
+            if ratio < CUTOFF_SNAP_RATIO {
+                target
+            } else {
+                next
+            }
+        };
+        self.hp_cutoff = step(self.hp_cutoff, self.hp_target);
+        self.lp_cutoff = step(self.lp_cutoff, self.lp_target);
+
+        // Keep the low-pass clear of Nyquist, where the design breaks down
+        let nyquist_limit = self.sample_rate * 0.45;
+        self.high_pass =
+            Biquad::high_pass(self.hp_cutoff.min(nyquist_limit), SWEEP_Q, self.sample_rate);
+        self.low_pass =
+            Biquad::low_pass(self.lp_cutoff.min(nyquist_limit), SWEEP_Q, self.sample_rate);
+        // A filter switching back on starts from silence
+        let (hp_active, lp_active) = (self.hp_active(), self.lp_active());
+        for state in &mut self.state {
+            if !hp_active {
+                state[0] = [0.0; 2];
+            }
+            if !lp_active {
+                state[1] = [0.0; 2];
+            }
+        }
+    }
+
+    /// Filter interleaved `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.state.len();
+        if channels == 0 {
+            return;
+        }
+        for block in samples.chunks_mut(channels * CUTOFF_UPDATE_FRAMES) {
+            self.glide(block.len() / channels);
+            let (hp_active, lp_active) = (self.hp_active(), self.lp_active());
+            if !hp_active && !lp_active {
+                continue;
+            }
+            for frame in block.chunks_exact_mut(channels) {
+                for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                    if hp_active {
+                        *sample = self.high_pass.process(*sample, &mut state[0]);
+                    }
+                    if lp_active {
+                        *sample = self.low_pass.process(*sample, &mut state[1]);
+                    }
+                }
+            }
+        }
+    }
+}
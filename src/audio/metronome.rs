@@ -0,0 +1,159 @@
+use std::f32::consts::TAU;
+
+/// Length of a click, in seconds.
+const CLICK_SECS: f32 = 0.02;
+/// Click pitches in Hz; the downbeat is higher so bars stand out.
+const CLICK_HZ: f32 = 1000.0;
+const ACCENT_HZ: f32 = 1600.0;
+/// How quickly a click dies away, in decay time constants per click.
+const CLICK_DECAY: f32 = 5.0;
+/// Beat length used for a count-in when the file has no beat grid (120 BPM).
+const DEFAULT_BEAT_SECS: f64 = 0.5;
+
+/// Clicks played on their own before the audio starts, timed in output frames.
+pub struct CountIn {
+    beats: u32,
+    beat_len: usize,
+    elapsed: usize,
+}
+
+impl CountIn {
+    pub fn new(beats: u32, beat_len: usize) -> Self {
+        Self {
+            beats,
+            beat_len: beat_len.max(1),
+            elapsed: 0,
+        }
+    }
+
+    fn total_frames(&self) -> usize {
+        self.beats as usize * self.beat_len
+    }
+}
+
+/// Click track on the beat grid, mixed over the output by the engine.
+///
+/// Beats live in file frames and clicks are rendered in output frames, so the
+/// caller says which file frame each output frame corresponds to. That keeps
+/// the clicks on the beats whatever the tempo.
+pub struct Metronome {
+    pub enabled: bool,
+    pub gain: f32,
+    pub beats_per_bar: u32,
+    /// Beat positions in file frames, in order.
+    beat_frames: Vec<usize>,
+    click: Vec<f32>,
+    accent: Vec<f32>,
+    /// Output frames already played of the sounding click, and whether it's the accent.
+    sounding: Option<(usize, bool)>,
+}
+
+impl Metronome {
+    pub fn new(output_sample_rate: u32) -> Self {
+        let sr = output_sample_rate as f32;
+        let len = (CLICK_SECS * sr) as usize;
+        let tone = |hz: f32| -> Vec<f32> {
+            (0..len)
+                .map(|i| {
+                    let t = i as f32 / sr;
+                    (TAU * hz * t).sin() * (-CLICK_DECAY * t / CLICK_SECS).exp()
+                })
+                .collect()
+        };
+        Self {
+            enabled: false,
+            gain: 0.5,
+            beats_per_bar: 4,
+            beat_frames: Vec::new(),
+            click: tone(CLICK_HZ),
+            accent: tone(ACCENT_HZ),
+            sounding: None,
+        }
+    }
+
+    /// Replace the beat grid with `beats` (seconds) in a file at `sample_rate`.
+    pub fn set_beats(&mut self, beats: &[f64], sample_rate: u32) {
+        self.beat_frames.clear();
+        self.beat_frames
+            .extend(beats.iter().map(|b| (b * sample_rate as f64) as usize));
+    }
+
+    pub fn clear_beats(&mut self) {
+        self.beat_frames.clear();
+        self.sounding = None;
+    }
+
+    /// A count-in of `bars` bars at the pace of the beat grid, for a file at
+    /// `sample_rate` played with `step` file frames per output frame.
+    pub fn count_in(&self, bars: u32, sample_rate: u32, step: f64) -> CountIn {
+        let beat_len = self.beat_period(sample_rate) / step.max(f64::EPSILON);
+        CountIn::new(bars * self.beats_per_bar.max(1), beat_len as usize)
+    }
+
+    /// Typical beat length in file frames: the median gap of the beat grid, or
+    /// 120 BPM when there is no grid.
+    fn beat_period(&self, sample_rate: u32) -> f64 {
+        let mut gaps: Vec<usize> = self.beat_frames.windows(2).map(|w| w[1] - w[0]).collect();
+        if gaps.is_empty() {
+            return DEFAULT_BEAT_SECS * sample_rate as f64;
+        }
+        let mid = gaps.len() / 2;
+        *gaps.select_nth_unstable(mid).1 as f64
+    }
+
+    /// Write the count-in into the start of interleaved `output`, returning how
+    /// many frames it used. Fewer frames than `output` holds means it is over.
+    pub fn render_count_in(
+        &mut self,
+        output: &mut [f32],
+        channels: usize,
+        count_in: &mut CountIn,
+    ) -> usize {
+        let left = count_in.total_frames().saturating_sub(count_in.elapsed);
+        let frames = left.min(output.len() / channels);
+        let output = &mut output[..frames * channels];
+        output.fill(0.0);
+        for frame in output.chunks_exact_mut(channels) {
+            if count_in.elapsed.is_multiple_of(count_in.beat_len) {
+                let beat = (count_in.elapsed / count_in.beat_len) as u32;
+                self.sounding = Some((0, beat.is_multiple_of(self.beats_per_bar.max(1))));
+            }
+            self.play_click(frame);
+            count_in.elapsed += 1;
+        }
+        frames
+    }
+
+    /// Add clicks to interleaved `output`, whose first frame is heard at file
+    /// frame `heard_start`, with each output frame advancing `step` file frames.
+    pub fn render(&mut self, output: &mut [f32], channels: usize, heard_start: f64, step: f64) {
+        let mut next = self
+            .beat_frames
+            .partition_point(|&b| (b as f64) < heard_start);
+        for (f, frame) in output.chunks_exact_mut(channels).enumerate() {
+            let heard = heard_start + f as f64 * step;
+            // Several beats inside one frame (a huge step) only click once
+            while next < self.beat_frames.len() && (self.beat_frames[next] as f64) <= heard {
+                let bar_position = next as u32 % self.beats_per_bar.max(1);
+                self.sounding = Some((0, bar_position == 0));
+                next += 1;
+            }
+            self.play_click(frame);
+        }
+    }
+
+    /// Mix the next sample of the sounding click, if any, into every channel.
+    fn play_click(&mut self, frame: &mut [f32]) {
+        if let Some((played, accent)) = &mut self.sounding {
+            let click = if *accent { &self.accent } else { &self.click };
+            let sample = click[*played] * self.gain;
+            for s in frame.iter_mut() {
+                *s += sample;
+            }
+            *played += 1;
+            if *played >= click.len() {
+                self.sounding = None;
+            }
+        }
+    }
+}
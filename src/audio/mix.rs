@@ -0,0 +1,193 @@
+//! Mapping between the file's channels and the output device's channels.
+//!
+//! Source channels are assumed to be in the usual WAV/SMPTE order: front
+//! left, front right, centre, LFE, then the surround pairs.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use super::types::ChannelMode;
+
+/// Mix coefficients from `in_channels` to `out_channels`, row-major: entry
+/// `o * in_channels + i` is how much of input `i` goes into output `o`.
+///
+/// Mono feeds both front speakers at -3 dB so it sounds as loud as it would
+/// from one. Three to eight channels (3.0, quad, 5.0, 5.1, 6.1 and 7.1) fold
+/// their centre and surrounds into the front pair at -3 dB (LFE is dropped),
+/// normalized so a full-scale signal in every channel can't clip. Anything
+/// else copies matching channels and leaves the rest silent.
+///
+/// The solo modes put one source (left, right, or the mono sum) on every
+/// output channel instead.
+pub fn channel_matrix(in_channels: usize, out_channels: usize, mode: ChannelMode) -> Vec<f32> {
+    if mode == ChannelMode::Stereo || in_channels == 0 {
+        return mix_matrix(in_channels, out_channels);
+    }
+    let mut source = vec![0.0; in_channels];
+    match mode {
+        ChannelMode::Left => source[0] = 1.0,
+        // Mono files have no right channel, so soloing it plays the only one
+        ChannelMode::Right => source[1.min(in_channels - 1)] = 1.0,
+        ChannelMode::Mono => {
+            // Half of each side of the stereo fold-down
+            let fold = mix_matrix(in_channels, 2);
+            for (i, s) in source.iter_mut().enumerate() {
+                *s = 0.5 * (fold[i] + fold[in_channels + i]);
+            }
+            if in_channels == 1 {
+                source[0] = 1.0;
+            }
+        }
+        ChannelMode::Stereo => unreachable!(),
+    }
+    source.repeat(out_channels)
+}
+
+fn mix_matrix(in_channels: usize, out_channels: usize) -> Vec<f32> {
+    let mut m = vec![0.0; in_channels * out_channels];
+    if in_channels == 0 || out_channels == 0 {
+        return m;
+    }
+    let mut set = |o: usize, i: usize, gain: f32| m[o * in_channels + i] = gain;
+
+    if in_channels == out_channels {
+        for c in 0..in_channels {
+            set(c, c, 1.0);
+        }
+    } else if out_channels == 1 {
+        // Fold everything down to one speaker
+        for i in 0..in_channels {
+            set(0, i, 1.0 / in_channels as f32);
+        }
+    } else if in_channels == 1 {
+        set(0, 0, FRAC_1_SQRT_2);
+        set(1, 0, FRAC_1_SQRT_2);
+    } else if out_channels == 2 && (3..=8).contains(&in_channels) {
+        // Indices of the extra channels folded into each side. A lone
+        // centre or back-centre channel goes to both.
+        let (left, right): (&[usize], &[usize]) = match in_channels {
+            3 => (&[2], &[2]),
+            4 => (&[2], &[3]),
+            5 => (&[2, 3], &[2, 4]),
+            6 => (&[2, 4], &[2, 5]),
+            7 => (&[2, 4, 5], &[2, 4, 6]),
+            _ => (&[2, 4, 6], &[2, 5, 7]),
+        };
+        let norm = 1.0 / (1.0 + FRAC_1_SQRT_2 * left.len() as f32);
+        set(0, 0, norm);
+        set(1, 1, norm);
+        for &i in left {
+            set(0, i, FRAC_1_SQRT_2 * norm);
+        }
+        for &i in right {
+            set(1, i, FRAC_1_SQRT_2 * norm);
+        }
+    } else {
+        for c in 0..in_channels.min(out_channels) {
+            set(c, c, 1.0);
+        }
+    }
+    m
+}
+
+/// Mix one interleaved frame through `matrix` (from `channel_matrix`),
+/// scaling the result by `gain`.
+pub fn mix_frame(matrix: &[f32], input: &[f32], output: &mut [f32], gain: f32) {
+    let in_channels = input.len();
+    for (o, out) in output.iter_mut().enumerate() {
+        let row = &matrix[o * in_channels..(o + 1) * in_channels];
+        *out = row.iter().zip(input).map(|(m, s)| m * s).sum::<f32>() * gain;
+    }
+}
+
+/// Subtract `amount` of the mid signal, (L + R) / 2, from both sides of
+/// interleaved stereo samples. Centre-panned parts such as lead vocals
+/// cancel out at 1.0, which leaves only the side signal and so collapses
+/// the stereo image in proportion.
+pub fn reduce_centre(samples: &mut [f32], amount: f32) {
+    for frame in samples.chunks_exact_mut(2) {
+        let mid = 0.5 * (frame[0] + frame[1]);
+        frame[0] -= amount * mid;
+        frame[1] -= amount * mid;
+    }
+}
+
+/// Scale the side signal, (L - R) / 2, of interleaved stereo samples by
+/// `width`, leaving the mid alone: 0.0 is mono, 1.0 unchanged and above 1.0
+/// wider than recorded.
+pub fn set_stereo_width(samples: &mut [f32], width: f32) {
+    for frame in samples.chunks_exact_mut(2) {
+        let mid = 0.5 * (frame[0] + frame[1]);
+        let side = 0.5 * (frame[0] - frame[1]) * width;
+        frame[0] = mid + side;
+        frame[1] = mid - side;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gain of input `i` in output `o`.
+    fn gain(m: &[f32], in_channels: usize, o: usize, i: usize) -> f32 {
+        m[o * in_channels + i]
+    }
+
+    /// Mix one frame with every channel at full scale.
+    fn full_scale(m: &[f32], in_channels: usize) -> Vec<f32> {
+        let input = vec![1.0; in_channels];
+        let mut output = vec![0.0; m.len() / in_channels];
+        mix_frame(m, &input, &mut output, 1.0);
+        output
+    }
+
+    #[test]
+    fn mono_to_stereo_feeds_both_sides_at_minus_3_db() {
+        let m = channel_matrix(1, 2, ChannelMode::Stereo);
+        assert_eq!(m, vec![FRAC_1_SQRT_2, FRAC_1_SQRT_2]);
+    }
+
+    #[test]
+    fn surround_to_stereo_folds_centre_and_surrounds() {
+        let m = channel_matrix(6, 2, ChannelMode::Stereo);
+        let norm = 1.0 / (1.0 + 2.0 * FRAC_1_SQRT_2);
+        assert_eq!(gain(&m, 6, 0, 0), norm);
+        assert_eq!(gain(&m, 6, 1, 1), norm);
+        for o in 0..2 {
+            assert_eq!(gain(&m, 6, o, 2), FRAC_1_SQRT_2 * norm, "centre");
+            assert_eq!(gain(&m, 6, o, 3), 0.0, "LFE");
+        }
+        assert_eq!(gain(&m, 6, 0, 4), FRAC_1_SQRT_2 * norm);
+        assert_eq!(gain(&m, 6, 1, 4), 0.0);
+        assert_eq!(gain(&m, 6, 1, 5), FRAC_1_SQRT_2 * norm);
+        assert_eq!(gain(&m, 6, 0, 5), 0.0);
+    }
+
+    #[test]
+    fn odd_channel_counts_keep_centre_and_surrounds() {
+        // The channels expected to reach the front pair, besides L and R
+        for (in_channels, folded) in [(3, vec![2]), (5, vec![2, 3, 4]), (7, vec![2, 4, 5, 6])] {
+            let m = channel_matrix(in_channels, 2, ChannelMode::Stereo);
+            for i in folded {
+                let total = gain(&m, in_channels, 0, i) + gain(&m, in_channels, 1, i);
+                assert!(total > 0.0, "channel {i} of {in_channels} is dropped");
+            }
+            for out in full_scale(&m, in_channels) {
+                assert!(out <= 1.0 + 1e-6, "{in_channels} channels clip: {out}");
+            }
+        }
+    }
+
+    #[test]
+    fn folded_sides_are_balanced() {
+        for in_channels in 3..=8 {
+            let out = full_scale(
+                &channel_matrix(in_channels, 2, ChannelMode::Stereo),
+                in_channels,
+            );
+            assert!(
+                (out[0] - out[1]).abs() < 1e-6,
+                "{in_channels} channels: {out:?}"
+            );
+        }
+    }
+}
@@ -1,4 +1,9 @@
+pub mod analysis;
 pub mod decoder;
 pub mod engine;
+pub mod export;
+pub mod filter;
+pub mod metronome;
+pub mod mix;
 pub mod stretcher;
 pub mod types;
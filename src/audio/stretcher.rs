@@ -1,29 +1,51 @@
 use soundtouch::SoundTouch;
 
-/// Wrapper around SoundTouch for tempo-changing without pitch shift.
+/// Wrapper around SoundTouch that changes tempo and shifts pitch, each
+/// without affecting the other.
+///
+/// Also converts from the file's sample rate to the output device's, so the
+/// samples it produces can be played at `output_sample_rate` as-is.
 pub struct Stretcher {
     st: SoundTouch,
     channels: u16,
+    /// Input frames fed in since the last clear.
+    fed: usize,
+    /// Input frames the output received since the last clear stands for.
+    received: f64,
 }
 
 #[allow(dead_code)]
 impl Stretcher {
-    pub fn new(sample_rate: u32, channels: u16) -> Self {
+    pub fn new(sample_rate: u32, channels: u16, output_sample_rate: u32) -> Self {
         let mut st = SoundTouch::new();
         st.set_sample_rate(sample_rate);
         st.set_channels(channels as u32);
         st.set_tempo(1.0);
-        Stretcher { st, channels }
+        // Rate changes speed and pitch together, which is exactly a resample:
+        // 44.1kHz into 48kHz needs 48000/44100 output frames per input frame
+        st.set_rate(sample_rate as f64 / output_sample_rate as f64);
+        Stretcher {
+            st,
+            channels,
+            fed: 0,
+            received: 0.0,
+        }
     }
 
     pub fn set_tempo(&mut self, tempo: f32) {
         self.st.set_tempo(tempo as f64);
     }
 
+    /// Shift pitch by `semitones` without changing tempo.
+    pub fn set_pitch(&mut self, semitones: f32) {
+        self.st.set_pitch_octaves(semitones as f64 / 12.0);
+    }
+
     /// Feed interleaved input samples into SoundTouch.
     pub fn put_samples(&mut self, samples: &[f32]) {
-        self.st
-            .put_samples(samples, samples.len() / self.channels as usize);
+        let frames = samples.len() / self.channels as usize;
+        self.st.put_samples(samples, frames);
+        self.fed += frames;
     }
 
     /// Receive processed samples from SoundTouch.
@@ -31,9 +53,28 @@ impl Stretcher {
     pub fn receive_samples(&mut self, output: &mut [f32]) -> usize {
         let max_frames = output.len() / self.channels as usize;
         let received_frames = self.st.receive_samples(output, max_frames);
+        self.received += received_frames as f64 * self.input_frames_per_output_frame();
         received_frames * self.channels as usize
     }
 
+    /// Input frames fed in but not yet received back out, counted from the
+    /// output received so far in input time. SoundTouch's own count of ready
+    /// output isn't usable through the bindings, which read it from the
+    /// wrong object.
+    pub fn latency_frames(&mut self) -> usize {
+        (self.fed as f64 - self.received).max(0.0).round() as usize
+    }
+
+    /// Input frames consumed per output frame produced, e.g. 2.0 at double tempo.
+    pub fn input_frames_per_output_frame(&mut self) -> f64 {
+        let ratio = self.st.get_input_output_sample_ratio();
+        if ratio > 0.0 {
+            1.0 / ratio
+        } else {
+            1.0
+        }
+    }
+
     /// Flush remaining samples through the processor.
     pub fn flush(&mut self) {
         self.st.flush();
@@ -42,5 +83,93 @@ impl Stretcher {
     /// Clear all buffered data (use when seeking or changing loop).
     pub fn clear(&mut self) {
         self.st.clear();
+        self.fed = 0;
+        self.received = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `frames` of interleaved sine at 440 Hz on every channel.
+    fn sine(frames: usize, channels: u16, sample_rate: u32) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|f| {
+                let s = (f as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin();
+                std::iter::repeat_n(s * 0.5, channels as usize)
+            })
+            .collect()
+    }
+
+    /// Receive everything ready, returning the number of frames.
+    fn drain(stretcher: &mut Stretcher, channels: u16) -> usize {
+        let mut buf = vec![0.0; 4096 * channels as usize];
+        let mut frames = 0;
+        loop {
+            let got = stretcher.receive_samples(&mut buf);
+            if got == 0 {
+                return frames;
+            }
+            frames += got / channels as usize;
+        }
+    }
+
+    #[test]
+    fn resamples_to_the_output_rate() {
+        let channels = 2;
+        let frames = 44100;
+        let mut stretcher = Stretcher::new(44100, channels, 48000);
+        stretcher.put_samples(&sine(frames, channels, 44100));
+        stretcher.flush();
+        let received = drain(&mut stretcher, channels) as f64;
+        let expected = frames as f64 * 48000.0 / 44100.0;
+        assert!(
+            (received - expected).abs() < expected * 0.01,
+            "received {received} frames, expected about {expected}"
+        );
+    }
+
+    #[test]
+    fn latency_matches_what_is_heard_at_half_tempo() {
+        let channels = 2;
+        let sample_rate = 44100;
+        let tempo = 0.5;
+        // A burst in silence, to find in the output
+        let click_at = 30_000;
+        let mut input = vec![0.0; 80_000 * channels as usize];
+        input[click_at * channels as usize..(click_at + 32) * channels as usize].fill(1.0);
+
+        let mut stretcher = Stretcher::new(sample_rate, channels, sample_rate);
+        stretcher.set_tempo(tempo);
+        let mut output = Vec::new();
+        let mut buf = vec![0.0; 4096 * channels as usize];
+        // Output frames received so far, and the input position reported then
+        let mut reports = Vec::new();
+        let mut fed = 0;
+        for chunk in input.chunks(512 * channels as usize) {
+            stretcher.put_samples(chunk);
+            fed += chunk.len() / channels as usize;
+            loop {
+                let got = stretcher.receive_samples(&mut buf);
+                if got == 0 {
+                    break;
+                }
+                output.extend_from_slice(&buf[..got]);
+            }
+            let reported = fed as f64 - stretcher.latency_frames() as f64;
+            reports.push((output.len() / channels as usize, reported));
+        }
+
+        let heard_at = output.iter().position(|s| s.abs() > 0.3).unwrap() / channels as usize;
+        // The first report once the click has been received, wound back to it
+        let &(received, reported) = reports.iter().find(|(r, _)| *r > heard_at).unwrap();
+        let reported_at_click = reported - (received - heard_at) as f64 * tempo as f64;
+        // SoundTouch only keeps to the tempo within some tens of milliseconds
+        let tolerance = 0.05 * sample_rate as f64;
+        assert!(
+            (reported_at_click - click_at as f64).abs() < tolerance,
+            "reported {reported_at_click} when the click at {click_at} was heard"
+        );
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::sync::Arc;
 
 /// Decoded audio data stored entirely in memory.
@@ -39,11 +40,195 @@ pub enum AudioCommand {
     Pause,
     Stop,
     Seek(f64),
+    /// Play a short snippet around this time (seconds), if paused.
+    Scrub(f64),
     SetTempo(f32),
+    /// Shuttle speed as a multiple of the tempo, negative to play backwards;
+    /// 0 for normal playback.
+    SetShuttle(f32),
+    /// Beat times in seconds for the metronome, for the loaded file.
+    SetBeats(Vec<f64>),
+    /// Bars of clicks before a loop starts or restarts; 0 turns the count-in off.
+    SetCountIn(u32),
+    /// Click on each beat at `gain`, accenting the first of every
+    /// `beats_per_bar`.
+    SetMetronome {
+        enabled: bool,
+        gain: f32,
+        beats_per_bar: u32,
+    },
+    /// Change the tempo each time the loop comes round, or stop doing so.
+    SetTempoRamp(Option<TempoRamp>),
+    /// Pitch shift in semitones.
+    SetPitch(f32),
+    /// Linear output gain.
+    SetGain(f32),
+    SetMuted(bool),
     SetLoopRegion(Option<(f64, f64)>),
+    SetLoopMode(LoopMode),
+    /// Play the loop this many times (`None` for forever), then apply the
+    /// `AfterLoops` action.
+    SetLoopRepeats(Option<u32>, AfterLoops),
+    /// Crossfade length at the loop seam in seconds (0.0 for a hard cut).
+    SetLoopCrossfade(f32),
+    /// Snap loop boundaries to nearby zero crossings when they are set.
+    SetLoopSnap(bool),
+    SetChannelMode(ChannelMode),
+    /// Remove this fraction (0.0 to 1.0) of the centre of stereo files,
+    /// narrowing the stereo image by as much. Mono files are unaffected.
+    SetVocalReduction(f32),
+    /// Side level relative to mid for stereo files: 0.0 is mono, 1.0 as
+    /// recorded, above 1.0 wider. Mono files are unaffected.
+    SetStereoWidth(f32),
+    /// Gains in dB for the low shelf, mid band and high shelf of the EQ.
+    SetEq {
+        low_gain: f32,
+        mid_gain: f32,
+        high_gain: f32,
+    },
+    /// High-pass and low-pass cutoffs in Hz; the ends of the range turn
+    /// each filter off.
+    SetFilter {
+        hp_hz: f32,
+        lp_hz: f32,
+    },
     Shutdown,
 }
 
+/// Which of the file's channels are heard. Only the output mix changes; the
+/// decoded audio is left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChannelMode {
+    /// All channels, mixed to the device as usual.
+    #[default]
+    Stereo,
+    /// The left channel on every speaker.
+    Left,
+    /// The right channel on every speaker.
+    Right,
+    /// All channels summed to mono on every speaker.
+    Mono,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 4] = [
+        ChannelMode::Stereo,
+        ChannelMode::Left,
+        ChannelMode::Right,
+        ChannelMode::Mono,
+    ];
+}
+
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelMode::Stereo => write!(f, "Stereo"),
+            ChannelMode::Left => write!(f, "Left"),
+            ChannelMode::Right => write!(f, "Right"),
+            ChannelMode::Mono => write!(f, "Mono"),
+        }
+    }
+}
+
+/// What happens when playback reaches the end of the loop region.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LoopMode {
+    /// Jump back to the loop start.
+    #[default]
+    Restart,
+    /// Play the loop backwards to the start, then forwards again.
+    PingPong,
+    /// Pause at the loop end; playing again starts from the loop start.
+    PauseAtEnd,
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 3] = [LoopMode::Restart, LoopMode::PingPong, LoopMode::PauseAtEnd];
+}
+
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopMode::Restart => write!(f, "Restart"),
+            LoopMode::PingPong => write!(f, "Ping-pong"),
+            LoopMode::PauseAtEnd => write!(f, "Pause at end"),
+        }
+    }
+}
+
+/// Steps the tempo towards a target, one step per loop pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoRamp {
+    pub target: f32,
+    /// Size of each step; the direction comes from the target.
+    pub step: f32,
+}
+
+impl TempoRamp {
+    /// The tempo after one more pass starting at `tempo`.
+    pub fn next_tempo(&self, tempo: f32) -> f32 {
+        let step = self.step.abs();
+        if tempo < self.target {
+            (tempo + step).min(self.target)
+        } else {
+            (tempo - step).max(self.target)
+        }
+    }
+}
+
+/// What happens once a loop has played its set number of times.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AfterLoops {
+    /// Stop, ready to play the loop again from its start.
+    #[default]
+    Stop,
+    /// Keep playing past the loop end.
+    Continue,
+}
+
+impl AfterLoops {
+    pub const ALL: [AfterLoops; 2] = [AfterLoops::Stop, AfterLoops::Continue];
+}
+
+impl fmt::Display for AfterLoops {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AfterLoops::Stop => write!(f, "Stop"),
+            AfterLoops::Continue => write!(f, "Continue"),
+        }
+    }
+}
+
+/// Where the playhead goes when playback is stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StopBehavior {
+    /// Rewind to the start of the file.
+    #[default]
+    ResetToZero,
+    /// Rewind to the loop start, or the start of the file without a loop.
+    ResetToLoopStart,
+    /// Keep the current position, like pause.
+    StayInPlace,
+}
+
+impl StopBehavior {
+    pub const ALL: [StopBehavior; 3] = [
+        StopBehavior::ResetToZero,
+        StopBehavior::ResetToLoopStart,
+        StopBehavior::StayInPlace,
+    ];
+}
+
+impl fmt::Display for StopBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopBehavior::ResetToZero => write!(f, "Go to start"),
+            StopBehavior::ResetToLoopStart => write!(f, "Go to loop start"),
+            StopBehavior::StayInPlace => write!(f, "Stay in place"),
+        }
+    }
+}
+
 /// Events sent from the audio thread to the UI thread.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -51,6 +236,22 @@ pub enum AudioEvent {
     PositionChanged(f64),
     PlaybackFinished,
     Error(String),
+    /// Audio callbacks are consistently using this fraction of their deadline.
+    HighCpuLoad(f32),
+    /// The output ran out of audio for a moment, which is heard as a glitch.
+    Underrun,
+    /// The output device went away; the stream has been closed.
+    DeviceChanged,
+    /// The loop has played its set number of times.
+    LoopRepeatsFinished,
+    /// A tempo ramp moved the tempo on.
+    TempoChanged(f32),
+    /// Playback paused at the loop end (pause-at-end mode).
+    PausedAtLoopEnd,
+    /// Output levels since the last report: the peak sample, and the sum
+    /// over frames of the squared samples of all channels, over this many
+    /// frames.
+    Levels(f32, f64, usize),
 }
 
 /// Current playback status.
@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::waveform_cache::stable_hash;
+
+/// Bytes read between cancellation checks.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Smallest change in downloaded fraction worth reporting as progress.
+const PROGRESS_STEP: f32 = 0.01;
+
+/// Whether `location` is an http(s) URL rather than a file path.
+pub fn is_url(location: &str) -> bool {
+    let lower = location.trim_start().to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Where a download of `url` is kept: a temp directory, under the URL's own
+/// file name so the decoder can go by its extension and the title shows it.
+/// The directory is named after a hash of the whole URL, so two URLs ending
+/// in the same file name don't overwrite each other.
+fn download_path(url: &str) -> PathBuf {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path
+        .rsplit('/')
+        .find(|segment| !segment.is_empty() && !segment.contains(':'))
+        .unwrap_or("download");
+    std::env::temp_dir()
+        .join("transcribe")
+        .join(format!("{:016x}", stable_hash(url.as_bytes())))
+        .join(name)
+}
+
+/// The file a download is written to until it completes.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Download `url` to a temporary file and return its path.
+///
+/// `progress` is called with the downloaded fraction (0.0 to 1.0) when the
+/// server reports the length. Setting `cancel` stops the download with an
+/// error. The file only appears at the returned path once it is complete;
+/// a cancelled or failed download leaves nothing behind.
+pub fn download(
+    url: &str,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(f32),
+) -> Result<PathBuf, String> {
+    tracing::info!(url, "downloading");
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| format!("Failed to download {url}: {e}"))?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .filter(|&len| len > 0);
+
+    let path = download_path(url);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create download directory: {e}"))?;
+    }
+    let part = partial_path(&path);
+    let written =
+        write_body(url, response, &part, total, cancel, &mut progress).and_then(|received| {
+            std::fs::rename(&part, &path)
+                .map_err(|e| format!("Failed to save download file: {e}"))?;
+            Ok(received)
+        });
+    let received = match written {
+        Ok(received) => received,
+        Err(e) => {
+            let _ = std::fs::remove_file(&part);
+            return Err(e);
+        }
+    };
+    tracing::info!(url, bytes = received, path = %path.display(), "downloaded");
+    Ok(path)
+}
+
+/// Write the body of `response` to `part`, returning the number of bytes.
+fn write_body(
+    url: &str,
+    response: ureq::Response,
+    part: &Path,
+    total: Option<u64>,
+    cancel: &AtomicBool,
+    progress: &mut impl FnMut(f32),
+) -> Result<u64, String> {
+    let mut file =
+        File::create(part).map_err(|e| format!("Failed to create download file: {e}"))?;
+
+    let mut reader = response.into_reader();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut received: u64 = 0;
+    let mut reported = 0.0f32;
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            tracing::info!(url, "download cancelled");
+            return Err("Download cancelled".to_string());
+        }
+        let n = reader
+            .read(&mut buf)
+            .map_err(|e| format!("Download interrupted: {e}"))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])
+            .map_err(|e| format!("Failed to write download file: {e}"))?;
+        received += n as u64;
+        if let Some(total) = total {
+            let fraction = (received as f32 / total as f32).min(1.0);
+            if fraction - reported >= PROGRESS_STEP {
+                reported = fraction;
+                progress(reported);
+            }
+        }
+    }
+    Ok(received)
+}
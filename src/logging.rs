@@ -0,0 +1,27 @@
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable holding the log filter, e.g. `TRANSCRIBE_LOG=debug`
+/// or `TRANSCRIBE_LOG=info,transcribe::audio=debug`.
+const LOG_ENV_VAR: &str = "TRANSCRIBE_LOG";
+
+/// Install the stderr logger if `TRANSCRIBE_LOG` holds a filter. Logging is
+/// silent otherwise.
+pub fn init() {
+    let filter = match EnvFilter::try_from_env(LOG_ENV_VAR) {
+        Ok(filter) => filter,
+        // Unset is the usual case; anything else is a typo worth pointing out
+        Err(e) => {
+            if std::env::var_os(LOG_ENV_VAR).is_some() {
+                eprintln!("Ignoring {LOG_ENV_VAR}: {e}");
+            }
+            return;
+        }
+    };
+    let installed = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .try_init();
+    if installed.is_err() {
+        eprintln!("A global logger was already installed");
+    }
+}
@@ -1,8 +1,18 @@
 mod app;
 mod audio;
+mod download;
+mod logging;
+mod media_keys;
+mod midi;
+mod presets;
+mod recent;
+mod resume;
+mod session;
+mod settings;
 mod ui;
 mod waveform_cache;
 
 fn main() -> iced::Result {
+    logging::init();
     app::run()
 }
@@ -0,0 +1,77 @@
+use global_hotkey::hotkey::{Code, HotKey};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+/// A media key on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaKey {
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+}
+
+impl MediaKey {
+    const ALL: [MediaKey; 4] = [
+        MediaKey::PlayPause,
+        MediaKey::Stop,
+        MediaKey::Next,
+        MediaKey::Previous,
+    ];
+
+    fn code(self) -> Code {
+        match self {
+            MediaKey::PlayPause => Code::MediaPlayPause,
+            MediaKey::Stop => Code::MediaStop,
+            MediaKey::Next => Code::MediaTrackNext,
+            MediaKey::Previous => Code::MediaTrackPrevious,
+        }
+    }
+}
+
+/// The media keys, registered as global hotkeys so they reach the app while
+/// another window has focus. Dropping this unregisters them.
+pub struct MediaKeys {
+    manager: GlobalHotKeyManager,
+    hotkeys: Vec<(HotKey, MediaKey)>,
+}
+
+impl MediaKeys {
+    pub fn register() -> Result<MediaKeys, String> {
+        let manager =
+            GlobalHotKeyManager::new().map_err(|e| format!("Global hotkeys unavailable: {e}"))?;
+        let hotkeys: Vec<(HotKey, MediaKey)> = MediaKey::ALL
+            .iter()
+            .map(|&key| (HotKey::new(None, key.code()), key))
+            .collect();
+        let keys: Vec<HotKey> = hotkeys.iter().map(|(hotkey, _)| *hotkey).collect();
+        manager
+            .register_all(&keys)
+            .map_err(|e| format!("Failed to register media keys: {e}"))?;
+        tracing::info!("registered media keys");
+        Ok(MediaKeys { manager, hotkeys })
+    }
+
+    /// Media keys pressed since the last call, in order.
+    pub fn pressed(&self) -> Vec<MediaKey> {
+        GlobalHotKeyEvent::receiver()
+            .try_iter()
+            .filter(|event| event.state == HotKeyState::Pressed)
+            .filter_map(|event| {
+                self.hotkeys
+                    .iter()
+                    .find(|(hotkey, _)| hotkey.id() == event.id)
+                    .map(|(_, key)| *key)
+            })
+            .collect()
+    }
+}
+
+impl Drop for MediaKeys {
+    fn drop(&mut self) {
+        let keys: Vec<HotKey> = self.hotkeys.iter().map(|(hotkey, _)| *hotkey).collect();
+        match self.manager.unregister_all(&keys) {
+            Ok(()) => tracing::info!("unregistered media keys"),
+            Err(e) => tracing::warn!(error = %e, "failed to unregister media keys"),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crossbeam_channel::{Receiver, Sender};
+use directories::ProjectDirs;
+use midir::{Ignore, MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+
+/// Name the app registers with the MIDI system.
+const CLIENT_NAME: &str = "transcribe";
+
+/// Controller values at or above this count as a pedal being pressed.
+const CC_PRESSED: u8 = 64;
+
+/// What a MIDI controller can do. Actions go through the same paths as the
+/// on-screen controls, so the display stays in step with the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MidiAction {
+    PlayPause,
+    /// Jump back to the loop start, or the start of the file without a loop.
+    RestartLoop,
+    TempoUp,
+    TempoDown,
+}
+
+impl fmt::Display for MidiAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiAction::PlayPause => write!(f, "Play/pause"),
+            MidiAction::RestartLoop => write!(f, "Restart loop"),
+            MidiAction::TempoUp => write!(f, "Tempo up"),
+            MidiAction::TempoDown => write!(f, "Tempo down"),
+        }
+    }
+}
+
+/// The message that fires an action, on any channel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MidiTrigger {
+    /// Note on with this note number.
+    Note(u8),
+    /// This controller going from released to pressed.
+    ControlChange(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    pub trigger: MidiTrigger,
+    pub action: MidiAction,
+}
+
+/// The input port to listen on and what its messages do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MidiConfig {
+    /// Port name, or `None` to leave MIDI off.
+    pub port: Option<String>,
+    pub bindings: Vec<MidiBinding>,
+}
+
+impl Default for MidiConfig {
+    /// Off, with the pedal controllers most foot switches send mapped.
+    fn default() -> Self {
+        let cc = |number, action| MidiBinding {
+            trigger: MidiTrigger::ControlChange(number),
+            action,
+        };
+        Self {
+            port: None,
+            bindings: vec![
+                cc(64, MidiAction::PlayPause),
+                cc(65, MidiAction::RestartLoop),
+                cc(66, MidiAction::TempoDown),
+                cc(67, MidiAction::TempoUp),
+            ],
+        }
+    }
+}
+
+/// Where the config is stored: `midi.json` in the platform config directory.
+fn config_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.config_dir().join("midi.json"))
+}
+
+impl MidiConfig {
+    pub fn load() -> MidiConfig {
+        let Some(path) = config_path() else {
+            return MidiConfig::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "ignoring unreadable MIDI settings");
+                MidiConfig::default()
+            }),
+            Err(_) => MidiConfig::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = config_path().ok_or("No config directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create config directory: {e}"))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize MIDI settings: {e}"))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write MIDI settings: {e}"))
+    }
+}
+
+/// Names of the MIDI input ports. Empty when there are none or MIDI isn't
+/// available on this system.
+pub fn input_port_names() -> Vec<String> {
+    let input = match MidiInput::new(CLIENT_NAME) {
+        Ok(input) => input,
+        Err(e) => {
+            tracing::info!(error = %e, "MIDI input unavailable");
+            return Vec::new();
+        }
+    };
+    let names: Vec<String> = input
+        .ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect();
+    tracing::debug!(?names, "enumerated MIDI input ports");
+    names
+}
+
+/// An open MIDI input port. Dropping it closes the port.
+pub struct MidiListener {
+    _connection: MidiInputConnection<()>,
+    /// Actions triggered since they were last drained.
+    pub actions: Receiver<MidiAction>,
+}
+
+/// Open the input port called `port_name` and translate its messages into
+/// actions on midir's own thread.
+pub fn listen(port_name: &str, bindings: Vec<MidiBinding>) -> Result<MidiListener, String> {
+    let mut input =
+        MidiInput::new(CLIENT_NAME).map_err(|e| format!("MIDI input unavailable: {e}"))?;
+    input.ignore(Ignore::All);
+    let port = input
+        .ports()
+        .into_iter()
+        .find(|p| input.port_name(p).as_deref() == Ok(port_name))
+        .ok_or_else(|| format!("MIDI input \"{port_name}\" not found"))?;
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let mut pressed = [false; 128];
+    let connection = input
+        .connect(
+            &port,
+            "transcribe-input",
+            move |_stamp, message, _| handle_message(message, &bindings, &mut pressed, &tx),
+            (),
+        )
+        .map_err(|e| format!("Failed to open MIDI input: {e}"))?;
+    tracing::info!(port = %port_name, "listening for MIDI input");
+    Ok(MidiListener {
+        _connection: connection,
+        actions: rx,
+    })
+}
+
+/// Send the action bound to `message`, if any. `pressed` holds which
+/// controllers are held down, so a pedal fires once per press.
+fn handle_message(
+    message: &[u8],
+    bindings: &[MidiBinding],
+    pressed: &mut [bool; 128],
+    tx: &Sender<MidiAction>,
+) {
+    let trigger = match *message {
+        [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+            MidiTrigger::Note(note)
+        }
+        [status, number, value] if status & 0xF0 == 0xB0 => {
+            let slot = &mut pressed[number as usize & 0x7F];
+            let was_pressed = std::mem::replace(slot, value >= CC_PRESSED);
+            if was_pressed || !*slot {
+                return;
+            }
+            MidiTrigger::ControlChange(number)
+        }
+        _ => return,
+    };
+    for binding in bindings.iter().filter(|b| b.trigger == trigger) {
+        tracing::debug!(?trigger, action = %binding.action, "MIDI action");
+        let _ = tx.send(binding.action);
+    }
+}
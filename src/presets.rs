@@ -0,0 +1,109 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// A named set of EQ, filter, stereo width and vocal reduction settings,
+/// so a chain dialled in for one kind of recording can be recalled later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DspPreset {
+    pub name: String,
+    /// EQ band gains in dB.
+    pub eq_low: f32,
+    pub eq_mid: f32,
+    pub eq_high: f32,
+    /// High-pass and low-pass cutoffs in Hz.
+    pub hp_hz: f32,
+    pub lp_hz: f32,
+    pub stereo_width: f32,
+    pub vocal_reduction: f32,
+}
+
+impl fmt::Display for DspPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Where presets are stored: `presets.json` in the platform config directory.
+fn presets_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.config_dir().join("presets.json"))
+}
+
+/// Read the stored presets, sorted by name.
+pub fn load() -> Vec<DspPreset> {
+    let Some(path) = presets_path() else {
+        return Vec::new();
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "ignoring unreadable presets");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Add `preset` to `presets`, replacing any with the same name, then write
+/// the list out.
+pub fn add(presets: &mut Vec<DspPreset>, preset: DspPreset) -> Result<(), String> {
+    insert(presets, preset);
+    save(presets)
+}
+
+/// Put `preset` in its place by name, replacing one with the same name.
+fn insert(presets: &mut Vec<DspPreset>, preset: DspPreset) {
+    match presets.binary_search_by(|p| p.name.cmp(&preset.name)) {
+        Ok(i) => presets[i] = preset,
+        Err(i) => presets.insert(i, preset),
+    }
+}
+
+fn save(presets: &[DspPreset]) -> Result<(), String> {
+    let path = presets_path().ok_or("No config directory available")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create config directory: {e}"))?;
+    }
+    let json = serde_json::to_string_pretty(presets)
+        .map_err(|e| format!("Failed to serialize presets: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write presets: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, eq_low: f32) -> DspPreset {
+        DspPreset {
+            name: name.to_string(),
+            eq_low,
+            eq_mid: -3.0,
+            eq_high: 4.5,
+            hp_hz: 80.0,
+            lp_hz: 12_000.0,
+            stereo_width: 1.25,
+            vocal_reduction: 0.6,
+        }
+    }
+
+    #[test]
+    fn presets_round_trip_through_json() {
+        let presets = vec![preset("Bass lines", 6.0), preset("Guitar solo", -2.0)];
+        let json = serde_json::to_string_pretty(&presets).unwrap();
+        let read: Vec<DspPreset> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, presets);
+    }
+
+    #[test]
+    fn saving_under_an_existing_name_replaces_it() {
+        let mut presets = Vec::new();
+        insert(&mut presets, preset("Vocals", 0.0));
+        insert(&mut presets, preset("Bass lines", 6.0));
+        insert(&mut presets, preset("Vocals", -4.0));
+        let names: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Bass lines", "Vocals"]);
+        assert_eq!(presets[1].eq_low, -4.0);
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/// How many recently opened files are remembered.
+const MAX_RECENT_FILES: usize = 10;
+
+/// A recently opened file, shown by name in the recent-files menu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentFile(pub PathBuf);
+
+impl fmt::Display for RecentFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.file_name() {
+            Some(name) => write!(f, "{}", name.to_string_lossy()),
+            None => write!(f, "{}", self.0.display()),
+        }
+    }
+}
+
+/// Where the list is stored: `recent.json` in the platform config directory.
+fn recent_files_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.config_dir().join("recent.json"))
+}
+
+/// Read the stored list, most recent first, dropping files that no longer exist.
+pub fn load() -> Vec<RecentFile> {
+    let Some(path) = recent_files_path() else {
+        return Vec::new();
+    };
+    let paths: Vec<PathBuf> = match std::fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "ignoring unreadable recent files list");
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    };
+    paths
+        .into_iter()
+        .filter(|p| p.exists())
+        .map(RecentFile)
+        .collect()
+}
+
+/// Move `path` to the front of `recent`, dropping duplicates, missing files
+/// and anything past the limit, then write the list out.
+pub fn add(recent: &mut Vec<RecentFile>, path: PathBuf) -> Result<(), String> {
+    recent.retain(|r| r.0 != path && r.0.exists());
+    recent.insert(0, RecentFile(path));
+    recent.truncate(MAX_RECENT_FILES);
+    save(recent)
+}
+
+fn save(recent: &[RecentFile]) -> Result<(), String> {
+    let path = recent_files_path().ok_or("No config directory available")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create config directory: {e}"))?;
+    }
+    let paths: Vec<&PathBuf> = recent.iter().map(|r| &r.0).collect();
+    let json = serde_json::to_string_pretty(&paths)
+        .map_err(|e| format!("Failed to serialize recent files: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write recent files: {e}"))
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// Where playback of a file was left off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResumePoint {
+    /// Playback position in seconds.
+    pub position: f64,
+    pub tempo: f32,
+    /// Loop start and end in seconds.
+    pub loop_region: Option<(f64, f64)>,
+}
+
+/// Per-file resume points, plus whether they are used at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeState {
+    pub enabled: bool,
+    pub files: HashMap<PathBuf, ResumePoint>,
+    /// Original tempo of each file in BPM. Remembered even with resuming off.
+    #[serde(default)]
+    pub bpms: HashMap<PathBuf, f32>,
+}
+
+impl Default for ResumeState {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            files: HashMap::new(),
+            bpms: HashMap::new(),
+        }
+    }
+}
+
+/// Where the state is stored: `resume.json` in the platform config directory.
+fn resume_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.config_dir().join("resume.json"))
+}
+
+impl ResumeState {
+    /// Read the stored state, dropping files that no longer exist.
+    pub fn load() -> ResumeState {
+        let Some(path) = resume_path() else {
+            return ResumeState::default();
+        };
+        let mut state: ResumeState = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "ignoring unreadable resume positions");
+                ResumeState::default()
+            }),
+            Err(_) => ResumeState::default(),
+        };
+        state.files.retain(|p, _| p.exists());
+        state.bpms.retain(|p, _| p.exists());
+        state
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = resume_path().ok_or("No config directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create config directory: {e}"))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize resume positions: {e}"))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write resume positions: {e}"))
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// A saved practice session: the audio file and the settings used with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub audio_path: PathBuf,
+    pub tempo: f32,
+    /// Pitch shift in semitones.
+    pub pitch: f32,
+    /// Volume slider position.
+    pub volume: f32,
+    /// Loop start and end in seconds.
+    pub loop_region: Option<(f64, f64)>,
+    /// Cue markers as (time in seconds, label).
+    pub markers: Vec<(f64, String)>,
+    /// Tempo of the recording itself, if known. Missing from older sessions.
+    #[serde(default)]
+    pub original_bpm: Option<f32>,
+    /// Seconds the detected beat grid is moved by, so its first beat lands
+    /// on a downbeat. Missing from older sessions.
+    #[serde(default)]
+    pub downbeat_offset: f64,
+}
+
+impl Session {
+    /// Write the session to `path` as JSON.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize session: {e}"))?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write session: {e}"))
+    }
+
+    /// Read a session from a JSON file at `path`.
+    pub fn load(path: &Path) -> Result<Session, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read session: {e}"))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid session file: {e}"))
+    }
+}
+
+/// Markers and stored loops on their own, to share with someone who has the
+/// same recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotations {
+    /// Cue markers as (time in seconds, label).
+    pub markers: Vec<(f64, String)>,
+    /// Stored loop slots as (start, end) in seconds.
+    pub loops: Vec<Option<(f64, f64)>>,
+}
+
+impl Annotations {
+    /// Write the annotations to `path` as JSON.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize markers: {e}"))?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write markers: {e}"))
+    }
+
+    /// Read annotations from a JSON file at `path`.
+    pub fn load(path: &Path) -> Result<Annotations, String> {
+        let json =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read markers: {e}"))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid markers file: {e}"))
+    }
+
+    /// Fit the annotations to a file `duration` seconds long: markers outside
+    /// it are dropped and loops are clamped to it, emptying any slot whose
+    /// loop lies wholly outside. Returns how many entries were changed.
+    pub fn fit_to(&mut self, duration: f64) -> usize {
+        let before = self.markers.len();
+        self.markers
+            .retain(|(time, _)| (0.0..=duration).contains(time));
+        let mut changed = before - self.markers.len();
+        for slot in &mut self.loops {
+            let Some((start, end)) = *slot else {
+                continue;
+            };
+            let clamped = (start.max(0.0), end.min(duration));
+            if clamped != (start, end) {
+                changed += 1;
+                *slot = (clamped.0 < clamped.1).then_some(clamped);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotations() -> Annotations {
+        Annotations {
+            markers: vec![(1.5, "Verse".to_string()), (30.25, "Chorus".to_string())],
+            loops: vec![Some((2.0, 6.5)), None, Some((28.0, 40.0))],
+        }
+    }
+
+    #[test]
+    fn annotations_round_trip_through_a_file() {
+        let path = std::env::temp_dir().join(format!("annotations-{}.json", std::process::id()));
+        annotations().save(&path).unwrap();
+        let read = Annotations::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read.unwrap(), annotations());
+    }
+
+    #[test]
+    fn entries_past_the_end_are_dropped_or_clamped() {
+        let mut imported = annotations();
+        imported.markers.push((-1.0, "Count-in".to_string()));
+        imported.loops.push(Some((50.0, 60.0)));
+        // A 35 s file: the chorus loop runs over, the last loop is past the end
+        assert_eq!(imported.fit_to(35.0), 3);
+        assert_eq!(
+            imported.markers,
+            [(1.5, "Verse".to_string()), (30.25, "Chorus".to_string())]
+        );
+        assert_eq!(
+            imported.loops,
+            [Some((2.0, 6.5)), None, Some((28.0, 35.0)), None]
+        );
+    }
+}
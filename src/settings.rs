@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use iced::Color;
+use serde::{Deserialize, Serialize};
+
+/// Which theme the app uses.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ThemeChoice {
+    #[default]
+    Dark,
+    Light,
+    /// Follow the system's light or dark preference.
+    System,
+}
+
+impl ThemeChoice {
+    pub const ALL: [ThemeChoice; 3] = [ThemeChoice::Dark, ThemeChoice::Light, ThemeChoice::System];
+}
+
+impl fmt::Display for ThemeChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeChoice::Dark => write!(f, "Dark"),
+            ThemeChoice::Light => write!(f, "Light"),
+            ThemeChoice::System => write!(f, "System"),
+        }
+    }
+}
+
+/// How the waveform is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum WaveformStyle {
+    /// A min/max line per pixel column.
+    #[default]
+    Lines,
+    /// A filled shape between the top and bottom envelopes.
+    Filled,
+}
+
+impl WaveformStyle {
+    pub const ALL: [WaveformStyle; 2] = [WaveformStyle::Lines, WaveformStyle::Filled];
+}
+
+impl fmt::Display for WaveformStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaveformStyle::Lines => write!(f, "Lines"),
+            WaveformStyle::Filled => write!(f, "Filled"),
+        }
+    }
+}
+
+/// Colours of the waveform canvas. Stored as `#rrggbb` strings; fainter
+/// shades (the loop fill, beat lines) are drawn from these with transparency.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WaveformColors {
+    #[serde(with = "hex")]
+    pub background: Color,
+    /// Centre line, beat lines and ruler labels.
+    #[serde(with = "hex")]
+    pub foreground: Color,
+    /// Strip behind the time ruler, drawn half transparent.
+    #[serde(with = "hex")]
+    pub ruler: Color,
+    #[serde(with = "hex")]
+    pub waveform: Color,
+    #[serde(with = "hex")]
+    pub rms: Color,
+    #[serde(with = "hex")]
+    pub playhead: Color,
+    #[serde(with = "hex")]
+    pub loop_region: Color,
+    #[serde(with = "hex")]
+    pub marker: Color,
+}
+
+impl WaveformColors {
+    pub const DARK: WaveformColors = WaveformColors {
+        background: Color::from_rgb(0.12, 0.12, 0.15),
+        foreground: Color::WHITE,
+        ruler: Color::BLACK,
+        waveform: Color::from_rgb(0.3, 0.7, 1.0),
+        rms: Color::from_rgb(0.65, 0.88, 1.0),
+        playhead: Color::WHITE,
+        loop_region: Color::from_rgb(1.0, 0.8, 0.0),
+        marker: Color::from_rgb(0.9, 0.4, 0.9),
+    };
+
+    pub const LIGHT: WaveformColors = WaveformColors {
+        background: Color::from_rgb(0.96, 0.96, 0.97),
+        foreground: Color::from_rgb(0.1, 0.1, 0.12),
+        ruler: Color::from_rgb(0.75, 0.75, 0.8),
+        waveform: Color::from_rgb(0.1, 0.4, 0.75),
+        rms: Color::from_rgb(0.05, 0.25, 0.5),
+        playhead: Color::from_rgb(0.1, 0.1, 0.12),
+        loop_region: Color::from_rgb(0.9, 0.55, 0.0),
+        marker: Color::from_rgb(0.7, 0.2, 0.7),
+    };
+}
+
+/// Size and position of the main window when it was last closed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub width: f32,
+    pub height: f32,
+    /// Top-left corner on screen, when the platform reports it.
+    pub position: Option<(f32, f32)>,
+}
+
+/// Default for `Settings::seek_step`.
+pub const DEFAULT_SEEK_STEP: f64 = 5.0;
+
+/// Appearance and preference settings, kept in `settings.json` in the
+/// config directory. Colours can be changed by editing the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: ThemeChoice,
+    pub waveform_style: WaveformStyle,
+    /// Step for the arrow keys and scrolling over the time readout, in seconds.
+    pub seek_step: f64,
+    /// Waveform colours used with the dark theme.
+    pub dark_colors: WaveformColors,
+    /// Waveform colours used with the light theme.
+    pub light_colors: WaveformColors,
+    /// `None` until the window has been closed once.
+    pub window: Option<WindowGeometry>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: ThemeChoice::default(),
+            waveform_style: WaveformStyle::default(),
+            seek_step: DEFAULT_SEEK_STEP,
+            dark_colors: WaveformColors::DARK,
+            light_colors: WaveformColors::LIGHT,
+            window: None,
+        }
+    }
+}
+
+/// Where the settings are stored: `settings.json` in the platform config directory.
+fn settings_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.config_dir().join("settings.json"))
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        let Some(path) = settings_path() else {
+            return Settings::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "ignoring unreadable settings");
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = settings_path().ok_or("No config directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create config directory: {e}"))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write settings: {e}"))
+    }
+
+    /// Waveform colours for a dark or light theme.
+    pub fn colors(&self, dark: bool) -> WaveformColors {
+        if dark {
+            self.dark_colors
+        } else {
+            self.light_colors
+        }
+    }
+}
+
+/// Serde helpers storing a colour as `#rrggbb`.
+mod hex {
+    use iced::Color;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, _] = color.into_rgba8();
+        serializer.serialize_str(&format!("#{r:02x}{g:02x}{b:02x}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let digits = s.strip_prefix('#').unwrap_or(&s);
+        let channel = |i: usize| {
+            digits
+                .get(i..i + 2)
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+        };
+        match (digits.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::from_rgb8(r, g, b)),
+            _ => Err(de::Error::custom(format!(
+                "invalid colour {s:?}, expected #rrggbb"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use iced::widget::{button, container, mouse_area, pin, text, Column};
+use iced::{Element, Length, Point, Size};
+
+use crate::ui::waveform::WaveformMessage;
+
+const MENU_WIDTH: f32 = 170.0;
+const ITEM_HEIGHT: f32 = 26.0;
+const MENU_PADDING: f32 = 4.0;
+
+/// Right-click menu over the waveform, for the spot that was clicked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextMenu {
+    /// Time under the pointer, in seconds.
+    pub time: f64,
+    /// Marker whose flag was clicked, if any.
+    pub marker: Option<usize>,
+    /// Where the menu opens, in canvas coordinates.
+    pub position: Point,
+    /// Size of the canvas, so the menu can be kept inside it.
+    pub bounds: Size,
+}
+
+/// The menu layered over the waveform. Clicking anywhere outside it closes it.
+pub fn view_context_menu(menu: &ContextMenu, has_loop: bool) -> Element<'_, WaveformMessage> {
+    let mut entries = vec![
+        (
+            "Set loop start here",
+            WaveformMessage::SetLoopStart(menu.time),
+        ),
+        ("Set loop end here", WaveformMessage::SetLoopEnd(menu.time)),
+        ("Add marker", WaveformMessage::AddMarker(menu.time)),
+    ];
+    if let Some(index) = menu.marker {
+        entries.push(("Remove marker", WaveformMessage::RemoveMarker(index)));
+    }
+    if has_loop {
+        entries.push(("Clear loop", WaveformMessage::ClearLoop));
+    }
+
+    // Shifted back from the pointer where it would run off the canvas
+    let height = entries.len() as f32 * ITEM_HEIGHT + 2.0 * MENU_PADDING;
+    let x = menu.position.x.min(menu.bounds.width - MENU_WIDTH).max(0.0);
+    let y = menu.position.y.min(menu.bounds.height - height).max(0.0);
+
+    let items = Column::with_children(entries.into_iter().map(|(label, message)| {
+        button(text(label).size(13))
+            .on_press(message)
+            .style(button::text)
+            .width(Length::Fill)
+            .height(Length::Fixed(ITEM_HEIGHT))
+            .into()
+    }));
+
+    let panel = container(items)
+        .padding(MENU_PADDING)
+        .width(Length::Fixed(MENU_WIDTH))
+        .style(container::bordered_box);
+
+    mouse_area(pin(panel).x(x).y(y))
+        .on_press(WaveformMessage::CloseMenu)
+        .on_right_press(WaveformMessage::CloseMenu)
+        .into()
+}
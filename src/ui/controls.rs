@@ -1,77 +1,900 @@
-use iced::widget::{button, container, row, slider, text, Column, Row};
+use iced::mouse::ScrollDelta;
+use iced::widget::{
+    button, checkbox, container, mouse_area, pick_list, row, slider, text, text_input, Column, Row,
+};
 use iced::{Alignment, Element, Length};
 
-use crate::audio::types::PlaybackStatus;
+use crate::audio::decoder::DecodePolicy;
+use crate::audio::export::ExportChannels;
+use crate::audio::filter::{MAX_CUTOFF_HZ, MIN_CUTOFF_HZ};
+use crate::audio::types::{AfterLoops, ChannelMode, LoopMode, PlaybackStatus, StopBehavior};
+use crate::presets::DspPreset;
+use crate::recent::RecentFile;
+use crate::settings::{ThemeChoice, WaveformStyle};
+use crate::ui::format::format_time;
+use crate::ui::meter::{self, MeterReading};
 
 #[derive(Debug, Clone)]
 pub enum ControlMessage {
     PlayPause,
     Stop,
     TempoChanged(f32),
+    /// Pitch shift in semitones.
+    PitchChanged(f32),
+    /// Fine pitch adjustment in cents, on top of the semitones.
+    PitchCentsChanged(f32),
+    /// Volume slider position (0.0 to 1.5).
+    VolumeChanged(f32),
+    ToggleMute,
+    ChannelModeChanged(ChannelMode),
+    /// How much of the centre to remove (0.0 to 1.0).
+    VocalReductionChanged(f32),
+    /// Stereo width (0.0 mono, 1.0 as recorded).
+    StereoWidthChanged(f32),
+    /// Gain of one EQ band in dB.
+    EqChanged(EqBand, f32),
+    /// High-pass cutoff in Hz.
+    HighPassChanged(f32),
+    /// Low-pass cutoff in Hz.
+    LowPassChanged(f32),
+    /// Apply a saved EQ, filter and stereo preset.
+    PresetSelected(DspPreset),
+    PresetNameInput(String),
+    /// Save the current EQ, filter and stereo settings under the typed name.
+    SavePreset,
+    MetronomeToggled(bool),
+    /// Flash a light on each beat.
+    BeatFlashToggled(bool),
+    /// Click level (linear gain).
+    MetronomeGainChanged(f32),
+    BeatsPerBarChanged(u32),
+    /// Bars of count-in before a loop; 0 for none.
+    CountInChanged(u32),
     ClearLoop,
+    /// Switch the time readout between elapsed and remaining time.
+    ToggleTimeDisplay,
+    /// Zoom the waveform so the loop fills it.
+    ZoomToLoop,
+    /// Zoom the waveform out to the whole file.
+    ZoomToFit,
     OpenFile,
+    /// Pick files to add to the end of the queue.
+    AddToQueue,
+    RecentFileSelected(RecentFile),
+    UrlInput(String),
+    /// Download the typed URL and open it.
+    OpenUrl,
+    SaveSession,
+    LoadSession,
+    /// Write the markers and stored loops to a file of their own.
+    ExportAnnotations,
+    /// Replace the markers and stored loops with ones from a file.
+    ImportAnnotations,
+    ExportProcessed,
+    ExportChannelsChanged(ExportChannels),
+    OutputDeviceSelected(String),
+    MidiPortSelected(String),
+    /// The MIDI port list was opened; look for newly connected ports.
+    MidiPortsRefresh,
+    MidiDisconnect,
+    LoopLengthLockToggled(bool),
+    LoopLengthInput(String),
+    LoopModeChanged(LoopMode),
+    LoopSnapToggled(bool),
+    BeatSnapToggled(bool),
+    /// Loop crossfade length in seconds.
+    LoopCrossfadeChanged(f32),
+    /// Step for the loop nudge keys in seconds.
+    LoopNudgeChanged(f64),
+    GoToInput(String),
+    /// Seek to the typed time.
+    GoToSubmitted,
+    /// Back to the original tempo and pitch.
+    ResetTempoPitch,
+    /// Shuttle pushed to this position, from -1 (full rewind) to 1.
+    ShuttleMoved(f32),
+    /// The shuttle was let go and springs back to the centre.
+    ShuttleReleased,
+    /// Step for Shift+arrow seeking in seconds.
+    FineStepChanged(f64),
+    /// Step for arrow-key seeking in seconds.
+    SeekStepChanged(f64),
+    LoopRepeatsInput(String),
+    AfterLoopsChanged(AfterLoops),
+    OriginalBpmInput(String),
+    /// Seconds to move the detected beat grid by.
+    DownbeatOffsetChanged(f64),
+    TargetBpmInput(String),
+    /// Set the tempo so the recording plays at the typed target BPM.
+    TargetBpmSubmitted,
+    TempoRampToggled(bool),
+    TempoRampStartInput(String),
+    TempoRampTargetInput(String),
+    TempoRampStepInput(String),
+    DragThresholdChanged(f32),
+    ShowRmsToggled(bool),
+    DbScaleToggled(bool),
+    FollowPlayheadToggled(bool),
+    WaveformStyleChanged(WaveformStyle),
+    /// Show the note at the playhead.
+    TunerToggled(bool),
+    StopBehaviorChanged(StopBehavior),
+    ThemeChanged(ThemeChoice),
+    ShowStopButtonToggled(bool),
+    /// Honour the file's ReplayGain tags.
+    ReplayGainToggled(bool),
+    ResumeToggled(bool),
+    /// Respond to the keyboard's media keys even without focus.
+    MediaKeysToggled(bool),
+    DecodePolicyChanged(DecodePolicy),
+    MarkerLabelInput(String),
+    /// Loop between the markers either side of the playhead.
+    LoopSection,
+    AddMarker,
+    /// Seek by this many steps (negative is backwards).
+    ScrollSeek(f32),
 }
 
-/// Format seconds as MM:SS.
-fn format_time(seconds: f64) -> String {
-    let total_secs = seconds as u64;
-    let mins = total_secs / 60;
-    let secs = total_secs % 60;
-    format!("{mins}:{secs:02}")
+/// Everything the transport controls need to render.
+pub struct ControlState<'a> {
+    pub status: PlaybackStatus,
+    pub position: f64,
+    pub duration: f64,
+    /// Show the time left rather than the time played.
+    pub show_remaining: bool,
+    /// Output peak and RMS levels.
+    pub meter: MeterReading,
+    pub tempo: f32,
+    /// Shuttle position while it is held, 0 otherwise.
+    pub shuttle: f32,
+    pub pitch: f32,
+    pub pitch_cents: f32,
+    pub volume: f32,
+    pub muted: bool,
+    pub channel_mode: ChannelMode,
+    pub vocal_reduction: f32,
+    pub stereo_width: f32,
+    /// EQ gains in dB.
+    pub eq_low: f32,
+    pub eq_mid: f32,
+    pub eq_high: f32,
+    /// Filter cutoffs in Hz; the ends of the range are off.
+    pub hp_hz: f32,
+    pub lp_hz: f32,
+    pub presets: &'a [DspPreset],
+    pub preset_name_input: &'a str,
+    pub metronome_enabled: bool,
+    pub beat_flash: bool,
+    pub metronome_gain: f32,
+    pub beats_per_bar: u32,
+    pub count_in_bars: u32,
+    /// Loop start and end in seconds, following an edge while it is dragged.
+    pub loop_region: Option<(f64, f64)>,
+    /// The waveform is zoomed in.
+    pub zoomed: bool,
+    pub output_devices: &'a [String],
+    pub output_device: Option<&'a String>,
+    pub midi_ports: &'a [String],
+    /// MIDI input being listened to, if any.
+    pub midi_port: Option<&'a String>,
+    pub loop_length_locked: bool,
+    pub loop_length_input: &'a str,
+    pub loop_mode: LoopMode,
+    pub loop_snap: bool,
+    pub beat_snap: bool,
+    /// Beats have been detected, so loops can snap to them.
+    pub has_beats: bool,
+    pub loop_crossfade: f32,
+    pub loop_nudge: f64,
+    pub fine_step: f64,
+    pub seek_step: f64,
+    pub go_to_input: &'a str,
+    /// Number of loop passes as typed; empty loops forever.
+    pub loop_repeats_input: &'a str,
+    pub after_loops: AfterLoops,
+    /// Tempo of the recording itself, if known.
+    pub original_bpm: Option<f32>,
+    pub original_bpm_input: &'a str,
+    /// Seconds the detected beat grid is moved by.
+    pub downbeat_offset: f64,
+    pub target_bpm_input: &'a str,
+    pub tempo_ramp_enabled: bool,
+    /// Ramp start, target and step as typed, in percent.
+    pub tempo_ramp_start_input: &'a str,
+    pub tempo_ramp_target_input: &'a str,
+    pub tempo_ramp_step_input: &'a str,
+    pub drag_threshold_px: f32,
+    pub show_rms: bool,
+    pub follow_playhead: bool,
+    pub waveform_style: WaveformStyle,
+    pub tuner_enabled: bool,
+    pub db_scale: bool,
+    pub stop_behavior: StopBehavior,
+    pub theme: ThemeChoice,
+    pub show_stop_button: bool,
+    pub replay_gain: bool,
+    /// Reopened files continue from where they were left off.
+    pub resume_enabled: bool,
+    pub media_keys: bool,
+    pub decode_policy: DecodePolicy,
+    pub export_channels: ExportChannels,
+    pub marker_label_input: &'a str,
+    pub recent_files: &'a [RecentFile],
+    pub url_input: &'a str,
+}
+
+/// A band of the three-band EQ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EqBand {
+    Low,
+    Mid,
+    High,
+}
+
+/// Most an EQ band boosts or cuts, in dB.
+pub const MAX_EQ_GAIN: f32 = 12.0;
+
+/// Cutoff slider position (0.0 to 1.0) for a frequency, on a log scale so
+/// each octave gets the same travel.
+fn cutoff_to_slider(hz: f32) -> f32 {
+    (hz / MIN_CUTOFF_HZ).ln() / (MAX_CUTOFF_HZ / MIN_CUTOFF_HZ).ln()
+}
+
+fn slider_to_cutoff(position: f32) -> f32 {
+    MIN_CUTOFF_HZ * (MAX_CUTOFF_HZ / MIN_CUTOFF_HZ).powf(position)
+}
+
+/// A cutoff for display, or "off" at the end of the range where the filter
+/// does nothing.
+fn format_cutoff(hz: f32, off_at: f32) -> String {
+    if hz == off_at {
+        "off".to_string()
+    } else if hz >= 1000.0 {
+        format!("{:.1} kHz", hz / 1000.0)
+    } else {
+        format!("{hz:.0} Hz")
+    }
+}
+
+/// Range of the fine pitch slider either side of zero, in cents.
+const MAX_PITCH_CENTS: f32 = 50.0;
+
+/// Furthest the beat grid can be moved either way, in seconds: a beat at
+/// 30 BPM, so any downbeat is in reach.
+const MAX_DOWNBEAT_OFFSET: f64 = 2.0;
+
+/// Widest the stereo width slider goes.
+const MAX_STEREO_WIDTH: f32 = 2.0;
+
+/// Tempos offered as quick buttons beside the tempo slider.
+const TEMPO_PRESETS: [f32; 4] = [0.5, 0.75, 1.0, 1.5];
+
+/// Bar lengths offered for the metronome accent.
+const BEATS_PER_BAR_CHOICES: [u32; 7] = [1, 2, 3, 4, 5, 6, 7];
+/// Count-in lengths in bars.
+const COUNT_IN_CHOICES: [u32; 5] = [0, 1, 2, 3, 4];
+
+/// Range of the tempo slider, as playback speed ratios.
+pub const MIN_TEMPO: f32 = 0.25;
+pub const MAX_TEMPO: f32 = 2.0;
+
+/// Fastest shuttle speed, as a multiple of the tempo.
+const MAX_SHUTTLE_SPEED: f32 = 4.0;
+/// Shuttle positions this close to the centre leave playback as it is.
+const SHUTTLE_DEAD_ZONE: f32 = 0.05;
+
+/// Speed for a shuttle position: 0 (normal playback) around the centre,
+/// then from 1× to `MAX_SHUTTLE_SPEED`, negative (backwards) to the left.
+pub fn shuttle_speed(position: f32) -> f32 {
+    if position.abs() < SHUTTLE_DEAD_ZONE {
+        return 0.0;
+    }
+    let t = (position.abs() - SHUTTLE_DEAD_ZONE) / (1.0 - SHUTTLE_DEAD_ZONE);
+    (1.0 + t * (MAX_SHUTTLE_SPEED - 1.0)).copysign(position)
+}
+
+/// Linear gain for a volume slider position. Squaring gives the slider a
+/// roughly perceptual feel.
+pub fn volume_gain(volume: f32) -> f32 {
+    volume * volume
+}
+
+/// Format a linear gain as decibels.
+fn format_db(gain: f32) -> String {
+    if gain <= 0.0 {
+        "-inf dB".to_string()
+    } else {
+        format!("{:+.1} dB", 20.0 * gain.log10())
+    }
+}
+
+/// Pixels of smooth (touchpad) scrolling that count as one seek step.
+const PIXELS_PER_SCROLL_STEP: f32 = 40.0;
+
+fn scroll_steps(delta: ScrollDelta) -> f32 {
+    match delta {
+        ScrollDelta::Lines { y, .. } => y,
+        ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_SCROLL_STEP,
+    }
 }
 
 /// Build the transport controls view.
-pub fn view_controls<'a>(
-    status: PlaybackStatus,
-    position: f64,
-    duration: f64,
-    tempo: f32,
-    has_loop: bool,
-) -> Element<'a, ControlMessage> {
-    let play_label = match status {
-        PlaybackStatus::Playing => "Pause",
-        _ => "Play",
+pub fn view_controls(state: ControlState<'_>) -> Element<'_, ControlMessage> {
+    let ControlState {
+        status,
+        position,
+        duration,
+        show_remaining,
+        meter,
+        tempo,
+        shuttle,
+        pitch,
+        pitch_cents,
+        volume,
+        muted,
+        channel_mode,
+        vocal_reduction,
+        stereo_width,
+        eq_low,
+        eq_mid,
+        eq_high,
+        hp_hz,
+        lp_hz,
+        presets,
+        preset_name_input,
+        metronome_enabled,
+        beat_flash,
+        metronome_gain,
+        beats_per_bar,
+        count_in_bars,
+        loop_region,
+        zoomed,
+        output_devices,
+        output_device,
+        midi_ports,
+        midi_port,
+        loop_length_locked,
+        loop_length_input,
+        loop_mode,
+        loop_snap,
+        beat_snap,
+        has_beats,
+        loop_crossfade,
+        loop_nudge,
+        fine_step,
+        seek_step,
+        go_to_input,
+        loop_repeats_input,
+        after_loops,
+        original_bpm,
+        original_bpm_input,
+        downbeat_offset,
+        target_bpm_input,
+        tempo_ramp_enabled,
+        tempo_ramp_start_input,
+        tempo_ramp_target_input,
+        tempo_ramp_step_input,
+        drag_threshold_px,
+        show_rms,
+        follow_playhead,
+        waveform_style,
+        tuner_enabled,
+        db_scale,
+        stop_behavior,
+        theme,
+        show_stop_button,
+        replay_gain,
+        resume_enabled,
+        media_keys,
+        decode_policy,
+        export_channels,
+        marker_label_input,
+        recent_files,
+        url_input,
+    } = state;
+
+    // Without a Stop button, the play button cycles Play → Pause → Stop
+    let (play_label, play_msg) = match status {
+        PlaybackStatus::Playing => ("Pause", ControlMessage::PlayPause),
+        PlaybackStatus::Paused if !show_stop_button => ("Stop", ControlMessage::Stop),
+        _ => ("Play", ControlMessage::PlayPause),
     };
 
-    let play_btn = button(text(play_label)).on_press(ControlMessage::PlayPause);
-    let stop_btn = button(text("Stop")).on_press(ControlMessage::Stop);
+    let play_btn = button(text(play_label)).on_press(play_msg);
     let open_btn = button(text("Open File")).on_press(ControlMessage::OpenFile);
+    let queue_btn = button(text("Add to Queue")).on_press(ControlMessage::AddToQueue);
+    let recent_picker = pick_list(
+        recent_files,
+        None::<RecentFile>,
+        ControlMessage::RecentFileSelected,
+    )
+    .placeholder("Recent files")
+    .width(Length::Fixed(160.0));
+    let url_field = text_input("Open URL", url_input)
+        .on_input(ControlMessage::UrlInput)
+        .on_submit(ControlMessage::OpenUrl)
+        .width(Length::Fixed(160.0));
+    let save_session_btn = button(text("Save Session")).on_press(ControlMessage::SaveSession);
+    let load_session_btn = button(text("Load Session")).on_press(ControlMessage::LoadSession);
+    let export_btn = button(text("Export")).on_press(ControlMessage::ExportProcessed);
+    let export_channels_picker = pick_list(
+        &ExportChannels::ALL[..],
+        Some(export_channels),
+        ControlMessage::ExportChannelsChanged,
+    );
 
-    let time_display = text(format!(
-        "{} / {}",
-        format_time(position),
-        format_time(duration)
-    ))
-    .size(16);
+    let shown_time = if show_remaining {
+        format!("-{}", format_time((duration - position).max(0.0)))
+    } else {
+        format_time(position)
+    };
+    // Clicking the time readout switches it; scrolling over it nudges the position
+    let time_display =
+        mouse_area(text(format!("{shown_time} / {}", format_time(duration))).size(16))
+            .on_press(ControlMessage::ToggleTimeDisplay)
+            .on_scroll(|delta| ControlMessage::ScrollSeek(scroll_steps(delta)));
 
-    let tempo_label = text(format!("Tempo: {:.0}%", tempo * 100.0)).size(14);
-    let tempo_slider = slider(0.25..=2.0, tempo, ControlMessage::TempoChanged).step(0.05);
+    let tempo_label = match original_bpm {
+        Some(bpm) => text(format!(
+            "Tempo: {:.0}% ({:.0} BPM)",
+            tempo * 100.0,
+            bpm * tempo
+        )),
+        None => text(format!("Tempo: {:.0}%", tempo * 100.0)),
+    }
+    .size(14);
+    let tempo_slider =
+        slider(MIN_TEMPO..=MAX_TEMPO, tempo, ControlMessage::TempoChanged).step(0.05);
+
+    // Springs back to the centre when let go
+    let speed = shuttle_speed(shuttle);
+    let shuttle_label = if speed == 0.0 {
+        text("Shuttle").size(14)
+    } else {
+        text(format!("Shuttle: {speed:+.1}×")).size(14)
+    };
+    let shuttle_slider = slider(-1.0..=1.0, shuttle, ControlMessage::ShuttleMoved)
+        .step(0.01)
+        .on_release(ControlMessage::ShuttleReleased)
+        .width(Length::Fixed(120.0));
+
+    let pitch_label = text(format!("Pitch: {pitch:+.0} st")).size(14);
+    let pitch_slider = slider(-12.0..=12.0, pitch, ControlMessage::PitchChanged).step(1.0);
+    let cents_label = text(format!("{pitch_cents:+.0} c")).size(14);
+    let cents_slider = slider(
+        -MAX_PITCH_CENTS..=MAX_PITCH_CENTS,
+        pitch_cents,
+        ControlMessage::PitchCentsChanged,
+    )
+    .step(1.0)
+    .width(Length::Fixed(80.0));
+
+    let volume_label = text(format!("Volume: {}", format_db(volume_gain(volume)))).size(14);
+    let volume_slider = slider(0.0..=1.5, volume, ControlMessage::VolumeChanged).step(0.01);
 
     let mut controls_row = Row::new()
         .spacing(10)
         .align_y(Alignment::Center)
         .push(open_btn)
-        .push(play_btn)
-        .push(stop_btn)
-        .push(time_display);
+        .push(queue_btn)
+        .push(recent_picker)
+        .push(url_field)
+        .push(save_session_btn)
+        .push(load_session_btn)
+        .push(export_btn)
+        .push(export_channels_picker)
+        .push(play_btn);
+
+    if show_stop_button {
+        controls_row = controls_row.push(button(text("Stop")).on_press(ControlMessage::Stop));
+    }
+
+    controls_row = controls_row.push(time_display).push(
+        text_input("Go to M:SS", go_to_input)
+            .on_input(ControlMessage::GoToInput)
+            .on_submit(ControlMessage::GoToSubmitted)
+            .width(Length::Fixed(100.0)),
+    );
 
-    if has_loop {
+    if let Some((start, end)) = loop_region {
+        controls_row = controls_row
+            .push(
+                text(format!(
+                    "Loop {} – {} ({:.1}s)",
+                    format_time(start),
+                    format_time(end),
+                    end - start
+                ))
+                .size(14),
+            )
+            .push(button(text("Zoom to Loop")).on_press(ControlMessage::ZoomToLoop))
+            .push(button(text("Clear Loop")).on_press(ControlMessage::ClearLoop));
+    }
+
+    if zoomed {
         controls_row =
-            controls_row.push(button(text("Clear Loop")).on_press(ControlMessage::ClearLoop));
+            controls_row.push(button(text("Zoom to Fit")).on_press(ControlMessage::ZoomToFit));
     }
 
     let tempo_row = row![tempo_label, tempo_slider]
         .spacing(10)
         .align_y(Alignment::Center)
-        .width(Length::Fixed(300.0));
+        .width(Length::Fixed(340.0));
+
+    let pitch_row = row![pitch_label, pitch_slider, cents_label, cents_slider]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .width(Length::Fixed(370.0));
+
+    let mute_btn =
+        button(text(if muted { "Unmute" } else { "Mute" })).on_press(ControlMessage::ToggleMute);
+
+    let volume_row = row![mute_btn, volume_label, volume_slider]
+        .spacing(10)
+        .align_y(Alignment::Center)
+        .width(Length::Fixed(330.0));
+
+    let device_picker = pick_list(
+        output_devices,
+        output_device,
+        ControlMessage::OutputDeviceSelected,
+    )
+    .placeholder("Default output")
+    .width(Length::Fixed(200.0));
+
+    let midi_picker = pick_list(midi_ports, midi_port, ControlMessage::MidiPortSelected)
+        .placeholder("No MIDI input")
+        .on_open(ControlMessage::MidiPortsRefresh)
+        .width(Length::Fixed(160.0));
+    let midi_off_btn = button(text("Off"))
+        .on_press_maybe(
+            midi_port
+                .is_some()
+                .then_some(ControlMessage::MidiDisconnect),
+        )
+        .style(button::secondary);
 
-    let full_row = Row::new()
+    // Segmented control: the selected channel mode is highlighted
+    let channel_row = ChannelMode::ALL
+        .iter()
+        .fold(Row::new().spacing(2), |row, &mode| {
+            let style = if mode == channel_mode {
+                button::primary
+            } else {
+                button::secondary
+            };
+            row.push(
+                button(text(mode.to_string()).size(12))
+                    .style(style)
+                    .on_press(ControlMessage::ChannelModeChanged(mode)),
+            )
+        });
+
+    // One-click tempos, the current one highlighted, then Reset (also the 0 key)
+    let tempo_presets = TEMPO_PRESETS
+        .iter()
+        .fold(Row::new().spacing(2), |row, &preset| {
+            let style = if (tempo - preset).abs() < 0.005 {
+                button::primary
+            } else {
+                button::secondary
+            };
+            row.push(
+                button(text(format!("{:.0}%", preset * 100.0)).size(12))
+                    .style(style)
+                    .on_press(ControlMessage::TempoChanged(preset)),
+            )
+        })
+        .push(
+            button(text("Reset").size(12))
+                .style(button::secondary)
+                .on_press(ControlMessage::ResetTempoPitch),
+        );
+
+    let sliders_row = Row::new()
         .spacing(20)
         .align_y(Alignment::Center)
-        .push(controls_row)
-        .push(tempo_row);
+        .push(tempo_row)
+        .push(tempo_presets)
+        .push(
+            row![shuttle_label, shuttle_slider]
+                .spacing(10)
+                .align_y(Alignment::Center),
+        )
+        .push(pitch_row)
+        .push(volume_row)
+        .push(meter::view_meter(meter));
+
+    let mix_row = row![
+        text("Channels:").size(14),
+        channel_row,
+        text(format!("Vocal reduction: {:.0}%", vocal_reduction * 100.0)).size(14),
+        slider(
+            0.0..=1.0,
+            vocal_reduction,
+            ControlMessage::VocalReductionChanged
+        )
+        .step(0.05)
+        .width(Length::Fixed(150.0)),
+        text(format!("Width: {:.0}%", stereo_width * 100.0)).size(14),
+        slider(
+            0.0..=MAX_STEREO_WIDTH,
+            stereo_width,
+            ControlMessage::StereoWidthChanged
+        )
+        .step(0.05)
+        .width(Length::Fixed(100.0)),
+        checkbox(metronome_enabled)
+            .label("Metronome")
+            .on_toggle(ControlMessage::MetronomeToggled),
+        slider(
+            0.0..=1.0,
+            metronome_gain,
+            ControlMessage::MetronomeGainChanged
+        )
+        .step(0.05)
+        .width(Length::Fixed(100.0)),
+        pick_list(
+            &BEATS_PER_BAR_CHOICES[..],
+            Some(beats_per_bar),
+            ControlMessage::BeatsPerBarChanged
+        ),
+        text("beats per bar").size(14),
+        text("Count-in:").size(14),
+        pick_list(
+            &COUNT_IN_CHOICES[..],
+            Some(count_in_bars),
+            ControlMessage::CountInChanged
+        ),
+        text("bars").size(14),
+        checkbox(beat_flash)
+            .label("Beat flash")
+            .on_toggle(ControlMessage::BeatFlashToggled),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let eq_slider = |label: &str, gain: f32, band: EqBand| {
+        row![
+            text(format!("{label}: {gain:+.0} dB")).size(14),
+            slider(-MAX_EQ_GAIN..=MAX_EQ_GAIN, gain, move |g| {
+                ControlMessage::EqChanged(band, g)
+            })
+            .step(1.0)
+            .width(Length::Fixed(100.0)),
+        ]
+        .spacing(5)
+        .align_y(Alignment::Center)
+    };
+    let filter_row = row![
+        text("EQ").size(14),
+        eq_slider("Low", eq_low, EqBand::Low),
+        eq_slider("Mid", eq_mid, EqBand::Mid),
+        eq_slider("High", eq_high, EqBand::High),
+        text(format!(
+            "High-pass: {}",
+            format_cutoff(hp_hz, MIN_CUTOFF_HZ)
+        ))
+        .size(14),
+        slider(0.0..=1.0, cutoff_to_slider(hp_hz), |p| {
+            ControlMessage::HighPassChanged(slider_to_cutoff(p))
+        })
+        .step(0.005)
+        .width(Length::Fixed(120.0)),
+        text(format!("Low-pass: {}", format_cutoff(lp_hz, MAX_CUTOFF_HZ))).size(14),
+        slider(0.0..=1.0, cutoff_to_slider(lp_hz), |p| {
+            ControlMessage::LowPassChanged(slider_to_cutoff(p))
+        })
+        .step(0.005)
+        .width(Length::Fixed(120.0)),
+        pick_list(presets, None::<DspPreset>, ControlMessage::PresetSelected)
+            .placeholder("Presets")
+            .width(Length::Fixed(140.0)),
+        text_input("preset name", preset_name_input)
+            .on_input(ControlMessage::PresetNameInput)
+            .on_submit(ControlMessage::SavePreset)
+            .width(Length::Fixed(120.0)),
+        button(text("Save Preset")).on_press(ControlMessage::SavePreset),
+    ]
+    .spacing(15)
+    .align_y(Alignment::Center);
+
+    let loop_length_row = row![
+        checkbox(loop_length_locked)
+            .label("Lock loop length")
+            .on_toggle(ControlMessage::LoopLengthLockToggled),
+        text_input("seconds", loop_length_input)
+            .on_input(ControlMessage::LoopLengthInput)
+            .width(Length::Fixed(80.0)),
+        text("s").size(14),
+        text("Loop mode:").size(14),
+        pick_list(
+            &LoopMode::ALL[..],
+            Some(loop_mode),
+            ControlMessage::LoopModeChanged
+        ),
+        checkbox(loop_snap)
+            .label("Snap to zero crossings")
+            .on_toggle(ControlMessage::LoopSnapToggled),
+        checkbox(beat_snap && has_beats)
+            .label("Snap to beats")
+            .on_toggle_maybe(has_beats.then_some(ControlMessage::BeatSnapToggled)),
+        text(format!("Drag threshold: {drag_threshold_px:.0} px")).size(14),
+        slider(
+            1.0..=20.0,
+            drag_threshold_px,
+            ControlMessage::DragThresholdChanged
+        )
+        .step(1.0)
+        .width(Length::Fixed(100.0)),
+        text("Waveform:").size(14),
+        pick_list(
+            &WaveformStyle::ALL[..],
+            Some(waveform_style),
+            ControlMessage::WaveformStyleChanged
+        ),
+        checkbox(show_rms)
+            .label("Show RMS")
+            .on_toggle(ControlMessage::ShowRmsToggled),
+        checkbox(db_scale)
+            .label("dB scale")
+            .on_toggle(ControlMessage::DbScaleToggled),
+        checkbox(follow_playhead)
+            .label("Follow playhead")
+            .on_toggle(ControlMessage::FollowPlayheadToggled),
+        checkbox(tuner_enabled)
+            .label("Tuner")
+            .on_toggle(ControlMessage::TunerToggled),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let settings_row = row![
+        text("Output:").size(14),
+        device_picker,
+        text("MIDI:").size(14),
+        midi_picker,
+        midi_off_btn,
+        text("Stop:").size(14),
+        pick_list(
+            &StopBehavior::ALL[..],
+            Some(stop_behavior),
+            ControlMessage::StopBehaviorChanged
+        ),
+        checkbox(show_stop_button)
+            .label("Stop button")
+            .on_toggle(ControlMessage::ShowStopButtonToggled),
+        checkbox(replay_gain)
+            .label("ReplayGain")
+            .on_toggle(ControlMessage::ReplayGainToggled),
+        checkbox(resume_enabled)
+            .label("Resume where I left off")
+            .on_toggle(ControlMessage::ResumeToggled),
+        checkbox(media_keys)
+            .label("Media keys")
+            .on_toggle(ControlMessage::MediaKeysToggled),
+        text("Theme:").size(14),
+        pick_list(
+            &ThemeChoice::ALL[..],
+            Some(theme),
+            ControlMessage::ThemeChanged
+        ),
+        text("Damaged packets:").size(14),
+        pick_list(
+            &DecodePolicy::ALL[..],
+            Some(decode_policy),
+            ControlMessage::DecodePolicyChanged
+        ),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let loop_edit_row = row![
+        text(format!("Crossfade: {:.0} ms", loop_crossfade * 1000.0)).size(14),
+        slider(
+            0.0..=0.05,
+            loop_crossfade,
+            ControlMessage::LoopCrossfadeChanged
+        )
+        .step(0.001)
+        .width(Length::Fixed(100.0)),
+        text(format!("Nudge: {:.0} ms", loop_nudge * 1000.0)).size(14),
+        slider(0.001..=0.1, loop_nudge, ControlMessage::LoopNudgeChanged)
+            .step(0.001)
+            .width(Length::Fixed(100.0)),
+        text("Play loop").size(14),
+        text_input("∞", loop_repeats_input)
+            .on_input(ControlMessage::LoopRepeatsInput)
+            .width(Length::Fixed(50.0)),
+        text("times, then").size(14),
+        pick_list(
+            &AfterLoops::ALL[..],
+            Some(after_loops),
+            ControlMessage::AfterLoopsChanged
+        ),
+        text(", and . nudge the loop start, < and > the end").size(12),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let ramp_row = row![
+        checkbox(tempo_ramp_enabled)
+            .label("Tempo ramp")
+            .on_toggle(ControlMessage::TempoRampToggled),
+        text("from").size(14),
+        text_input("%", tempo_ramp_start_input)
+            .on_input(ControlMessage::TempoRampStartInput)
+            .width(Length::Fixed(50.0)),
+        text("% to").size(14),
+        text_input("%", tempo_ramp_target_input)
+            .on_input(ControlMessage::TempoRampTargetInput)
+            .width(Length::Fixed(50.0)),
+        text("% in steps of").size(14),
+        text_input("%", tempo_ramp_step_input)
+            .on_input(ControlMessage::TempoRampStepInput)
+            .width(Length::Fixed(50.0)),
+        text("% per loop").size(14),
+        text("Original BPM:").size(14),
+        text_input("BPM", original_bpm_input)
+            .on_input(ControlMessage::OriginalBpmInput)
+            .width(Length::Fixed(60.0)),
+        text("Play at BPM:").size(14),
+        text_input("BPM", target_bpm_input)
+            .on_input(ControlMessage::TargetBpmInput)
+            .on_submit(ControlMessage::TargetBpmSubmitted)
+            .width(Length::Fixed(60.0)),
+        text(format!("Beat 1 offset: {downbeat_offset:+.3} s")).size(14),
+        slider(
+            -MAX_DOWNBEAT_OFFSET..=MAX_DOWNBEAT_OFFSET,
+            downbeat_offset,
+            ControlMessage::DownbeatOffsetChanged
+        )
+        .step(0.005)
+        .width(Length::Fixed(120.0)),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let marker_row = row![
+        text_input("marker label", marker_label_input)
+            .on_input(ControlMessage::MarkerLabelInput)
+            .on_submit(ControlMessage::AddMarker)
+            .width(Length::Fixed(150.0)),
+        button(text("Add Marker")).on_press(ControlMessage::AddMarker),
+        button(text("Loop Section")).on_press(ControlMessage::LoopSection),
+        button(text("Export Markers")).on_press(ControlMessage::ExportAnnotations),
+        button(text("Import Markers")).on_press(ControlMessage::ImportAnnotations),
+        text(
+            "B adds a marker, [ and ] jump between markers, L loops between them, \
+             right-click a flag to remove it"
+        )
+        .size(12),
+        text(format!("Seek step: {seek_step:.0} s")).size(14),
+        slider(1.0..=60.0, seek_step, ControlMessage::SeekStepChanged)
+            .step(1.0)
+            .width(Length::Fixed(100.0)),
+        text(format!("Fine step: {:.0} ms", fine_step * 1000.0)).size(14),
+        slider(0.01..=1.0, fine_step, ControlMessage::FineStepChanged)
+            .step(0.01)
+            .width(Length::Fixed(100.0)),
+        text("Arrows: seek step; Alt: 1 s; Shift: fine step; Ctrl+Shift: one sample").size(12),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
 
-    container(Column::new().push(full_row))
-        .padding(10)
-        .into()
+    container(
+        Column::new()
+            .spacing(5)
+            .push(controls_row)
+            .push(sliders_row)
+            .push(mix_row)
+            .push(filter_row)
+            .push(loop_length_row)
+            .push(loop_edit_row)
+            .push(ramp_row)
+            .push(settings_row)
+            .push(marker_row),
+    )
+    .padding(10)
+    .into()
 }
@@ -0,0 +1,36 @@
+/// Format seconds as MM:SS.
+pub fn format_time(seconds: f64) -> String {
+    let total_secs = seconds as u64;
+    let mins = total_secs / 60;
+    let secs = total_secs % 60;
+    format!("{mins}:{secs:02}")
+}
+
+/// Parse a typed time as seconds: `SS`, `M:SS` or `H:MM:SS`, each with
+/// optional fractional seconds.
+pub fn parse_time(input: &str) -> Result<f64, String> {
+    let invalid = || format!("\"{}\" isn't a time; use M:SS or M:SS.mmm", input.trim());
+    let parts: Vec<&str> = input.trim().split(':').map(str::trim).collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let (seconds, whole) = parts.split_last().ok_or_else(invalid)?;
+    let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(invalid());
+    }
+    // Hours then minutes, each worth 60 of the next
+    let mut total = 0.0;
+    for part in whole {
+        let value: u64 = part.parse().map_err(|_| invalid())?;
+        total = (total + value as f64) * 60.0;
+    }
+    Ok(total + seconds)
+}
+
+/// Format seconds as MM:SS.mmm.
+pub fn format_time_precise(seconds: f64) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    format!("{}.{ms:03}", format_time((total_ms / 1000) as f64))
+}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use iced::widget::{progress_bar, row, text};
+use iced::{Alignment, Element, Length};
+
+/// Window of the short-term level.
+const SHORT_TERM_WINDOW: Duration = Duration::from_secs(3);
+/// How long the loudest peak stays on the bar.
+const PEAK_HOLD: Duration = Duration::from_millis(500);
+/// Blocks quieter than this are left out of the integrated level, so pauses
+/// in the music don't drag it down.
+const SILENCE_GATE_DB: f32 = -70.0;
+/// Bottom of the peak bar in dBFS.
+const METER_FLOOR_DB: f32 = -60.0;
+
+/// One block of output levels as reported by the engine.
+#[derive(Debug, Clone, Copy)]
+struct LevelBlock {
+    received: Instant,
+    peak: f32,
+    /// Sum over frames of the squared samples of all channels.
+    sum_squares: f64,
+    frames: usize,
+}
+
+/// Collects the engine's level reports into peak and RMS readings. The RMS
+/// is unweighted, so it is a level rather than a loudness.
+#[derive(Debug, Default)]
+pub struct LevelMeter {
+    recent: VecDeque<LevelBlock>,
+    integrated_sum: f64,
+    integrated_frames: u64,
+}
+
+/// What the meter shows; `None` where there is nothing to measure yet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeterReading {
+    /// Highest recent sample in dBFS.
+    pub peak_db: Option<f32>,
+    /// RMS level over the last few seconds, in dB.
+    pub short_term_db: Option<f32>,
+    /// RMS level of everything played since the file was loaded, in dB.
+    pub integrated_db: Option<f32>,
+}
+
+/// Level in dB of a mean square summed over channels; `None` for silence,
+/// or for no audio at all (0/0).
+fn rms_db(mean_square: f64) -> Option<f32> {
+    (mean_square > 0.0).then(|| 10.0 * mean_square.log10() as f32)
+}
+
+impl LevelMeter {
+    pub fn push(&mut self, peak: f32, sum_squares: f64, frames: usize) {
+        if frames == 0 {
+            return;
+        }
+        let now = Instant::now();
+        while self
+            .recent
+            .front()
+            .is_some_and(|b| now.duration_since(b.received) > SHORT_TERM_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(LevelBlock {
+            received: now,
+            peak,
+            sum_squares,
+            frames,
+        });
+        if rms_db(sum_squares / frames as f64).is_some_and(|l| l > SILENCE_GATE_DB) {
+            self.integrated_sum += sum_squares;
+            self.integrated_frames += frames as u64;
+        }
+    }
+
+    /// Start measuring afresh, for a new file.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn reading(&self) -> MeterReading {
+        let now = Instant::now();
+        let age = |b: &&LevelBlock| now.duration_since(b.received);
+        let peak = self
+            .recent
+            .iter()
+            .filter(|b| age(b) <= PEAK_HOLD)
+            .map(|b| b.peak)
+            .reduce(f32::max);
+        let (sum, frames) = self
+            .recent
+            .iter()
+            .filter(|b| age(b) <= SHORT_TERM_WINDOW)
+            .fold((0.0, 0), |(sum, frames), b| {
+                (sum + b.sum_squares, frames + b.frames)
+            });
+        MeterReading {
+            peak_db: peak.filter(|p| *p > 0.0).map(|p| 20.0 * p.log10()),
+            short_term_db: rms_db(sum / frames as f64),
+            integrated_db: rms_db(self.integrated_sum / self.integrated_frames as f64),
+        }
+    }
+}
+
+/// The peak bar with its level, then the short-term and integrated RMS.
+pub fn view_meter<'a, Message: 'a>(reading: MeterReading) -> Element<'a, Message> {
+    let format_level = |level: Option<f32>, unit: &str| match level {
+        Some(l) => format!("{l:.1} {unit}"),
+        None => "—".to_string(),
+    };
+    let peak = reading
+        .peak_db
+        .unwrap_or(METER_FLOOR_DB)
+        .max(METER_FLOOR_DB);
+    row![
+        text("Peak:").size(14),
+        progress_bar(METER_FLOOR_DB..=0.0, peak)
+            .length(Length::Fixed(80.0))
+            .girth(Length::Fixed(8.0)),
+        text(format_level(reading.peak_db, "dB"))
+            .size(12)
+            .width(Length::Fixed(60.0)),
+        text(format!(
+            "Short-term: {}",
+            format_level(reading.short_term_db, "dB RMS")
+        ))
+        .size(12),
+        text(format!(
+            "Integrated: {}",
+            format_level(reading.integrated_db, "dB RMS")
+        ))
+        .size(12),
+    ]
+    .spacing(8)
+    .align_y(Alignment::Center)
+    .into()
+}
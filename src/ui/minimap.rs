@@ -0,0 +1,180 @@
+use iced::mouse;
+use iced::widget::canvas::{self, Action, Event, Frame, Geometry, Path, Stroke};
+use iced::{Element, Length, Rectangle, Renderer, Theme};
+
+use crate::ui::waveform::{WaveformMessage, WaveformView};
+
+/// Height of the overview strip under the waveform.
+const MINIMAP_HEIGHT: f32 = 40.0;
+
+/// The whole file in miniature, with the part shown in the waveform above
+/// outlined. Dragging the outline pans the waveform; clicking elsewhere
+/// centres it on that spot.
+struct Minimap<'a> {
+    view: &'a WaveformView,
+}
+
+/// Pointer state the minimap keeps between events.
+#[derive(Default)]
+struct MinimapState {
+    /// Where in the viewport the pointer grabbed it, as a file fraction
+    /// from its left edge, while dragging.
+    grab: Option<f64>,
+}
+
+impl Minimap<'_> {
+    /// The scroll offset that puts the viewport's grabbed point under `x`.
+    fn offset_for(&self, x: f32, width: f32, grab: f64) -> f64 {
+        (x / width).clamp(0.0, 1.0) as f64 - grab
+    }
+}
+
+impl canvas::Program<WaveformMessage> for Minimap<'_> {
+    type State = MinimapState;
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let width = bounds.width;
+        let height = bounds.height;
+        let colors = self.view.colors();
+
+        // Only changes with the file or the colours, not with the view
+        let overview = self
+            .view
+            .minimap_cache()
+            .draw(renderer, bounds.size(), |frame| {
+                frame.fill_rectangle(iced::Point::ORIGIN, bounds.size(), colors.background);
+                let Some(peaks) = &self.view.peaks else {
+                    return;
+                };
+                let center_y = height / 2.0;
+                let columns = Path::new(|builder| {
+                    let display_peaks =
+                        peaks.peaks_for_width(width, self.view.total_frames, (0.0, 1.0));
+                    for (i, peak) in display_peaks.iter().enumerate() {
+                        let x = i as f32;
+                        let top = center_y - peak.max.min(1.0) * center_y;
+                        let bottom = center_y - peak.min.max(-1.0) * center_y;
+                        builder.move_to(iced::Point::new(x, top));
+                        builder.line_to(iced::Point::new(x, bottom));
+                    }
+                });
+                frame.stroke(
+                    &columns,
+                    Stroke::default()
+                        .with_color(colors.waveform.scale_alpha(0.6))
+                        .with_width(1.0),
+                );
+            });
+
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        if let Some((start, end)) = self.view.loop_region {
+            frame.fill_rectangle(
+                iced::Point::new(start as f32 * width, 0.0),
+                iced::Size::new((end - start) as f32 * width, height),
+                colors.loop_region.scale_alpha(0.2),
+            );
+        }
+
+        let (start, end) = self.view.visible_window();
+        let viewport = Path::rectangle(
+            iced::Point::new(start as f32 * width, 0.5),
+            iced::Size::new(((end - start) as f32 * width).max(2.0), height - 1.0),
+        );
+        frame.fill(&viewport, colors.foreground.scale_alpha(0.1));
+        frame.stroke(
+            &viewport,
+            Stroke::default()
+                .with_color(colors.foreground.scale_alpha(0.7))
+                .with_width(1.0),
+        );
+
+        let playhead_x = self.view.playback_position as f32 * width;
+        frame.stroke(
+            &Path::line(
+                iced::Point::new(playhead_x, 0.0),
+                iced::Point::new(playhead_x, height),
+            ),
+            Stroke::default()
+                .with_color(colors.playhead)
+                .with_width(1.0),
+        );
+
+        vec![overview, frame.into_geometry()]
+    }
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Option<Action<WaveformMessage>> {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let pos = cursor.position_in(bounds)?;
+                let (start, end) = self.view.visible_window();
+                let frac = (pos.x / bounds.width) as f64;
+                // Grabbing outside the viewport jumps it there, centred
+                let grab = if (start..=end).contains(&frac) {
+                    frac - start
+                } else {
+                    (end - start) / 2.0
+                };
+                state.grab = Some(grab);
+                let offset = self.offset_for(pos.x, bounds.width, grab);
+                Some(Action::publish(WaveformMessage::ScrollTo(offset)).and_capture())
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                // Keeps panning when the pointer strays off the strip
+                let grab = state.grab?;
+                let pos = cursor.position()?;
+                let x = pos.x - bounds.x;
+                let offset = self.offset_for(x, bounds.width, grab);
+                Some(Action::publish(WaveformMessage::ScrollTo(offset)).and_capture())
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                state.grab.take().map(|_| Action::capture())
+            }
+            _ => None,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if state.grab.is_some() {
+            return mouse::Interaction::Grabbing;
+        }
+        match cursor.position_in(bounds) {
+            Some(pos) => {
+                let (start, end) = self.view.visible_window();
+                let frac = (pos.x / bounds.width) as f64;
+                if (start..=end).contains(&frac) {
+                    mouse::Interaction::Grab
+                } else {
+                    mouse::Interaction::Pointer
+                }
+            }
+            None => mouse::Interaction::default(),
+        }
+    }
+}
+
+/// The overview strip for `view`, to go under the waveform canvas.
+pub fn view_minimap(view: &WaveformView) -> Element<'_, WaveformMessage> {
+    canvas::Canvas::new(Minimap { view })
+        .width(Length::Fill)
+        .height(Length::Fixed(MINIMAP_HEIGHT))
+        .into()
+}
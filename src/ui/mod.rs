@@ -1,2 +1,8 @@
+pub mod context_menu;
 pub mod controls;
+pub mod format;
+pub mod meter;
+pub mod minimap;
+pub mod playlist;
+pub mod tuner;
 pub mod waveform;
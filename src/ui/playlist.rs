@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use iced::widget::{button, checkbox, column, container, row, text, Column};
+use iced::{Alignment, Element, Length};
+
+/// Interactions with the queue of files.
+#[derive(Debug, Clone)]
+pub enum PlaylistMessage {
+    /// Open the entry at this index.
+    Play(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Remove(usize),
+    Previous,
+    Next,
+    /// Keep tempo and pitch when moving to another entry.
+    KeepSettingsToggled(bool),
+}
+
+/// The queue, one row per file, with the current one highlighted.
+pub fn view_playlist(
+    entries: &[PathBuf],
+    current: Option<usize>,
+    keep_settings: bool,
+) -> Element<'_, PlaylistMessage> {
+    let has_previous = current.is_some_and(|i| i > 0);
+    let has_next = current.map_or(!entries.is_empty(), |i| i + 1 < entries.len());
+    let header = row![
+        text(format!("Queue ({})", entries.len())).size(14),
+        button(text("Previous").size(13))
+            .on_press_maybe(has_previous.then_some(PlaylistMessage::Previous)),
+        button(text("Next").size(13)).on_press_maybe(has_next.then_some(PlaylistMessage::Next)),
+        checkbox(keep_settings)
+            .label("Keep tempo and pitch")
+            .on_toggle(PlaylistMessage::KeepSettingsToggled),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center);
+
+    let rows = Column::with_children(entries.iter().enumerate().map(|(i, path)| {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let marker = if current == Some(i) { "▶" } else { " " };
+        let last = i + 1 == entries.len();
+        row![
+            text(marker).size(13).width(Length::Fixed(14.0)),
+            button(text(name).size(13))
+                .on_press(PlaylistMessage::Play(i))
+                .style(button::text)
+                .width(Length::Fill),
+            button(text("↑").size(13))
+                .on_press_maybe((i > 0).then_some(PlaylistMessage::MoveUp(i))),
+            button(text("↓").size(13))
+                .on_press_maybe((!last).then_some(PlaylistMessage::MoveDown(i))),
+            button(text("Remove").size(13)).on_press(PlaylistMessage::Remove(i)),
+        ]
+        .spacing(5)
+        .align_y(Alignment::Center)
+        .into()
+    }))
+    .spacing(2);
+
+    container(column![header, rows].spacing(5))
+        .padding([0, 10])
+        .into()
+}
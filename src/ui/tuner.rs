@@ -0,0 +1,94 @@
+use iced::mouse;
+use iced::widget::canvas::{self, Frame, Geometry, Path, Stroke};
+use iced::widget::{row, text};
+use iced::{Alignment, Color, Element, Length, Rectangle, Renderer, Theme};
+
+use crate::audio::analysis::NoteReading;
+
+/// Deviation in cents that still counts as in tune.
+const IN_TUNE_CENTS: f32 = 5.0;
+
+/// Horizontal needle showing cents deviation, centered on the note.
+struct TunerNeedle {
+    cents: Option<f32>,
+}
+
+impl<Message> canvas::Program<Message> for TunerNeedle {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let width = bounds.width;
+        let height = bounds.height;
+        let center_x = width / 2.0;
+
+        frame.fill_rectangle(
+            iced::Point::ORIGIN,
+            bounds.size(),
+            Color::from_rgb(0.12, 0.12, 0.15),
+        );
+
+        // Scale ticks at -50, -25, 0, +25 and +50 cents
+        for step in -2..=2 {
+            let x = center_x + step as f32 * width / 4.0;
+            let tick_height = if step == 0 { height } else { height / 3.0 };
+            let tick = Path::line(
+                iced::Point::new(x, height - tick_height),
+                iced::Point::new(x, height),
+            );
+            frame.stroke(
+                &tick,
+                Stroke::default()
+                    .with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.3))
+                    .with_width(1.0),
+            );
+        }
+
+        if let Some(cents) = self.cents {
+            let x = center_x + cents.clamp(-50.0, 50.0) / 50.0 * center_x;
+            let color = if cents.abs() <= IN_TUNE_CENTS {
+                Color::from_rgb(0.3, 0.9, 0.4)
+            } else {
+                Color::from_rgb(1.0, 0.6, 0.2)
+            };
+            let needle = Path::line(iced::Point::new(x, 0.0), iced::Point::new(x, height));
+            frame.stroke(&needle, Stroke::default().with_color(color).with_width(2.0));
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Build the tuner: note name, cents needle and a sharp/flat readout.
+/// `None` means no clear pitch and shows a dash.
+pub fn view_tuner<'a, Message: 'a>(reading: Option<NoteReading>) -> Element<'a, Message> {
+    let (note, detail) = match reading {
+        Some(r) if r.cents.abs() <= IN_TUNE_CENTS => (r.to_string(), "in tune".to_string()),
+        Some(r) if r.cents > 0.0 => (r.to_string(), format!("{:.0} cents sharp", r.cents)),
+        Some(r) => (r.to_string(), format!("{:.0} cents flat", -r.cents)),
+        None => ("—".to_string(), String::new()),
+    };
+
+    let needle = canvas::Canvas::new(TunerNeedle {
+        cents: reading.map(|r| r.cents),
+    })
+    .width(Length::Fixed(150.0))
+    .height(Length::Fixed(20.0));
+
+    row![
+        text("Tuner:").size(14),
+        text(note).size(18).width(Length::Fixed(40.0)),
+        needle,
+        text(detail).size(14),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
@@ -1,17 +1,110 @@
-use iced::mouse;
+use std::time::{Duration, Instant};
+
+use iced::mouse::{self, ScrollDelta};
 use iced::widget::canvas::{self, Action, Cache, Event, Frame, Geometry, Path, Stroke};
-use iced::{Color, Rectangle, Renderer, Theme};
+use iced::{keyboard, window, Color, Rectangle, Renderer, Theme};
 
+use crate::settings::{WaveformColors, WaveformStyle};
+use crate::ui::context_menu::ContextMenu;
+use crate::ui::format::{format_time, format_time_precise};
 use crate::waveform_cache::WaveformPeaks;
 
 /// State for the waveform canvas widget.
 pub struct WaveformView {
     waveform_cache: Cache,
+    /// The overview strip's waveform, which doesn't change with zoom or scroll.
+    minimap_cache: Cache,
     pub peaks: Option<WaveformPeaks>,
     pub total_frames: usize,
-    pub playback_position: f64, // 0.0 to 1.0 fraction
+    pub playback_position: f64,          // 0.0 to 1.0 fraction
     pub loop_region: Option<(f64, f64)>, // fractions
+    /// Stored loop slots as (start, end) fractions, drawn faintly.
+    pub stored_loops: Vec<Option<(f64, f64)>>,
+    /// Cue markers as (fraction, label), sorted by position.
+    pub markers: Vec<(f64, String)>,
+    /// Detected beats as fractions, in order.
+    pub beats: Vec<f64>,
     pub duration: f64,
+    /// Horizontal magnification: 1.0 shows the whole file.
+    pub zoom: f64,
+    /// File fraction at the left edge of the canvas.
+    pub scroll_offset: f64,
+    /// Display-only amplitude magnification, so quiet recordings are visible.
+    pub vertical_scale: f32,
+    /// Draw amplitudes on a dB scale, so quiet detail is visible.
+    pub db_scale: bool,
+    /// Draw the RMS level inside the peaks, to show where the energy is.
+    pub show_rms: bool,
+    /// Pointer travel in pixels before a press counts as a loop drag rather than a click.
+    pub drag_threshold_px: f32,
+    /// Brightness of the beat flash, from `beat_flash`; `None` leaves it dark.
+    pub beat_flash: Option<f32>,
+    colors: WaveformColors,
+    style: WaveformStyle,
+}
+
+/// Default for `WaveformView::drag_threshold_px`.
+pub const DEFAULT_DRAG_THRESHOLD_PX: f32 = 4.0;
+
+/// Furthest the waveform zooms in, as a multiple of the whole-file width.
+const MAX_ZOOM: f64 = 1000.0;
+/// Zoom factor per mouse-wheel line.
+const ZOOM_STEP: f64 = 1.25;
+/// Room left either side when zooming to a range, as a fraction of its length.
+const ZOOM_TO_PADDING: f64 = 0.05;
+/// Height of the time ruler along the top of the canvas.
+const RULER_HEIGHT: f32 = 16.0;
+/// Tick intervals the ruler picks from, in seconds.
+const RULER_INTERVALS: &[f64] = &[1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0];
+/// Minimum spacing between labeled ticks, wide enough that labels never overlap.
+const MIN_TICK_SPACING_PX: f64 = 60.0;
+
+/// Height of a marker's label flag, drawn just below the ruler.
+const FLAG_HEIGHT: f32 = 14.0;
+/// Horizontal distance from a marker within which its flag takes clicks.
+const MARKER_HIT_PX: f32 = 6.0;
+/// Horizontal distance from a loop boundary within which a press grabs it.
+const LOOP_EDGE_HIT_PX: f32 = 5.0;
+/// Longest gap between two clicks that still makes a double-click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+/// Beat lines closer together than this are left out, so zoomed-out
+/// views don't turn into a solid block.
+const MIN_BEAT_SPACING_PX: f32 = 4.0;
+/// Rough width of one label character, for sizing flags.
+const FLAG_CHAR_WIDTH: f32 = 6.5;
+
+/// How long the beat flash takes to fade, in heard (output) time.
+const BEAT_FLASH_TIME: f64 = 0.15;
+/// Brightness of the flash on beats other than the first of the bar.
+const OFFBEAT_FLASH: f32 = 0.45;
+/// Radius of the beat flash, drawn in the top right corner below the ruler.
+const BEAT_FLASH_RADIUS: f32 = 7.0;
+
+/// Limits for `WaveformView::vertical_scale`.
+const MIN_VERTICAL_SCALE: f32 = 0.25;
+const MAX_VERTICAL_SCALE: f32 = 50.0;
+/// Level drawn at the centre line in dB mode; anything quieter is flat.
+const DB_FLOOR: f32 = -60.0;
+/// Vertical scale factor per Shift+wheel line.
+const VERTICAL_SCALE_STEP: f32 = 1.25;
+/// Pixels of smooth (touchpad) scrolling that count as one wheel line.
+const PIXELS_PER_WHEEL_LINE: f32 = 40.0;
+
+/// Brightness of the beat flash with the playhead at `position` seconds: full
+/// on the first beat of each bar, dimmer on the rest, fading out over
+/// `BEAT_FLASH_TIME` as heard at `tempo`. `None` once it has faded.
+pub fn beat_flash(beats: &[f64], position: f64, tempo: f32, beats_per_bar: u32) -> Option<f32> {
+    let index = beats.partition_point(|&b| b <= position).checked_sub(1)?;
+    let heard = (position - beats[index]) / tempo.max(f32::EPSILON) as f64;
+    if heard >= BEAT_FLASH_TIME {
+        return None;
+    }
+    let peak = if index % beats_per_bar.max(1) as usize == 0 {
+        1.0
+    } else {
+        OFFBEAT_FLASH
+    };
+    Some(peak * (1.0 - heard / BEAT_FLASH_TIME) as f32)
 }
 
 /// Interactions on the waveform.
@@ -20,7 +113,29 @@ pub enum WaveformMessage {
     Seek(f64),              // time in seconds
     LoopSelected(f64, f64), // start, end in seconds
     DragStarted(f64),       // x fraction
+    AddMarker(f64),         // time in seconds
+    RemoveMarker(usize),    // index into the marker list
     DragMoved(f64),         // x fraction
+    ZoomAt(f64, f64),       // anchor file fraction, factor
+    ScrollTo(f64),          // file fraction at the left edge
+    ScaleVertically(f32),   // factor
+    /// The in-progress drag was abandoned; the preview should be discarded.
+    DragCancelled,
+    /// Loop being edited, as (start, end) fractions; display only.
+    LoopPreview(f64, f64),
+    /// An existing loop was resized or moved to (start, end) in seconds.
+    LoopAdjusted(f64, f64),
+    /// Alt+drag is scrubbing over this time in seconds; release seeks there.
+    Scrub(f64),
+    /// Double-click: drop the loop.
+    ClearLoop,
+    /// Move the loop start here, in seconds.
+    SetLoopStart(f64),
+    /// Move the loop end here, in seconds.
+    SetLoopEnd(f64),
+    /// Right-click: open the context menu for this spot.
+    OpenMenu(ContextMenu),
+    CloseMenu,
 }
 
 #[allow(dead_code)]
@@ -28,18 +143,403 @@ impl WaveformView {
     pub fn new() -> Self {
         Self {
             waveform_cache: Cache::new(),
+            minimap_cache: Cache::new(),
             peaks: None,
             total_frames: 0,
             playback_position: 0.0,
             loop_region: None,
+            stored_loops: Vec::new(),
+            markers: Vec::new(),
+            beats: Vec::new(),
             duration: 0.0,
+            zoom: 1.0,
+            scroll_offset: 0.0,
+            vertical_scale: 1.0,
+            db_scale: false,
+            show_rms: true,
+            drag_threshold_px: DEFAULT_DRAG_THRESHOLD_PX,
+            beat_flash: None,
+            colors: WaveformColors::DARK,
+            style: WaveformStyle::default(),
         }
     }
 
-    pub fn set_peaks(&mut self, peaks: WaveformPeaks, total_frames: usize, duration: f64) {
-        self.peaks = Some(peaks);
+    /// Set up the view for a newly loaded file. Peaks arrive separately via
+    /// `set_peaks`; until then a placeholder is drawn, but seeking and loop
+    /// selection already work since they only need the duration.
+    pub fn set_audio(&mut self, total_frames: usize, duration: f64) {
+        self.peaks = None;
+        self.beats.clear();
         self.total_frames = total_frames;
         self.duration = duration;
+        self.zoom = 1.0;
+        self.scroll_offset = 0.0;
+        self.waveform_cache.clear();
+        self.minimap_cache.clear();
+    }
+
+    pub fn set_peaks(&mut self, peaks: WaveformPeaks) {
+        self.peaks = Some(peaks);
+        self.waveform_cache.clear();
+        self.minimap_cache.clear();
+    }
+
+    /// The drag threshold as a file fraction on a canvas `width` pixels wide.
+    pub fn drag_threshold_fraction(&self, width: f32) -> f64 {
+        if width <= 0.0 {
+            return 0.0;
+        }
+        (self.drag_threshold_px / width) as f64 * self.visible_span()
+    }
+
+    /// Fraction of the file visible across the canvas.
+    pub fn visible_span(&self) -> f64 {
+        1.0 / self.zoom
+    }
+
+    /// The visible part of the file as (start, end) fractions.
+    pub fn visible_window(&self) -> (f64, f64) {
+        (self.scroll_offset, self.scroll_offset + self.visible_span())
+    }
+
+    /// File fraction under canvas position `x` on a canvas `width` pixels wide.
+    pub fn fraction_at(&self, x: f32, width: f32) -> f64 {
+        if width <= 0.0 {
+            return self.scroll_offset;
+        }
+        let ratio = (x / width).clamp(0.0, 1.0) as f64;
+        (self.scroll_offset + ratio * self.visible_span()).clamp(0.0, 1.0)
+    }
+
+    /// Canvas position of file fraction `frac`; off-canvas when scrolled out of view.
+    pub fn x_at(&self, frac: f64, width: f32) -> f32 {
+        ((frac - self.scroll_offset) / self.visible_span() * width as f64) as f32
+    }
+
+    /// Zoom by `factor` (above 1 zooms in), keeping file fraction `anchor`
+    /// at the same place on screen.
+    pub fn zoom_at(&mut self, anchor: f64, factor: f64) {
+        let ratio = (anchor - self.scroll_offset) / self.visible_span();
+        self.zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+        self.set_scroll_offset(anchor - ratio * self.visible_span());
+    }
+
+    /// Zoom so file fractions `start` to `end` fill the canvas, with a little
+    /// room either side.
+    pub fn zoom_to(&mut self, start: f64, end: f64) {
+        let padding = (end - start) * ZOOM_TO_PADDING;
+        let span = (end - start + 2.0 * padding).clamp(1.0 / MAX_ZOOM, 1.0);
+        self.zoom = 1.0 / span;
+        self.set_scroll_offset((start + end - span) / 2.0);
+    }
+
+    /// Zoom out to show the whole file.
+    pub fn zoom_to_fit(&mut self) {
+        self.zoom = 1.0;
+        self.set_scroll_offset(0.0);
+    }
+
+    /// Draw the time ruler: a strip along the top with MM:SS labeled ticks,
+    /// spaced according to the current zoom.
+    fn draw_ruler(&self, frame: &mut Frame, width: f32) {
+        frame.fill_rectangle(
+            iced::Point::ORIGIN,
+            iced::Size::new(width, RULER_HEIGHT),
+            self.colors.ruler.scale_alpha(0.5),
+        );
+
+        let visible_secs = self.duration * self.visible_span();
+        if visible_secs <= 0.0 || width <= 0.0 {
+            return;
+        }
+        let px_per_sec = width as f64 / visible_secs;
+        let interval = RULER_INTERVALS
+            .iter()
+            .copied()
+            .find(|i| i * px_per_sec >= MIN_TICK_SPACING_PX)
+            .unwrap_or(RULER_INTERVALS[RULER_INTERVALS.len() - 1]);
+
+        let start_secs = self.scroll_offset * self.duration;
+        let mut tick = (start_secs / interval).ceil() * interval;
+        while tick <= start_secs + visible_secs {
+            let x = self.x_at(tick / self.duration, width);
+            let line = Path::line(
+                iced::Point::new(x, RULER_HEIGHT - 5.0),
+                iced::Point::new(x, RULER_HEIGHT),
+            );
+            frame.stroke(
+                &line,
+                Stroke::default()
+                    .with_color(self.colors.foreground.scale_alpha(0.6))
+                    .with_width(1.0),
+            );
+            frame.fill_text(canvas::Text {
+                content: format_time(tick),
+                position: iced::Point::new(x + 3.0, 1.0),
+                color: self.colors.foreground.scale_alpha(0.7),
+                size: 11.0.into(),
+                ..canvas::Text::default()
+            });
+            tick += interval;
+        }
+    }
+
+    /// Light a dot in the corner as each beat plays, fading as it goes.
+    fn draw_beat_flash(&self, frame: &mut Frame, width: f32) {
+        let Some(brightness) = self.beat_flash else {
+            return;
+        };
+        let centre = iced::Point::new(
+            width - BEAT_FLASH_RADIUS * 2.0,
+            RULER_HEIGHT + FLAG_HEIGHT + BEAT_FLASH_RADIUS * 2.0,
+        );
+        frame.fill(
+            &Path::circle(centre, BEAT_FLASH_RADIUS),
+            self.colors.playhead.scale_alpha(brightness),
+        );
+    }
+
+    /// Draw the beat grid as faint lines below the ruler.
+    fn draw_beats(&self, frame: &mut Frame, width: f32, height: f32) {
+        let (first, last) = match (self.beats.first(), self.beats.last()) {
+            (Some(first), Some(last)) if self.beats.len() > 1 => (*first, *last),
+            _ => return,
+        };
+        let average_spacing =
+            (self.x_at(last, width) - self.x_at(first, width)) / (self.beats.len() - 1) as f32;
+        if average_spacing < MIN_BEAT_SPACING_PX {
+            return;
+        }
+        let (start, end) = self.visible_window();
+        let from = self.beats.partition_point(|b| *b < start);
+        for &beat in self.beats[from..].iter().take_while(|b| **b <= end) {
+            let x = self.x_at(beat, width);
+            let line = Path::line(
+                iced::Point::new(x, RULER_HEIGHT),
+                iced::Point::new(x, height),
+            );
+            frame.stroke(
+                &line,
+                Stroke::default()
+                    .with_color(self.colors.foreground.scale_alpha(0.12))
+                    .with_width(1.0),
+            );
+        }
+    }
+
+    /// Draw the time under the pointer in a label beside it, flipped to the
+    /// left near the right edge.
+    fn draw_hover_time(&self, frame: &mut Frame, x: f32, width: f32) {
+        if self.duration <= 0.0 {
+            return;
+        }
+        let label = format_time_precise(self.fraction_at(x, width) * self.duration);
+        let label_width = label.chars().count() as f32 * FLAG_CHAR_WIDTH + 6.0;
+        let left = if x + 8.0 + label_width > width {
+            x - 8.0 - label_width
+        } else {
+            x + 8.0
+        };
+        let top = RULER_HEIGHT + FLAG_HEIGHT + 2.0;
+        frame.fill_rectangle(
+            iced::Point::new(left, top),
+            iced::Size::new(label_width, FLAG_HEIGHT),
+            Color::from_rgba(0.0, 0.0, 0.0, 0.7),
+        );
+        frame.fill_text(canvas::Text {
+            content: label,
+            position: iced::Point::new(left + 3.0, top + 1.0),
+            color: Color::WHITE,
+            size: 11.0.into(),
+            ..canvas::Text::default()
+        });
+    }
+
+    /// Draw each marker as a line across the canvas with a labeled flag.
+    fn draw_markers(&self, frame: &mut Frame, width: f32, height: f32) {
+        let color = self.colors.marker;
+        for (frac, label) in &self.markers {
+            let x = self.x_at(*frac, width);
+            let line = Path::line(iced::Point::new(x, 0.0), iced::Point::new(x, height));
+            frame.stroke(&line, Stroke::default().with_color(color).with_width(1.0));
+
+            let flag_width = label.chars().count() as f32 * FLAG_CHAR_WIDTH + 6.0;
+            frame.fill_rectangle(
+                iced::Point::new(x, RULER_HEIGHT),
+                iced::Size::new(flag_width, FLAG_HEIGHT),
+                color,
+            );
+            frame.fill_text(canvas::Text {
+                content: label.clone(),
+                position: iced::Point::new(x + 3.0, RULER_HEIGHT + 1.0),
+                color: Color::BLACK,
+                size: 11.0.into(),
+                ..canvas::Text::default()
+            });
+        }
+    }
+
+    /// Draw each stored loop as a faint band numbered by its slot along the
+    /// bottom edge. The one matching the current loop gets a bright number.
+    fn draw_stored_loops(&self, frame: &mut Frame, width: f32, height: f32) {
+        for (slot, region) in self.stored_loops.iter().enumerate() {
+            let Some((start, end)) = *region else {
+                continue;
+            };
+            let active = self.loop_region == Some((start, end));
+            let x_start = self.x_at(start, width);
+            let x_end = self.x_at(end, width);
+            if !active {
+                frame.fill_rectangle(
+                    iced::Point::new(x_start, 0.0),
+                    iced::Size::new(x_end - x_start, height),
+                    self.colors.waveform.scale_alpha(0.07),
+                );
+            }
+            frame.fill_text(canvas::Text {
+                content: (slot + 1).to_string(),
+                position: iced::Point::new(x_start + 3.0, height - 14.0),
+                color: if active {
+                    self.colors.loop_region
+                } else {
+                    self.colors.waveform.scale_alpha(0.6)
+                },
+                size: 11.0.into(),
+                ..canvas::Text::default()
+            });
+        }
+    }
+
+    /// Index of the marker whose flag is under `pos`, if any.
+    fn marker_at(&self, pos: iced::Point, width: f32) -> Option<usize> {
+        if pos.y > RULER_HEIGHT + FLAG_HEIGHT {
+            return None;
+        }
+        self.markers
+            .iter()
+            .position(|(frac, _)| (self.x_at(*frac, width) - pos.x).abs() <= MARKER_HIT_PX)
+    }
+
+    /// Which part of the loop region is under canvas position `x`, if any.
+    /// Boundaries win over the body, so a narrow loop can still be resized.
+    fn loop_part_at(&self, x: f32, width: f32) -> Option<LoopPart> {
+        let (start, end) = self.loop_region?;
+        let (x_start, x_end) = (self.x_at(start, width), self.x_at(end, width));
+        if (x - x_start).abs() <= LOOP_EDGE_HIT_PX {
+            Some(LoopPart::Start)
+        } else if (x - x_end).abs() <= LOOP_EDGE_HIT_PX {
+            Some(LoopPart::End)
+        } else if x > x_start && x < x_end {
+            Some(LoopPart::Body)
+        } else {
+            None
+        }
+    }
+
+    /// The loop region after dragging `edit` to file fraction `frac`.
+    fn adjusted_loop(&self, edit: &LoopEdit, frac: f64) -> (f64, f64) {
+        let (start, end) = edit.original;
+        match edit.part {
+            LoopPart::Start => (frac.min(end), frac.max(end)),
+            LoopPart::End => (frac.min(start), frac.max(start)),
+            LoopPart::Body => {
+                let shift = (frac - edit.grab).clamp(-start, 1.0 - end);
+                (start + shift, end + shift)
+            }
+        }
+    }
+
+    /// Multiply the amplitude magnification by `factor`, within limits.
+    pub fn scale_vertically(&mut self, factor: f32) {
+        self.vertical_scale =
+            (self.vertical_scale * factor).clamp(MIN_VERTICAL_SCALE, MAX_VERTICAL_SCALE);
+        self.waveform_cache.clear();
+    }
+
+    /// Scroll so the canvas starts at file fraction `offset`, kept within the file.
+    pub fn set_scroll_offset(&mut self, offset: f64) {
+        self.scroll_offset = offset.clamp(0.0, 1.0 - self.visible_span());
+        self.waveform_cache.clear();
+    }
+
+    pub fn set_db_scale(&mut self, db: bool) {
+        self.db_scale = db;
+        self.waveform_cache.clear();
+    }
+
+    pub fn set_colors(&mut self, colors: WaveformColors) {
+        if self.colors != colors {
+            self.colors = colors;
+            self.waveform_cache.clear();
+            self.minimap_cache.clear();
+        }
+    }
+
+    pub fn colors(&self) -> WaveformColors {
+        self.colors
+    }
+
+    pub fn minimap_cache(&self) -> &Cache {
+        &self.minimap_cache
+    }
+
+    pub fn set_style(&mut self, style: WaveformStyle) {
+        self.style = style;
+        self.waveform_cache.clear();
+    }
+
+    /// Draw one layer of the waveform from the (top, bottom) y of each
+    /// pixel column, in the current style. Either way it is a single path.
+    fn draw_layer(&self, frame: &mut Frame, spans: &[(f32, f32)], color: Color) {
+        match self.style {
+            WaveformStyle::Lines => {
+                let columns = Path::new(|builder| {
+                    for (i, &(top, bottom)) in spans.iter().enumerate() {
+                        builder.move_to(iced::Point::new(i as f32, top));
+                        builder.line_to(iced::Point::new(i as f32, bottom));
+                    }
+                });
+                frame.stroke(
+                    &columns,
+                    Stroke::default().with_color(color).with_width(1.0),
+                );
+            }
+            WaveformStyle::Filled => {
+                // Along the top envelope, then back along the bottom one
+                let outline = Path::new(|builder| {
+                    for (i, &(top, _)) in spans.iter().enumerate() {
+                        let point = iced::Point::new(i as f32, top);
+                        if i == 0 {
+                            builder.move_to(point);
+                        } else {
+                            builder.line_to(point);
+                        }
+                    }
+                    for (i, &(_, bottom)) in spans.iter().enumerate().rev() {
+                        builder.line_to(iced::Point::new(i as f32, bottom));
+                    }
+                    builder.close();
+                });
+                frame.fill(&outline, color);
+            }
+        }
+    }
+
+    /// Height of sample value `v` as a fraction of the half-height, with the
+    /// same sign. Only the drawing is affected, never the peaks themselves.
+    fn display_amplitude(&self, v: f32) -> f32 {
+        let magnitude = (v.abs() * self.vertical_scale).min(1.0);
+        let height = if self.db_scale {
+            let db = 20.0 * magnitude.max(f32::MIN_POSITIVE).log10();
+            ((db - DB_FLOOR) / -DB_FLOOR).max(0.0)
+        } else {
+            magnitude
+        };
+        height.copysign(v)
+    }
+
+    pub fn set_show_rms(&mut self, show: bool) {
+        self.show_rms = show;
         self.waveform_cache.clear();
     }
 
@@ -48,12 +548,49 @@ impl WaveformView {
     }
 }
 
+/// A part of the loop region that can be dragged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LoopPart {
+    Start,
+    End,
+    Body,
+}
+
+/// An edit of an existing loop region in progress.
+struct LoopEdit {
+    part: LoopPart,
+    /// File fraction where the button went down.
+    grab: f64,
+    /// The region as it was before the edit, as fractions.
+    original: (f64, f64),
+    /// Whether the pointer has moved past the drag threshold.
+    moved: bool,
+}
+
+/// Pointer state the canvas keeps between events.
+#[derive(Default)]
+pub struct InteractionState {
+    /// File fraction where the button went down, while clicking or selecting.
+    drag_start: Option<f64>,
+    /// Set while a loop boundary or the whole loop is being dragged.
+    loop_edit: Option<LoopEdit>,
+    /// Cursor x and scroll offset when a Ctrl+drag pan began.
+    pan_start: Option<(f32, f64)>,
+    /// Cursor x while the pointer is over the canvas.
+    hover_x: Option<f32>,
+    /// Set while Alt+dragging to scrub.
+    scrubbing: bool,
+    /// When and where the last plain click landed, to spot a double-click.
+    last_click: Option<(Instant, f32)>,
+    modifiers: keyboard::Modifiers,
+}
+
 impl canvas::Program<WaveformMessage> for WaveformView {
-    type State = Option<f64>; // drag start x fraction
+    type State = InteractionState;
 
     fn draw(
         &self,
-        _state: &Self::State,
+        state: &Self::State,
         renderer: &Renderer,
         _theme: &Theme,
         bounds: Rectangle,
@@ -65,11 +602,7 @@ impl canvas::Program<WaveformMessage> for WaveformView {
         // Layer 1: Cached waveform
         let waveform = self.waveform_cache.draw(renderer, bounds.size(), |frame| {
             // Background
-            frame.fill_rectangle(
-                iced::Point::ORIGIN,
-                bounds.size(),
-                Color::from_rgb(0.12, 0.12, 0.15),
-            );
+            frame.fill_rectangle(iced::Point::ORIGIN, bounds.size(), self.colors.background);
 
             // Center line
             let center_y = height / 2.0;
@@ -80,66 +613,110 @@ impl canvas::Program<WaveformMessage> for WaveformView {
             frame.stroke(
                 &center_line,
                 Stroke::default()
-                    .with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.15))
+                    .with_color(self.colors.foreground.scale_alpha(0.15))
                     .with_width(1.0),
             );
 
             if let Some(peaks) = &self.peaks {
-                let display_peaks = peaks.peaks_for_width(width, self.total_frames);
-                let waveform_color = Color::from_rgb(0.3, 0.7, 1.0);
+                let display_peaks =
+                    peaks.peaks_for_width(width, self.total_frames, self.visible_window());
+                let waveform_color = self.colors.waveform;
+                let rms_color = self.colors.rms;
 
-                for (i, peak) in display_peaks.iter().enumerate() {
-                    let x = i as f32;
-                    let min_y = center_y - peak.max * center_y;
-                    let max_y = center_y - peak.min * center_y;
+                let spans: Vec<(f32, f32)> = display_peaks
+                    .iter()
+                    .map(|peak| {
+                        let top = center_y - self.display_amplitude(peak.max) * center_y;
+                        let bottom = center_y - self.display_amplitude(peak.min) * center_y;
+                        (top, bottom)
+                    })
+                    .collect();
+                self.draw_layer(frame, &spans, waveform_color);
 
-                    let line = Path::line(
-                        iced::Point::new(x, min_y),
-                        iced::Point::new(x, max_y),
-                    );
-                    frame.stroke(
-                        &line,
-                        Stroke::default()
-                            .with_color(waveform_color)
-                            .with_width(1.0),
-                    );
+                if self.show_rms {
+                    let rms_spans: Vec<(f32, f32)> = display_peaks
+                        .iter()
+                        .map(|peak| {
+                            // Kept inside the peaks, which an offset signal could poke out of
+                            let scaled_rms = self.display_amplitude(peak.rms);
+                            let rms_top = scaled_rms.min(self.display_amplitude(peak.max));
+                            let rms_bottom = (-scaled_rms).max(self.display_amplitude(peak.min));
+                            (
+                                center_y - rms_top * center_y,
+                                center_y - rms_bottom * center_y,
+                            )
+                        })
+                        .collect();
+                    self.draw_layer(frame, &rms_spans, rms_color);
                 }
+            } else {
+                // Peaks are still being computed; the flat center line stands in
+                frame.fill_text(canvas::Text {
+                    content: "Analyzing…".to_string(),
+                    position: iced::Point::new(width / 2.0, center_y - 8.0),
+                    color: self.colors.foreground.scale_alpha(0.5),
+                    size: 14.0.into(),
+                    align_x: iced::widget::text::Alignment::Center,
+                    align_y: iced::alignment::Vertical::Bottom,
+                    ..canvas::Text::default()
+                });
             }
         });
 
-        // Layer 2: Dynamic overlay (playhead + loop region)
+        // Layer 2: Dynamic overlay (loop region, beats, time ruler, markers, playhead)
         let overlay = {
             let mut frame = Frame::new(renderer, bounds.size());
+            let playhead_x = self.x_at(self.playback_position, width);
+
+            // Dim the part still to be played. Veiling it in the background
+            // colour here leaves the cached waveform alone as the playhead moves.
+            let unplayed_x = playhead_x.clamp(0.0, width);
+            frame.fill_rectangle(
+                iced::Point::new(unplayed_x, 0.0),
+                iced::Size::new(width - unplayed_x, height),
+                self.colors.background.scale_alpha(0.45),
+            );
+
+            self.draw_stored_loops(&mut frame, width, height);
 
             // Draw loop region
             if let Some((start, end)) = self.loop_region {
-                let x_start = (start * width as f64) as f32;
-                let x_end = (end * width as f64) as f32;
+                let x_start = self.x_at(start, width);
+                let x_end = self.x_at(end, width);
                 let loop_width = x_end - x_start;
 
                 frame.fill_rectangle(
                     iced::Point::new(x_start, 0.0),
                     iced::Size::new(loop_width, height),
-                    Color::from_rgba(1.0, 0.8, 0.0, 0.15),
+                    self.colors.loop_region.scale_alpha(0.15),
                 );
 
                 // Loop region borders
                 for &x in &[x_start, x_end] {
-                    let line = Path::line(
-                        iced::Point::new(x, 0.0),
-                        iced::Point::new(x, height),
-                    );
+                    let line = Path::line(iced::Point::new(x, 0.0), iced::Point::new(x, height));
                     frame.stroke(
                         &line,
                         Stroke::default()
-                            .with_color(Color::from_rgba(1.0, 0.8, 0.0, 0.7))
+                            .with_color(self.colors.loop_region.scale_alpha(0.7))
                             .with_width(1.0),
                     );
                 }
             }
 
+            self.draw_beats(&mut frame, width, height);
+            self.draw_ruler(&mut frame, width);
+            self.draw_markers(&mut frame, width, height);
+            self.draw_beat_flash(&mut frame, width);
+
+            // A drag shows its own feedback, so the time label only shows while hovering
+            let dragging = state.drag_start.is_some()
+                || state.loop_edit.is_some()
+                || state.pan_start.is_some();
+            if let (Some(x), false) = (state.hover_x, dragging) {
+                self.draw_hover_time(&mut frame, x, width);
+            }
+
             // Draw playhead
-            let playhead_x = (self.playback_position * width as f64) as f32;
             let playhead = Path::line(
                 iced::Point::new(playhead_x, 0.0),
                 iced::Point::new(playhead_x, height),
@@ -147,7 +724,7 @@ impl canvas::Program<WaveformMessage> for WaveformView {
             frame.stroke(
                 &playhead,
                 Stroke::default()
-                    .with_color(Color::from_rgb(1.0, 1.0, 1.0))
+                    .with_color(self.colors.playhead)
                     .with_width(2.0),
             );
 
@@ -164,32 +741,154 @@ impl canvas::Program<WaveformMessage> for WaveformView {
         bounds: Rectangle,
         cursor: mouse::Cursor,
     ) -> Option<Action<WaveformMessage>> {
+        match event {
+            Event::Window(window::Event::Resized(_)) => {
+                // The press was measured against the old width; drop the drag
+                // rather than finish it with a threshold from another scale
+                state.pan_start = None;
+                let was_editing = state.loop_edit.take().is_some();
+                let was_selecting = state.drag_start.take().is_some();
+                return (was_editing || was_selecting)
+                    .then(|| Action::publish(WaveformMessage::DragCancelled));
+            }
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.modifiers = *modifiers;
+                return None;
+            }
+            _ => {}
+        }
+
+        if let Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft) = event {
+            let hover_x = cursor.position_in(bounds).map(|p| p.x);
+            let left = state.hover_x.is_some() && hover_x.is_none();
+            state.hover_x = hover_x;
+            if left {
+                return Some(Action::request_redraw());
+            }
+        }
+
         let cursor_pos = cursor.position_in(bounds)?;
 
         match event {
             Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
-                let frac = (cursor_pos.x / bounds.width) as f64;
-                let frac = frac.clamp(0.0, 1.0);
-                *state = Some(frac);
+                // Also ends a scrub whose release happened off the canvas
+                state.scrubbing = false;
+                if state.modifiers.command() {
+                    // Ctrl+drag pans instead of selecting
+                    state.pan_start = Some((cursor_pos.x, self.scroll_offset));
+                    return Some(Action::capture());
+                }
+                if state.modifiers.alt() {
+                    state.scrubbing = true;
+                    let time = self.fraction_at(cursor_pos.x, bounds.width) * self.duration;
+                    return Some(Action::publish(WaveformMessage::Scrub(time)).and_capture());
+                }
+                if let Some((at, x)) = state.last_click.take() {
+                    // The first click has already seeked; the second clears the loop
+                    if at.elapsed() <= DOUBLE_CLICK_TIME
+                        && (x - cursor_pos.x).abs() <= self.drag_threshold_px
+                    {
+                        return Some(Action::publish(WaveformMessage::ClearLoop).and_capture());
+                    }
+                }
+                if let Some(i) = self.marker_at(cursor_pos, bounds.width) {
+                    // Clicking a marker flag jumps to it
+                    let time = self.markers[i].0 * self.duration;
+                    return Some(Action::publish(WaveformMessage::Seek(time)).and_capture());
+                }
+                let frac = self.fraction_at(cursor_pos.x, bounds.width);
+                if let (Some(part), Some(original)) = (
+                    self.loop_part_at(cursor_pos.x, bounds.width),
+                    self.loop_region,
+                ) {
+                    state.loop_edit = Some(LoopEdit {
+                        part,
+                        grab: frac,
+                        original,
+                        moved: false,
+                    });
+                    return Some(Action::capture());
+                }
+                state.drag_start = Some(frac);
                 Some(Action::publish(WaveformMessage::DragStarted(frac)).and_capture())
             }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                let menu = ContextMenu {
+                    time: self.fraction_at(cursor_pos.x, bounds.width) * self.duration,
+                    marker: self.marker_at(cursor_pos, bounds.width),
+                    position: cursor_pos,
+                    bounds: bounds.size(),
+                };
+                Some(Action::publish(WaveformMessage::OpenMenu(menu)).and_capture())
+            }
             Event::Mouse(mouse::Event::CursorMoved { .. }) => {
-                if state.is_some() {
-                    let frac = (cursor_pos.x / bounds.width) as f64;
-                    let frac = frac.clamp(0.0, 1.0);
+                if let Some((start_x, start_offset)) = state.pan_start {
+                    let shift =
+                        ((start_x - cursor_pos.x) / bounds.width) as f64 * self.visible_span();
+                    return Some(
+                        Action::publish(WaveformMessage::ScrollTo(start_offset + shift))
+                            .and_capture(),
+                    );
+                }
+                let frac = self.fraction_at(cursor_pos.x, bounds.width);
+                if state.scrubbing {
+                    let time = frac * self.duration;
+                    return Some(Action::publish(WaveformMessage::Scrub(time)).and_capture());
+                }
+                if let Some(edit) = &mut state.loop_edit {
+                    if (frac - edit.grab).abs() > self.drag_threshold_fraction(bounds.width) {
+                        edit.moved = true;
+                    }
+                    if !edit.moved {
+                        return None;
+                    }
+                    let (start, end) = self.adjusted_loop(edit, frac);
+                    return Some(
+                        Action::publish(WaveformMessage::LoopPreview(start, end)).and_capture(),
+                    );
+                }
+                let Some(start) = state.drag_start else {
+                    // Just hovering; redraw so the time label follows the pointer
+                    return Some(Action::request_redraw());
+                };
+                if (frac - start).abs() > self.drag_threshold_fraction(bounds.width) {
                     Some(Action::publish(WaveformMessage::DragMoved(frac)).and_capture())
                 } else {
                     None
                 }
             }
             Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
-                if let Some(start) = state.take() {
-                    let end = (cursor_pos.x / bounds.width) as f64;
-                    let end = end.clamp(0.0, 1.0);
+                if state.pan_start.take().is_some() {
+                    return Some(Action::capture());
+                }
+                if std::mem::take(&mut state.scrubbing) {
+                    let time = self.fraction_at(cursor_pos.x, bounds.width) * self.duration;
+                    return Some(Action::publish(WaveformMessage::Seek(time)).and_capture());
+                }
+                if let Some(edit) = state.loop_edit.take() {
+                    let frac = self.fraction_at(cursor_pos.x, bounds.width);
+                    if !edit.moved {
+                        // Click inside the loop: seek, like anywhere else
+                        state.last_click = Some((Instant::now(), cursor_pos.x));
+                        let time = edit.grab * self.duration;
+                        return Some(Action::publish(WaveformMessage::Seek(time)).and_capture());
+                    }
+                    let (start, end) = self.adjusted_loop(&edit, frac);
+                    return Some(
+                        Action::publish(WaveformMessage::LoopAdjusted(
+                            start * self.duration,
+                            end * self.duration,
+                        ))
+                        .and_capture(),
+                    );
+                }
+                if let Some(start) = state.drag_start.take() {
+                    let end = self.fraction_at(cursor_pos.x, bounds.width);
                     let diff = (end - start).abs();
 
-                    if diff < 0.005 {
+                    if diff <= self.drag_threshold_fraction(bounds.width) {
                         // Click: seek
+                        state.last_click = Some((Instant::now(), cursor_pos.x));
                         let time = start * self.duration;
                         Some(Action::publish(WaveformMessage::Seek(time)).and_capture())
                     } else {
@@ -210,7 +909,218 @@ impl canvas::Program<WaveformMessage> for WaveformView {
                     None
                 }
             }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => {
+                let (x, y) = match *delta {
+                    ScrollDelta::Lines { x, y } => (x, y),
+                    ScrollDelta::Pixels { x, y } => {
+                        (x / PIXELS_PER_WHEEL_LINE, y / PIXELS_PER_WHEEL_LINE)
+                    }
+                };
+                if state.modifiers.shift() {
+                    // Some platforms turn Shift+wheel into horizontal scrolling
+                    let lines = if y != 0.0 { y } else { x };
+                    let factor = VERTICAL_SCALE_STEP.powf(lines);
+                    return Some(
+                        Action::publish(WaveformMessage::ScaleVertically(factor)).and_capture(),
+                    );
+                }
+                let lines = y;
+                let anchor = self.fraction_at(cursor_pos.x, bounds.width);
+                let factor = ZOOM_STEP.powf(lines as f64);
+                Some(Action::publish(WaveformMessage::ZoomAt(anchor, factor)).and_capture())
+            }
             _ => None,
         }
     }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        let resize = mouse::Interaction::ResizingHorizontally;
+        if state.pan_start.is_some() {
+            mouse::Interaction::Grabbing
+        } else if state.scrubbing {
+            resize
+        } else if let Some(edit) = &state.loop_edit {
+            match edit.part {
+                LoopPart::Body => mouse::Interaction::Grabbing,
+                LoopPart::Start | LoopPart::End => resize,
+            }
+        } else if state.drag_start.is_some() {
+            // Dragging out a loop selection
+            mouse::Interaction::Crosshair
+        } else if let Some(pos) = cursor.position_in(bounds) {
+            match self.loop_part_at(pos.x, bounds.width) {
+                Some(LoopPart::Start | LoopPart::End) => resize,
+                Some(LoopPart::Body) => mouse::Interaction::Grab,
+                // A click seeks
+                None => mouse::Interaction::Pointer,
+            }
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drag_threshold_spans_pixels_of_the_whole_file_at_zoom_1() {
+        let view = WaveformView::new();
+        let fraction = view.drag_threshold_fraction(1000.0);
+        assert!((fraction - 0.004).abs() < 1e-9, "{fraction}");
+    }
+
+    #[test]
+    fn drag_threshold_shrinks_when_zoomed_in() {
+        let mut view = WaveformView::new();
+        view.zoom_at(0.5, 10.0);
+        let fraction = view.drag_threshold_fraction(1000.0);
+        // Still four pixels, of a tenth of the file
+        assert!((fraction - 0.0004).abs() < 1e-9, "{fraction}");
+        view.drag_threshold_px = 10.0;
+        let fraction = view.drag_threshold_fraction(500.0);
+        assert!((fraction - 0.002).abs() < 1e-9, "{fraction}");
+    }
+
+    #[test]
+    fn drag_threshold_is_zero_without_a_width() {
+        let view = WaveformView::new();
+        assert_eq!(view.drag_threshold_fraction(0.0), 0.0);
+        assert_eq!(view.drag_threshold_fraction(-20.0), 0.0);
+    }
+
+    /// Send `event` to the canvas, returning the message it publishes.
+    fn send(
+        view: &WaveformView,
+        state: &mut InteractionState,
+        event: Event,
+        bounds: Rectangle,
+        x: f32,
+    ) -> Option<WaveformMessage> {
+        let cursor = mouse::Cursor::Available(iced::Point::new(bounds.x + x, bounds.y + 10.0));
+        canvas::Program::update(view, state, &event, bounds, cursor)
+            .and_then(|action| action.into_inner().0)
+    }
+
+    #[test]
+    fn resizing_mid_edit_keeps_the_loop_times() {
+        let mut view = WaveformView::new();
+        view.duration = 10.0;
+        view.loop_region = Some((0.2, 0.6));
+        let mut state = InteractionState::default();
+        let wide = Rectangle::new(iced::Point::ORIGIN, iced::Size::new(1000.0, 100.0));
+
+        // Grab the loop body and drag it along
+        let press = Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left));
+        assert!(send(&view, &mut state, press, wide, 400.0).is_none());
+        let moved = Event::Mouse(mouse::Event::CursorMoved {
+            position: iced::Point::new(500.0, 10.0),
+        });
+        let preview = send(&view, &mut state, moved, wide, 500.0);
+        assert!(
+            matches!(preview, Some(WaveformMessage::LoopPreview(..))),
+            "{preview:?}"
+        );
+
+        // The resize drops the edit, leaving the loop where it was
+        let resized = Event::Window(window::Event::Resized(iced::Size::new(500.0, 100.0)));
+        let cancelled = send(&view, &mut state, resized, wide, 500.0);
+        assert!(
+            matches!(cancelled, Some(WaveformMessage::DragCancelled)),
+            "{cancelled:?}"
+        );
+        assert!(state.loop_edit.is_none());
+        assert_eq!(view.loop_region, Some((0.2, 0.6)));
+
+        // At the new width the edges are still at 2 s and 6 s
+        let narrow = Rectangle::new(iced::Point::ORIGIN, iced::Size::new(500.0, 100.0));
+        assert!(matches!(
+            view.loop_part_at(100.0, narrow.width),
+            Some(LoopPart::Start)
+        ));
+        assert!(matches!(
+            view.loop_part_at(300.0, narrow.width),
+            Some(LoopPart::End)
+        ));
+        let time = view.fraction_at(100.0, narrow.width) * view.duration;
+        assert!((time - 2.0).abs() < 1e-6, "{time}");
+        let time = view.fraction_at(300.0, narrow.width) * view.duration;
+        assert!((time - 6.0).abs() < 1e-6, "{time}");
+    }
+
+    #[test]
+    fn beat_flash_is_brighter_on_downbeats_and_fades() {
+        let beats = [0.0, 0.5, 1.0, 1.5, 2.0];
+        let downbeat = beat_flash(&beats, 2.0, 1.0, 4).unwrap();
+        let offbeat = beat_flash(&beats, 0.5, 1.0, 4).unwrap();
+        assert_eq!(downbeat, 1.0);
+        assert_eq!(offbeat, OFFBEAT_FLASH);
+        assert!(beat_flash(&beats, 2.05, 1.0, 4).unwrap() < downbeat);
+        assert_eq!(beat_flash(&beats, 2.2, 1.0, 4), None);
+        // At half tempo the same stretch of file takes twice as long to hear
+        assert_eq!(beat_flash(&beats, 2.1, 0.5, 4), None);
+        assert!(beat_flash(&beats, 2.1, 2.0, 4).is_some());
+        assert_eq!(beat_flash(&beats, -0.1, 1.0, 4), None);
+    }
+
+    /// Time `runs` draws into a fresh 3000-px frame on the software renderer.
+    fn time_draws(runs: u32, draw: impl Fn(&mut Frame)) -> Duration {
+        let renderer = iced::Renderer::Secondary(iced_tiny_skia::Renderer::new(
+            iced::Font::DEFAULT,
+            iced::Pixels(16.0),
+        ));
+        let start = Instant::now();
+        for _ in 0..runs {
+            let mut frame = Frame::new(&renderer, iced::Size::new(3000.0, 200.0));
+            draw(&mut frame);
+            std::hint::black_box(frame.into_geometry());
+        }
+        start.elapsed()
+    }
+
+    /// Run with `cargo test --release -- --ignored draw_layer`.
+    #[test]
+    #[ignore]
+    fn draw_layer_beats_a_path_per_column_at_3000_px() {
+        const RUNS: u32 = 200;
+        let spans: Vec<(f32, f32)> = (0..3000)
+            .map(|i| {
+                let level = ((i as f32 * 0.05).sin() * 0.5 + 0.5) * 90.0;
+                (100.0 - level, 100.0 + level)
+            })
+            .collect();
+        let color = Color::WHITE;
+
+        // What draw_layer replaced: one stroked path per column
+        let per_column = time_draws(RUNS, |frame| {
+            for (i, &(top, bottom)) in spans.iter().enumerate() {
+                let column = Path::line(
+                    iced::Point::new(i as f32, top),
+                    iced::Point::new(i as f32, bottom),
+                );
+                frame.stroke(&column, Stroke::default().with_color(color).with_width(1.0));
+            }
+        });
+
+        let mut view = WaveformView::new();
+        let lines = time_draws(RUNS, |frame| view.draw_layer(frame, &spans, color));
+        view.style = WaveformStyle::Filled;
+        let filled = time_draws(RUNS, |frame| view.draw_layer(frame, &spans, color));
+
+        let per_draw = |total: Duration| total / RUNS;
+        println!(
+            "3000 px: per column {:?}, lines {:?}, filled {:?}",
+            per_draw(per_column),
+            per_draw(lines),
+            per_draw(filled),
+        );
+        assert!(lines < per_column, "{lines:?} vs {per_column:?}");
+        assert!(filled < per_column, "{filled:?} vs {per_column:?}");
+    }
 }
@@ -1,10 +1,18 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
 use crate::audio::types::AudioData;
 
-/// A single peak entry: min and max sample values for a range of frames.
-#[derive(Clone, Copy, Debug)]
+/// A single peak entry: min and max sample values for a range of frames,
+/// plus their RMS level.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Peak {
     pub min: f32,
     pub max: f32,
+    pub rms: f32,
 }
 
 /// Pre-computed peaks at multiple resolutions for efficient waveform rendering.
@@ -14,86 +22,165 @@ pub struct WaveformPeaks {
     pub levels: Vec<(usize, Vec<Peak>)>,
 }
 
-/// Resolution levels: number of mono samples per peak.
+/// Resolution levels: number of mono samples per peak. Each is a multiple of
+/// the one before, so coarser levels can be built from finer ones.
 const RESOLUTIONS: &[usize] = &[64, 256, 1024, 4096];
 
+/// Fewest samples worth scanning on a thread of their own.
+const MIN_SAMPLES_PER_THREAD: usize = 1 << 20;
+
 #[allow(dead_code)]
 impl WaveformPeaks {
-    /// Compute peaks from audio data at multiple resolutions.
-    pub fn compute(audio: &AudioData) -> Self {
-        let mono = audio.to_mono();
-        let levels = RESOLUTIONS
-            .iter()
-            .map(|&spp| {
-                let peaks = compute_peaks_at_resolution(&mono, spp);
-                (spp, peaks)
+    /// Compute peaks from audio data at multiple resolutions. With the `source`
+    /// file given, peaks cached on disk for it are used when still current,
+    /// and freshly computed ones are cached.
+    pub fn compute(audio: &AudioData, source: Option<&Path>) -> Self {
+        let cache = source.and_then(|path| {
+            let meta = std::fs::metadata(path).ok()?;
+            Some(PeakCache {
+                path: cache_path(path)?,
+                len: meta.len(),
+                modified: meta.modified().ok()?,
+                frames: audio.num_frames() as u64,
             })
-            .collect();
-        WaveformPeaks { levels }
+        });
+        Self::compute_cached(audio, cache.as_ref())
     }
 
-    /// Get the best resolution level for the given canvas width and audio length.
-    pub fn best_level(&self, canvas_width: f32, total_frames: usize) -> &[(usize, Vec<Peak>)] {
-        // We want roughly 1-2 peaks per pixel
-        let target_spp = total_frames as f32 / canvas_width;
+    /// Compute peaks, reading them from `cache` instead when it holds ones
+    /// for this audio, and writing them to it otherwise.
+    fn compute_cached(audio: &AudioData, cache: Option<&PeakCache>) -> Self {
+        if let Some(levels) = cache.and_then(PeakCache::read) {
+            tracing::debug!("using cached waveform peaks");
+            return WaveformPeaks { levels };
+        }
 
-        // Find the level with samples_per_peak closest to target
-        // but not so fine that we'd have way too many peaks
-        let _ = target_spp;
-        &self.levels
-    }
+        let peaks = WaveformPeaks {
+            levels: compute_levels(&audio.to_mono()),
+        };
 
-    /// Get peaks for rendering at the given width.
-    /// Returns a Vec of peaks, one per pixel column.
-    pub fn peaks_for_width(&self, canvas_width: f32, total_frames: usize) -> Vec<Peak> {
-        if total_frames == 0 || canvas_width <= 0.0 {
-            return Vec::new();
+        if let Some(cache) = cache {
+            if let Err(e) = cache.write(&peaks.levels) {
+                tracing::warn!(error = %e, "failed to cache waveform peaks");
+            }
         }
+        peaks
+    }
 
+    /// Get the resolution level to draw `total_frames` frames across
+    /// `canvas_width` pixels: the coarsest one that still gives at least one
+    /// peak per pixel, or the finest when even that is too coarse.
+    pub fn best_level(&self, canvas_width: f32, total_frames: usize) -> &(usize, Vec<Peak>) {
         let target_spp = total_frames as f32 / canvas_width;
-
-        // Find the best resolution level
-        let (_, base_peaks) = self
-            .levels
+        self.levels
             .iter()
             .rev()
-            .find(|(spp, _)| (*spp as f32) <= target_spp * 2.0)
-            .unwrap_or(&self.levels[0]);
+            .find(|(spp, _)| (*spp as f32) <= target_spp)
+            .unwrap_or(&self.levels[0])
+    }
+
+    /// Get peaks for rendering the `(start, end)` window of the file, given
+    /// as fractions, at the given width.
+    /// Returns a Vec of peaks, one per pixel column.
+    pub fn peaks_for_width(
+        &self,
+        canvas_width: f32,
+        total_frames: usize,
+        window: (f64, f64),
+    ) -> Vec<Peak> {
+        let (window_start, window_end) = window;
+        let span = window_end - window_start;
+        if total_frames == 0 || canvas_width <= 0.0 || span <= 0.0 {
+            return Vec::new();
+        }
+
+        let visible_frames = (total_frames as f64 * span).round() as usize;
+        let (_, base_peaks) = self.best_level(canvas_width, visible_frames);
 
         // Resample peaks to exactly canvas_width entries
         let width = canvas_width as usize;
         let mut result = Vec::with_capacity(width);
 
         for i in 0..width {
-            let frac_start = i as f64 / width as f64;
-            let frac_end = (i + 1) as f64 / width as f64;
+            let frac_start = window_start + i as f64 / width as f64 * span;
+            let frac_end = window_start + (i + 1) as f64 / width as f64 * span;
             let peak_start = (frac_start * base_peaks.len() as f64) as usize;
-            let peak_end =
-                ((frac_end * base_peaks.len() as f64) as usize).min(base_peaks.len());
+            let peak_end = ((frac_end * base_peaks.len() as f64) as usize).min(base_peaks.len());
 
             if peak_start >= base_peaks.len() {
-                result.push(Peak { min: 0.0, max: 0.0 });
+                result.push(Peak {
+                    min: 0.0,
+                    max: 0.0,
+                    rms: 0.0,
+                });
                 continue;
             }
 
             let mut min = f32::MAX;
             let mut max = f32::MIN;
-            for p in &base_peaks[peak_start..peak_end.max(peak_start + 1)] {
+            let mut sum_squares = 0.0;
+            let span = &base_peaks[peak_start..peak_end.max(peak_start + 1)];
+            for p in span {
                 min = min.min(p.min);
                 max = max.max(p.max);
+                sum_squares += p.rms * p.rms;
             }
-            result.push(Peak { min, max });
+            let rms = (sum_squares / span.len() as f32).sqrt();
+            result.push(Peak { min, max, rms });
         }
 
         result
     }
 }
 
+/// Peaks of `mono` at every resolution. Only the finest level scans the
+/// samples; each coarser one merges the peaks of the level before it.
+fn compute_levels(mono: &[f32]) -> Vec<(usize, Vec<Peak>)> {
+    let mut levels: Vec<(usize, Vec<Peak>)> = Vec::with_capacity(RESOLUTIONS.len());
+    for &spp in RESOLUTIONS {
+        let peaks = match levels.last() {
+            Some((finer_spp, finer)) => merge_peaks(finer, spp / finer_spp, *finer_spp, mono.len()),
+            None => compute_peaks_at_resolution(mono, spp),
+        };
+        levels.push((spp, peaks));
+    }
+    levels
+}
+
+/// Scan `mono` for peaks, splitting long files across threads. Each thread
+/// gets a whole number of peaks, so the result is the same as one scan.
 fn compute_peaks_at_resolution(mono: &[f32], samples_per_peak: usize) -> Vec<Peak> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let peaks_per_thread = mono.len().div_ceil(samples_per_peak).div_ceil(threads);
+    let part_len = (peaks_per_thread * samples_per_peak)
+        .max(MIN_SAMPLES_PER_THREAD.next_multiple_of(samples_per_peak));
+    scan_peaks_in_parts(mono, samples_per_peak, part_len)
+}
+
+/// Scan `mono` on one thread per `part_len` samples, which must be a
+/// multiple of `samples_per_peak`.
+fn scan_peaks_in_parts(mono: &[f32], samples_per_peak: usize, part_len: usize) -> Vec<Peak> {
+    if mono.len() <= part_len {
+        return scan_peaks(mono, samples_per_peak);
+    }
+    std::thread::scope(|scope| {
+        let parts: Vec<_> = mono
+            .chunks(part_len)
+            .map(|part| scope.spawn(move || scan_peaks(part, samples_per_peak)))
+            .collect();
+        parts
+            .into_iter()
+            .flat_map(|part| part.join().expect("peak scan thread panicked"))
+            .collect()
+    })
+}
+
+fn scan_peaks(mono: &[f32], samples_per_peak: usize) -> Vec<Peak> {
     mono.chunks(samples_per_peak)
         .map(|chunk| {
             let mut min = f32::MAX;
             let mut max = f32::MIN;
+            let mut sum_squares = 0.0;
             for &s in chunk {
                 if s < min {
                     min = s;
@@ -101,8 +188,262 @@ fn compute_peaks_at_resolution(mono: &[f32], samples_per_peak: usize) -> Vec<Pea
                 if s > max {
                     max = s;
                 }
+                sum_squares += s * s;
+            }
+            let rms = (sum_squares / chunk.len() as f32).sqrt();
+            Peak { min, max, rms }
+        })
+        .collect()
+}
+
+/// Merge every `factor` consecutive peaks of a level with `finer_spp` samples
+/// per peak into one, giving the same peaks as scanning `total_samples`
+/// samples at `factor` times the resolution. The RMS is weighted by how many
+/// samples each peak covers, since the last one may be short.
+fn merge_peaks(finer: &[Peak], factor: usize, finer_spp: usize, total_samples: usize) -> Vec<Peak> {
+    finer
+        .chunks(factor)
+        .enumerate()
+        .map(|(i, group)| {
+            let first_sample = i * factor * finer_spp;
+            let mut min = f32::MAX;
+            let mut max = f32::MIN;
+            let mut sum_squares = 0.0;
+            let mut samples = 0;
+            for (j, p) in group.iter().enumerate() {
+                let start = first_sample + j * finer_spp;
+                let len = finer_spp.min(total_samples - start);
+                min = min.min(p.min);
+                max = max.max(p.max);
+                sum_squares += p.rms * p.rms * len as f32;
+                samples += len;
             }
-            Peak { min, max }
+            let rms = (sum_squares / samples as f32).sqrt();
+            Peak { min, max, rms }
         })
         .collect()
 }
+
+/// Bumped whenever `Peak` or the cache layout changes, so older cache files
+/// are recomputed.
+const CACHE_VERSION: u32 = 3;
+
+/// Peaks stored on disk, with the size and modification time of the file they
+/// were computed from so a changed file is noticed, and the number of frames
+/// decoded from it, which differs when a damaged file is decoded under
+/// another policy. `L` is borrowed when writing, so the levels aren't copied.
+#[derive(Serialize, Deserialize)]
+struct CachedPeaks<L> {
+    version: u32,
+    len: u64,
+    modified: SystemTime,
+    frames: u64,
+    levels: L,
+}
+
+/// The cache file for one source, and what the cached peaks must have been
+/// computed from to still be current.
+struct PeakCache {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+    frames: u64,
+}
+
+impl PeakCache {
+    /// The cached peaks, or `None` if they are missing, unreadable, stale or
+    /// at other resolutions.
+    fn read(&self) -> Option<Vec<(usize, Vec<Peak>)>> {
+        let bytes = std::fs::read(&self.path).ok()?;
+        let cached: CachedPeaks<Vec<(usize, Vec<Peak>)>> = match bincode::deserialize(&bytes) {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!(error = %e, "ignoring unreadable waveform cache");
+                return None;
+            }
+        };
+        let current = cached.version == CACHE_VERSION
+            && cached.len == self.len
+            && cached.modified == self.modified
+            && cached.frames == self.frames
+            && cached
+                .levels
+                .iter()
+                .map(|(spp, _)| *spp)
+                .eq(RESOLUTIONS.iter().copied());
+        current.then_some(cached.levels)
+    }
+
+    fn write(&self, levels: &[(usize, Vec<Peak>)]) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create cache directory: {e}"))?;
+        }
+        let cached = CachedPeaks {
+            version: CACHE_VERSION,
+            len: self.len,
+            modified: self.modified,
+            frames: self.frames,
+            levels,
+        };
+        let bytes = bincode::serialize(&cached)
+            .map_err(|e| format!("Failed to serialize waveform peaks: {e}"))?;
+        std::fs::write(&self.path, bytes)
+            .map_err(|e| format!("Failed to write waveform cache: {e}"))
+    }
+}
+
+/// Where the peaks of `source` are cached: one file per source path in the
+/// platform cache directory, overwritten when the source changes.
+fn cache_path(source: &Path) -> Option<PathBuf> {
+    let name = format!(
+        "{:016x}.peaks",
+        stable_hash(source.as_os_str().as_encoded_bytes())
+    );
+    ProjectDirs::from("", "", "transcribe").map(|dirs| dirs.cache_dir().join("peaks").join(name))
+}
+
+/// 64-bit FNV-1a hash of `bytes`. Unlike `DefaultHasher` it is the same on
+/// every build, so file names derived from it outlive an upgrade.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A deterministic, irregular signal so every peak differs.
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (i as f32 * 0.013).sin() * (i as f32 * 0.0007).cos())
+            .collect()
+    }
+
+    fn assert_peaks_match(merged: &[Peak], scanned: &[Peak], spp: usize) {
+        assert_eq!(merged.len(), scanned.len(), "peak count at {spp} spp");
+        for (i, (m, s)) in merged.iter().zip(scanned).enumerate() {
+            assert_eq!(m.min, s.min, "min of peak {i} at {spp} spp");
+            assert_eq!(m.max, s.max, "max of peak {i} at {spp} spp");
+            assert!(
+                (m.rms - s.rms).abs() < 1e-4,
+                "rms of peak {i} at {spp} spp: {} vs {}",
+                m.rms,
+                s.rms
+            );
+        }
+    }
+
+    #[test]
+    fn merged_levels_match_direct_scans() {
+        // Neither a multiple of the coarsest resolution nor of the finest
+        for len in [3 * 4096 + 1000 + 7, 4096, 100] {
+            let mono = signal(len);
+            let levels = compute_levels(&mono);
+            for (spp, merged) in &levels[1..] {
+                assert_peaks_match(merged, &scan_peaks(&mono, *spp), *spp);
+            }
+        }
+    }
+
+    #[test]
+    fn threaded_scan_matches_single_scan() {
+        let mono = signal(5 * 4096 + 1234);
+        for &spp in RESOLUTIONS {
+            let threaded = scan_peaks_in_parts(&mono, spp, 4096);
+            assert_peaks_match(&threaded, &scan_peaks(&mono, spp), spp);
+        }
+        let mono = signal(MIN_SAMPLES_PER_THREAD + 99);
+        assert_peaks_match(
+            &compute_peaks_at_resolution(&mono, 64),
+            &scan_peaks(&mono, 64),
+            64,
+        );
+    }
+
+    #[test]
+    fn stable_hash_is_fnv_1a() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    fn audio(frames: usize) -> AudioData {
+        AudioData {
+            samples: signal(frames),
+            sample_rate: 44100,
+            channels: 1,
+            duration: frames as f64 / 44100.0,
+        }
+    }
+
+    fn temp_cache(name: &str, frames: usize) -> PeakCache {
+        PeakCache {
+            path: std::env::temp_dir().join(format!("{name}-{}.peaks", std::process::id())),
+            len: 1234,
+            modified: SystemTime::UNIX_EPOCH,
+            frames: frames as u64,
+        }
+    }
+
+    #[test]
+    fn corrupt_or_stale_cache_is_recomputed() {
+        let audio = audio(10_000);
+        let expected = compute_levels(&audio.samples);
+
+        let corrupt = temp_cache("corrupt", 10_000);
+        std::fs::write(&corrupt.path, b"not a peak cache").unwrap();
+        assert!(corrupt.read().is_none());
+        let peaks = WaveformPeaks::compute_cached(&audio, Some(&corrupt));
+        assert_peaks_match(&peaks.levels[0].1, &expected[0].1, 64);
+        // The recomputed peaks replace the corrupt file
+        assert!(corrupt.read().is_some());
+        std::fs::remove_file(&corrupt.path).unwrap();
+
+        // Peaks of a shorter decode of the same file, e.g. under another policy
+        let stale = temp_cache("stale", 10_000);
+        let shorter = temp_cache("stale", 8_000);
+        shorter.write(&compute_levels(&signal(8_000))).unwrap();
+        assert!(stale.read().is_none());
+        let peaks = WaveformPeaks::compute_cached(&audio, Some(&stale));
+        assert_peaks_match(&peaks.levels[0].1, &expected[0].1, 64);
+        assert!(shorter.read().is_none());
+        std::fs::remove_file(&stale.path).unwrap();
+    }
+
+    fn empty_levels() -> WaveformPeaks {
+        WaveformPeaks {
+            levels: RESOLUTIONS.iter().map(|&spp| (spp, Vec::new())).collect(),
+        }
+    }
+
+    #[test]
+    fn best_level_is_coarsest_with_a_peak_per_pixel() {
+        let peaks = empty_levels();
+        for (width, total_frames, expected) in [
+            (1000.0, 64_000, 64),
+            (1000.0, 255_999, 64),
+            (1000.0, 256_000, 256),
+            (1000.0, 1_000_000, 256),
+            (800.0, 819_200, 1024),
+            (1000.0, 4_096_000, 4096),
+            (3000.0, 100_000_000, 4096),
+        ] {
+            let (spp, _) = peaks.best_level(width, total_frames);
+            assert_eq!(*spp, expected, "{total_frames} frames across {width} px");
+        }
+    }
+
+    #[test]
+    fn best_level_falls_back_to_finest_when_zoomed_in() {
+        let peaks = empty_levels();
+        // Fewer than 64 frames per pixel: no level is fine enough
+        for (width, total_frames) in [(1000.0, 63_999), (1000.0, 5_000), (3000.0, 100), (500.0, 0)]
+        {
+            let (spp, _) = peaks.best_level(width, total_frames);
+            assert_eq!(*spp, 64, "{total_frames} frames across {width} px");
+        }
+    }
+}